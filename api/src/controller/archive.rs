@@ -0,0 +1,354 @@
+#![allow(non_snake_case)]
+
+use super::{
+    escape_html,
+    Clock,
+    Event,
+    EventColumns,
+    InsertThread,
+    LaunchSync,
+    Page,
+    PostAt,
+    RenderFormat,
+    Repository,
+    Section,
+    TemplateSection,
+    TemplateSections,
+    Thread,
+    ThreadState,
+    TimeFormat,
+    TransferredEvent,
+    UpdateThread,
+    User,
+    UserFilter,
+    Webhooks,
+};
+use crate::Database;
+use derive_more::Display;
+use rocket_contrib::databases::diesel::{result::Error as DieselError, QueryResult};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error};
+
+/// The version of the bundle format produced by `Thread::export`.
+/// Bundles of any other version cannot be imported.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// A self-contained record of a thread,
+/// independent of both Reddit and the environment it was exported from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThreadBundle {
+    pub version:     u32,
+    /// When the bundle was created (a UNIX timestamp).
+    pub exported_at: i64,
+    pub thread:      BundledThread,
+    /// In the order they appear in the thread.
+    pub sections:    Vec<BundledSection>,
+    /// In the order they appear in the thread.
+    pub events:      Vec<TransferredEvent>,
+    /// Everyone referenced by the thread: its creator and anyone who scrubbed it.
+    /// Sections and events do not record who edited them, so are not attributed.
+    pub authors:     Vec<Author>,
+}
+
+/// The fields of a `Thread` that are meaningful outside of the environment it was created in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BundledThread {
+    pub thread_name: String,
+    pub display_name: String,
+    pub post_id: Option<String>,
+    pub subreddit: Option<String>,
+    pub space__t0: Option<i64>,
+    pub video_url: Option<String>,
+    pub spacex__api_id: Option<String>,
+    pub created_by_user_id: i32,
+    pub event_columns: EventColumns,
+    pub time_format: TimeFormat,
+    /// The full history of the countdown, including holds and scrubs.
+    pub clock: Clock,
+    pub state: ThreadState,
+}
+
+/// A `Section` as stored in a bundle.
+///
+/// The ID is only used to restore references to the section
+/// (such as from an archived scrub).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BundledSection {
+    pub id:      i32,
+    pub name:    String,
+    pub content: String,
+    pub kind:    String,
+    pub data:    serde_json::Value,
+}
+
+/// A user referenced by a bundle, by their ID in the environment it was exported from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Author {
+    pub id: i32,
+    pub reddit_username: String,
+}
+
+/// Why a bundle could not be imported.
+#[derive(Debug, Display)]
+pub enum ImportError {
+    #[display(fmt = "unsupported bundle version {}", _0)]
+    Version(u32),
+    #[display(fmt = "invalid bundle: {}", _0)]
+    Invalid(String),
+    #[display(fmt = "{}", _0)]
+    Database(DieselError),
+}
+
+impl Error for ImportError {}
+
+impl From<DieselError> for ImportError {
+    fn from(err: DieselError) -> Self {
+        ImportError::Database(err)
+    }
+}
+
+impl From<&Section> for BundledSection {
+    fn from(section: &Section) -> Self {
+        BundledSection {
+            id:      section.id,
+            name:    section.name.clone(),
+            content: section.content.clone(),
+            kind:    section.kind.clone(),
+            data:    section.data.clone(),
+        }
+    }
+}
+
+impl From<&BundledSection> for TemplateSection {
+    fn from(section: &BundledSection) -> Self {
+        TemplateSection {
+            name:    section.name.clone(),
+            content: section.content.clone(),
+            kind:    section.kind.clone(),
+            data:    section.data.clone(),
+        }
+    }
+}
+
+impl ThreadBundle {
+    /// Ensure the bundle can be restored,
+    /// with every section and event valid for the thread's schema.
+    fn check(&self) -> Result<(), ImportError> {
+        if self.version != BUNDLE_VERSION {
+            return Err(ImportError::Version(self.version));
+        }
+
+        let columns = &self.thread.event_columns;
+        columns
+            .check()
+            .map_err(|e| ImportError::Invalid(e.to_string()))?;
+        self.thread
+            .time_format
+            .check()
+            .map_err(|e| ImportError::Invalid(e.to_string()))?;
+        TemplateSections(self.sections.iter().map(Into::into).collect())
+            .check()
+            .map_err(|e| ImportError::Invalid(e.to_string()))?;
+        for event in &self.events {
+            columns
+                .validate(&event.cols)
+                .map_err(|e| ImportError::Invalid(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the local ID of each author with an account in this environment,
+/// keyed by their ID in the bundle.
+fn local_authors(conn: &Database, authors: &[Author]) -> QueryResult<HashMap<i32, i32>> {
+    let mut local_ids = HashMap::new();
+
+    for author in authors {
+        let filter = UserFilter {
+            reddit_username: Some(author.reddit_username.clone()),
+        };
+        if let Some(user) = User::find_all(conn, &filter, None, Page::new(Some(1), None))?
+            .into_iter()
+            .next()
+        {
+            local_ids.insert(author.id, user.id);
+        }
+    }
+
+    Ok(local_ids)
+}
+
+impl Thread {
+    /// Create a self-contained record of the thread,
+    /// including its sections, events, authors, and the history of its countdown.
+    pub fn export(conn: &Database, id: i32, now: i64) -> QueryResult<ThreadBundle> {
+        let thread = Thread::find_id(conn, id)?;
+        let sections = Section::find_ids(conn, &thread.sections_id)?.values;
+        let events = Event::find_ids(conn, &thread.events_id)?.values;
+
+        let mut authors_id = vec![thread.created_by_user_id];
+        for scrub in &thread.clock.scrubs {
+            if !authors_id.contains(&scrub.scrubbed_by_user_id) {
+                authors_id.push(scrub.scrubbed_by_user_id);
+            }
+        }
+        let authors = User::find_ids(conn, &authors_id)?
+            .values
+            .into_iter()
+            .map(|user| Author {
+                id: user.id,
+                reddit_username: user.reddit_username,
+            })
+            .collect();
+
+        Ok(ThreadBundle {
+            version: BUNDLE_VERSION,
+            exported_at: now,
+            thread: BundledThread {
+                thread_name: thread.thread_name,
+                display_name: thread.display_name,
+                post_id: thread.post_id,
+                subreddit: thread.subreddit,
+                space__t0: thread.space__t0,
+                video_url: thread.video_url,
+                spacex__api_id: thread.spacex__api_id,
+                created_by_user_id: thread.created_by_user_id,
+                event_columns: thread.event_columns,
+                time_format: thread.time_format,
+                clock: thread.clock,
+                state: thread.state,
+            },
+            sections: sections.iter().map(Into::into).collect(),
+            events: events.iter().map(Into::into).collect(),
+            authors,
+        })
+    }
+
+    /// Render the thread as a standalone document,
+    /// titled with the name of the thread.
+    pub fn export_document(
+        &self,
+        conn: &Database,
+        format: RenderFormat,
+    ) -> Result<String, Box<dyn Error>> {
+        let body = Thread::render(conn, self.id, format)?;
+
+        Ok(match format {
+            RenderFormat::Markdown => format!("# {}\n\n{}", self.thread_name, body),
+            RenderFormat::Html => format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                 <title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n\
+                 {body}\n</body>\n</html>\n",
+                title = escape_html(&self.thread_name),
+                body = body,
+            ),
+        })
+    }
+
+    /// Restore a bundle into a new thread, owned by the provided user.
+    ///
+    /// The new thread is not posted to Reddit.
+    /// It remains concluded or archived if the original was; otherwise it is a draft.
+    /// Unposted events remain scheduled only if relative to T-0,
+    /// as a fixed time would already have passed.
+    /// Scrubs are attributed to the same Reddit user if they have an account,
+    /// and to the importing user otherwise.
+    pub fn import(
+        conn: &Database,
+        bundle: &ThreadBundle,
+        user_id: i32,
+    ) -> Result<Self, ImportError> {
+        bundle.check()?;
+
+        let authors = local_authors(conn, &bundle.authors)?;
+        let mut clock = bundle.thread.clock.clone();
+        for scrub in &mut clock.scrubs {
+            scrub.scrubbed_by_user_id = authors
+                .get(&scrub.scrubbed_by_user_id)
+                .copied()
+                .unwrap_or(user_id);
+        }
+
+        let state = match bundle.thread.state {
+            ThreadState::Concluded | ThreadState::Archived => bundle.thread.state,
+            _ => ThreadState::Draft,
+        };
+        let insertable_thread = InsertThread {
+            thread_name: bundle.thread.thread_name.clone(),
+            display_name: bundle.thread.display_name.clone(),
+            post_id: None,
+            subreddit: bundle.thread.subreddit.clone(),
+            space__t0: bundle.thread.space__t0,
+            video_url: bundle.thread.video_url.clone(),
+            spacex__api_id: bundle.thread.spacex__api_id.clone(),
+            created_by_user_id: user_id,
+            sections_id: vec![],
+            events_id: vec![],
+            is_live: false,
+            event_columns: bundle.thread.event_columns.clone(),
+            time_format: bundle.thread.time_format.clone(),
+            clock: clock.clone(),
+            launch_sync: LaunchSync::default(),
+            state,
+            scheduled_at: None,
+            webhooks: Webhooks::default(),
+        };
+
+        let sections: Vec<TemplateSection> = bundle.sections.iter().map(Into::into).collect();
+        let events: Vec<TransferredEvent> = bundle
+            .events
+            .iter()
+            .map(|event| TransferredEvent {
+                cols:    event.cols.clone(),
+                posted:  event.posted,
+                post_at: match event.post_at {
+                    Some(PostAt::TMinus(_)) => event.post_at,
+                    post_at if event.posted => post_at,
+                    _ => None,
+                },
+            })
+            .collect();
+
+        // Sections archived by a scrub are referenced by ID,
+        // which are only known once the sections have been created.
+        let archives_sections = clock
+            .scrubs
+            .iter()
+            .any(|scrub| scrub.archive_section_id.is_some());
+
+        Ok(Thread::create_with(
+            conn,
+            &insertable_thread,
+            &sections,
+            &events,
+            |created_sections| {
+                if !archives_sections {
+                    return UpdateThread::default();
+                }
+
+                let section_ids: HashMap<_, _> = bundle
+                    .sections
+                    .iter()
+                    .map(|section| section.id)
+                    .zip(created_sections.iter().map(|section| section.id))
+                    .collect();
+                let mut clock = clock.clone();
+                for scrub in &mut clock.scrubs {
+                    scrub.archive_section_id = scrub
+                        .archive_section_id
+                        .and_then(|id| section_ids.get(&id).copied());
+                }
+
+                UpdateThread {
+                    clock: Some(clock),
+                    ..UpdateThread::default()
+                }
+            },
+        )?)
+    }
+}
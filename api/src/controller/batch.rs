@@ -0,0 +1,257 @@
+use super::{
+    invalidate,
+    CacheKey,
+    ColumnError,
+    Event,
+    Repository,
+    Thread,
+    UpdateEvent,
+    UpdateThread,
+};
+use crate::{
+    schema::thread,
+    websocket::{Action, DataType, Message, Room},
+    Database,
+};
+use derive_more::Display;
+use rocket_contrib::databases::diesel::{
+    result::Error as DieselError,
+    Connection,
+    QueryDsl,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, error::Error};
+
+/// A single operation on an `Event`, as part of a batch.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum EventOperation {
+    /// Update any fields of the event, as with a full `PATCH`.
+    Patch {
+        id:      i32,
+        #[serde(default)]
+        version: Option<i32>,
+        data:    UpdateEvent,
+    },
+    /// Update individual columns of the event, as with a partial `PATCH`.
+    PartialPatch {
+        id:      i32,
+        #[serde(default)]
+        version: Option<i32>,
+        cols:    Vec<(usize, Value)>,
+    },
+    /// Delete the event.
+    Delete { id: i32 },
+    /// Move the event to the provided position in the thread.
+    Move { id: i32, to: usize },
+}
+
+/// Why a batch of operations could not be applied.
+/// When this occurs, none of the operations are applied.
+#[derive(Debug, Display)]
+pub enum BatchError {
+    #[display(fmt = "event {} is not in the thread", _0)]
+    NotInThread(i32),
+    #[display(fmt = "event {} has been modified", _0)]
+    VersionMismatch(i32),
+    #[display(fmt = "event {}: {}", id, error)]
+    Column { id: i32, error: ColumnError },
+    #[display(fmt = "{}", _0)]
+    Database(DieselError),
+}
+
+impl Error for BatchError {}
+
+impl From<DieselError> for BatchError {
+    fn from(err: DieselError) -> Self {
+        BatchError::Database(err)
+    }
+}
+
+/// The result of applying a batch of operations to the events of a thread.
+#[derive(Clone, Serialize)]
+pub struct BatchResult {
+    /// Events that were updated (and not later deleted), in their final state.
+    pub updated:   Vec<Event>,
+    /// IDs of the events that were deleted.
+    pub deleted:   Vec<i32>,
+    /// The order of events in the thread after the batch was applied.
+    pub events_id: Vec<i32>,
+}
+
+impl BatchResult {
+    /// Store the changes in the global cache,
+    /// informing other instances and WebSocket clients (unless the thread is a draft) of them.
+    ///
+    /// Anything making changes to events directly (rather than via `Event::apply_batch`)
+    /// must call this once the changes are committed.
+    pub fn publish(&self, conn: &Database, thread_id: i32) {
+        for event in &self.updated {
+            Event::cache().insert(event.id, event.clone());
+            invalidate(conn, CacheKey::Event(event.id));
+        }
+        for &id in &self.deleted {
+            Event::cache().remove(id);
+            invalidate(conn, CacheKey::Event(id));
+        }
+
+        if Thread::has_public_contents(conn, thread_id) {
+            let _ = Message {
+                room:      Room::Thread(thread_id),
+                action:    Action::Batch,
+                data_type: DataType::Event,
+                data:      self,
+            }
+            .send();
+        }
+    }
+}
+
+impl Event {
+    /// Apply a number of operations to the events of a single thread, in order.
+    ///
+    /// Either all operations are applied or none are.
+    /// Columns are validated against the thread's schema as with individual updates,
+    /// and versions (where provided) are checked against the current state of the batch.
+    ///
+    /// Once applied, WebSocket clients receive a single `batch` message
+    /// containing the `BatchResult`,
+    /// and any events that were posted are sent to the thread's webhooks.
+    pub fn apply_batch(
+        conn: &Database,
+        thread_id: i32,
+        operations: &[EventOperation],
+    ) -> Result<BatchResult, BatchError> {
+        let (result, thread, unposted) = conn.transaction::<_, BatchError, _>(|| {
+            // The thread is locked until the batch is committed,
+            // so that its `events_id` cannot change before being rewritten below.
+            let thread: Thread = thread::table.find(thread_id).for_update().first(conn)?;
+            let mut events_id = thread.events_id.clone();
+            let mut events: HashMap<_, _> = Event::load_ids(conn, &events_id)?
+                .into_iter()
+                .map(|event| (event.id, event))
+                .collect();
+            let unposted: Vec<_> = events
+                .values()
+                .filter(|event| !event.posted)
+                .map(|event| event.id)
+                .collect();
+            let mut deleted = vec![];
+
+            for operation in operations {
+                let id = match operation {
+                    EventOperation::Patch { id, .. }
+                    | EventOperation::PartialPatch { id, .. }
+                    | EventOperation::Delete { id }
+                    | EventOperation::Move { id, .. } => *id,
+                };
+                let event = events.get(&id).ok_or(BatchError::NotInThread(id))?;
+
+                let (version, mut data) = match operation {
+                    EventOperation::Patch { version, data, .. } => (*version, data.clone()),
+                    EventOperation::PartialPatch { version, cols, .. } => (
+                        *version,
+                        UpdateEvent {
+                            cols: Some(
+                                thread
+                                    .event_columns
+                                    .apply_partial(&event.cols, cols)
+                                    .map_err(|error| BatchError::Column { id, error })?,
+                            ),
+                            ..UpdateEvent::default()
+                        },
+                    ),
+                    EventOperation::Delete { .. } => {
+                        Event::delete_row(conn, id)?;
+                        events.remove(&id);
+                        events_id.retain(|&cur_id| cur_id != id);
+                        deleted.push(id);
+                        continue;
+                    }
+                    EventOperation::Move { to, .. } => {
+                        events_id.retain(|&cur_id| cur_id != id);
+                        events_id.insert((*to).min(events_id.len()), id);
+                        continue;
+                    }
+                };
+
+                if version.map_or(false, |version| version != event.version) {
+                    return Err(BatchError::VersionMismatch(id));
+                }
+
+                if let Some(cols) = &data.cols {
+                    data.cols = Some(
+                        thread
+                            .event_columns
+                            .validate(cols)
+                            .map_err(|error| BatchError::Column { id, error })?,
+                    );
+                }
+
+                // The event may have been modified since it was loaded.
+                let updated = Event::update_row(conn, id, Some(event.version), &data).map_err(
+                    |err| match err {
+                        DieselError::NotFound => BatchError::VersionMismatch(id),
+                        err => err.into(),
+                    },
+                )?;
+                events.insert(id, updated);
+            }
+
+            let thread = if events_id == thread.events_id {
+                None
+            } else {
+                let changes = UpdateThread {
+                    events_id: Some(events_id.clone()),
+                    ..UpdateThread::default()
+                };
+                Some((
+                    Thread::update_row(conn, thread_id, None, &changes)?,
+                    changes,
+                ))
+            };
+
+            // Only events that were actually changed are returned.
+            let mut updated: Vec<_> = events
+                .into_iter()
+                .map(|(_, event)| event)
+                .filter(|event| {
+                    operations.iter().any(|operation| match operation {
+                        EventOperation::Patch { id, .. }
+                        | EventOperation::PartialPatch { id, .. } => *id == event.id,
+                        _ => false,
+                    })
+                })
+                .collect();
+            updated.sort_unstable_by_key(|event| event.id);
+
+            Ok((
+                BatchResult {
+                    updated,
+                    deleted,
+                    events_id,
+                },
+                thread,
+                unposted,
+            ))
+        })?;
+
+        result.publish(conn, thread_id);
+
+        if let Some((thread, changes)) = thread {
+            Thread::updated(conn, &thread, &changes);
+        }
+
+        for event in &result.updated {
+            if event.posted && unposted.contains(&event.id) {
+                if let Err(err) = event.notify_posted(conn) {
+                    eprintln!("error notifying webhooks of event {}: {}", event.id, err);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,373 @@
+use super::{
+    render::{clear_rendered, evict_rendered},
+    Event,
+    PresetEvent,
+    Repository,
+    Section,
+    Thread,
+    ThreadTemplate,
+    User,
+};
+use crate::{guid, Database};
+use derive_more::Display;
+use diesel::sql_types::Text;
+use fallible_iterator::FallibleIterator;
+use lru_cache::LruCache;
+use once_cell::sync::Lazy;
+use openssl::{
+    error::ErrorStack,
+    ssl::{SslConnector, SslMethod, SslVerifyMode},
+};
+use parking_lot::{Mutex, RwLock};
+use postgres::{tls::openssl::OpenSsl, TlsMode};
+use reqwest::Url;
+use rocket_contrib::databases::diesel::RunQueryDsl;
+use std::{
+    env,
+    error::Error,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// Declare the capacity of a cache, read from the environment variable of the same name
+/// when first used.
+macro_rules! cache_size {
+    ($name:ident, $default:expr) => {
+        pub(super) static $name: Lazy<usize> =
+            Lazy::new(|| cache_size(stringify!($name), $default));
+    };
+}
+
+/// Read the capacity of a cache from the provided environment variable,
+/// falling back to the provided default if it is not set (or is invalid).
+pub fn cache_size(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(default)
+}
+
+cache_size!(EVENT_CACHE_SIZE, 100);
+cache_size!(PRESET_EVENT_CACHE_SIZE, 50);
+cache_size!(RENDER_CACHE_SIZE, 10);
+cache_size!(SECTION_CACHE_SIZE, 50);
+cache_size!(THREAD_CACHE_SIZE, 5);
+cache_size!(THREAD_TEMPLATE_CACHE_SIZE, 20);
+cache_size!(USER_CACHE_SIZE, 100);
+
+/// The name of the Postgres channel used by `PgNotify`.
+const PG_CHANNEL: &str = "cache_invalidation";
+
+/// Uniquely identifies this process,
+/// allowing it to ignore invalidations it sent itself.
+static INSTANCE_ID: Lazy<String> = Lazy::new(|| guid!());
+
+/// The channel used to inform other instances of changes.
+/// Defaults to `NoInvalidation`, which is suitable for a single instance.
+static CHANNEL: Lazy<RwLock<Box<dyn InvalidationChannel>>> =
+    Lazy::new(|| RwLock::new(Box::new(NoInvalidation)));
+
+/// A global cache, containing a mapping of IDs to their respective values.
+///
+/// The values are protected by a `Mutex`,
+/// ensuring there is only ever at most one writer at a time.
+/// Note that even when reading,
+/// there must be a lock on mutability,
+/// as the `LruCache` must be able to update itself.
+///
+/// The number of hits and misses is recorded for telemetry.
+#[derive(Debug)]
+pub struct Cache<T> {
+    values: Mutex<LruCache<i32, T>>,
+    hits:   AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<T: Clone> Cache<T> {
+    /// Create an empty cache holding at most `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: Mutex::new(LruCache::new(capacity)),
+            hits:   AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get a copy of the value with the provided ID, if present.
+    pub fn get(&self, id: i32) -> Option<T> {
+        let value = self.values.lock().get_mut(&id).cloned();
+
+        if value.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        value
+    }
+
+    /// Insert a value, replacing any previous value with the same ID.
+    pub fn insert(&self, id: i32, value: T) {
+        self.values.lock().insert(id, value);
+    }
+
+    /// Remove the value with the provided ID, if present.
+    ///
+    /// This does _not_ inform any other instances.
+    pub fn remove(&self, id: i32) {
+        self.values.lock().remove(&id);
+    }
+
+    /// Remove all values.
+    ///
+    /// This does _not_ inform any other instances.
+    pub fn clear(&self) {
+        self.values.lock().clear();
+    }
+
+    /// The number of hits and misses since this was last called.
+    pub fn take_stats(&self) -> (usize, usize) {
+        (
+            self.hits.swap(0, Ordering::Relaxed),
+            self.misses.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+/// A single value that may be present in one of the global caches.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKey {
+    #[display(fmt = "event:{}", _0)]
+    Event(i32),
+    #[display(fmt = "preset_event:{}", _0)]
+    PresetEvent(i32),
+    #[display(fmt = "section:{}", _0)]
+    Section(i32),
+    #[display(fmt = "thread:{}", _0)]
+    Thread(i32),
+    #[display(fmt = "thread_template:{}", _0)]
+    ThreadTemplate(i32),
+    #[display(fmt = "user:{}", _0)]
+    User(i32),
+}
+
+impl FromStr for CacheKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut parts = string.splitn(2, ':');
+        let kind = parts.next().unwrap_or_default();
+        let id = match parts.next().map(str::parse) {
+            Some(Ok(id)) => id,
+            _ => return Err("invalid id"),
+        };
+
+        match kind {
+            "event" => Ok(CacheKey::Event(id)),
+            "preset_event" => Ok(CacheKey::PresetEvent(id)),
+            "section" => Ok(CacheKey::Section(id)),
+            "thread" => Ok(CacheKey::Thread(id)),
+            "thread_template" => Ok(CacheKey::ThreadTemplate(id)),
+            "user" => Ok(CacheKey::User(id)),
+            _ => Err("unknown cache"),
+        }
+    }
+}
+
+/// A means of informing other instances of the API that a value has changed,
+/// and should be evicted from their caches.
+pub trait InvalidationChannel: Send + Sync {
+    /// Publish the key of a value that has been created, updated, or deleted.
+    /// The connection should be that used to perform the change,
+    /// so the invalidation can be sent as part of any transaction.
+    fn publish(&self, conn: &Database, key: CacheKey);
+}
+
+/// Do not inform any other instances.
+/// This is only suitable when a single instance of the API is running.
+#[derive(Debug, Default)]
+pub struct NoInvalidation;
+
+impl InvalidationChannel for NoInvalidation {
+    fn publish(&self, _conn: &Database, _key: CacheKey) {}
+}
+
+/// Inform other instances using Postgres' `LISTEN`/`NOTIFY`.
+///
+/// Notifications are sent over the existing connection pool,
+/// and are therefore only delivered once any transaction is committed.
+/// Each instance must be running `PgNotify::listen` to receive them.
+#[derive(Debug, Default)]
+pub struct PgNotify;
+
+impl InvalidationChannel for PgNotify {
+    fn publish(&self, conn: &Database, key: CacheKey) {
+        let _ = diesel::sql_query("SELECT pg_notify($1, $2)")
+            .bind::<Text, _>(PG_CHANNEL)
+            .bind::<Text, _>(format!("{} {}", *INSTANCE_ID, key))
+            .execute(conn);
+    }
+}
+
+impl PgNotify {
+    /// Listen for invalidations sent by other instances,
+    /// evicting the relevant values from the local caches.
+    ///
+    /// If the connection is lost, the caches are cleared and the connection is retried,
+    /// as any invalidations sent in the meantime will have been missed.
+    /// This function never returns.
+    pub fn listen(url: &str) -> ! {
+        loop {
+            if let Err(err) = Self::listen_once(url) {
+                eprintln!("cache invalidation listener disconnected: {}", err);
+            }
+
+            clear_all();
+            thread::sleep(Duration::from_secs(5));
+        }
+    }
+
+    /// Listen for invalidations until an error occurs.
+    fn listen_once(url: &str) -> Result<(), Box<dyn Error>> {
+        let (url, ssl_mode) = SslMode::from_url(url)?;
+        let tls = ssl_mode.handshake()?;
+        let tls_mode = match (ssl_mode, &tls) {
+            (SslMode::Prefer, Some(tls)) => TlsMode::Prefer(tls),
+            (_, Some(tls)) => TlsMode::Require(tls),
+            (_, None) => TlsMode::None,
+        };
+
+        let conn = postgres::Connection::connect(url.as_str(), tls_mode)?;
+        conn.execute(&format!("LISTEN {}", PG_CHANNEL), &[])?;
+
+        // Anything sent before we were listening has been missed.
+        clear_all();
+
+        let notifications = conn.notifications();
+        let mut notifications = notifications.blocking_iter();
+        while let Some(notification) = notifications.next()? {
+            let mut parts = notification.payload.splitn(2, ' ');
+
+            if parts.next() == Some(INSTANCE_ID.as_str()) {
+                continue;
+            }
+
+            if let Some(Ok(key)) = parts.next().map(str::parse) {
+                evict(key);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How to secure the connection to the database, as given by the `sslmode` of its URL.
+///
+/// The modes are interpreted as libpq (and therefore Diesel) does,
+/// defaulting to `prefer` when none is provided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    /// Take the `sslmode` from a database URL,
+    /// returning the URL without it (as `postgres` does not understand it) and the mode.
+    fn from_url(url: &str) -> Result<(Url, Self), Box<dyn Error>> {
+        let mut url = Url::parse(url)?;
+        let mut mode = SslMode::Prefer;
+        let mut params = vec![];
+
+        for (key, value) in url.query_pairs().into_owned() {
+            if key != "sslmode" {
+                params.push((key, value));
+                continue;
+            }
+
+            mode = match value.as_str() {
+                "disable" => SslMode::Disable,
+                "allow" | "prefer" => SslMode::Prefer,
+                "require" => SslMode::Require,
+                "verify-ca" => SslMode::VerifyCa,
+                "verify-full" => SslMode::VerifyFull,
+                _ => return Err(format!("unknown sslmode {}", value).into()),
+            };
+        }
+
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+
+        Ok((url, mode))
+    }
+
+    /// The TLS handshake to use, if any.
+    ///
+    /// Only `verify-ca` and `verify-full` check the server's certificate,
+    /// and only the latter checks its hostname.
+    fn handshake(self) -> Result<Option<OpenSsl>, ErrorStack> {
+        if self == SslMode::Disable {
+            return Ok(None);
+        }
+
+        let mut connector = SslConnector::builder(SslMethod::tls())?;
+        if self != SslMode::VerifyCa && self != SslMode::VerifyFull {
+            connector.set_verify(SslVerifyMode::NONE);
+        }
+
+        let mut tls = OpenSsl::with_connector(connector.build());
+        if self != SslMode::VerifyFull {
+            tls.danger_disable_hostname_verification(true);
+        }
+
+        Ok(Some(tls))
+    }
+}
+
+/// Set the channel used to inform other instances of changes.
+/// This should be done once at startup, before any requests are handled.
+pub fn set_invalidation_channel(channel: impl InvalidationChannel + 'static) {
+    *CHANNEL.write() = Box::new(channel);
+}
+
+/// Inform all other instances that the value has changed.
+/// The value should already have been updated in (or removed from) the local cache.
+///
+/// Anything rendered from the value is evicted locally.
+pub(super) fn invalidate(conn: &Database, key: CacheKey) {
+    evict_rendered(key);
+    CHANNEL.read().publish(conn, key);
+}
+
+/// Remove a single value from the local cache.
+fn evict(key: CacheKey) {
+    match key {
+        CacheKey::Event(id) => Event::cache().remove(id),
+        CacheKey::PresetEvent(id) => PresetEvent::cache().remove(id),
+        CacheKey::Section(id) => Section::cache().remove(id),
+        CacheKey::Thread(id) => Thread::cache().remove(id),
+        CacheKey::ThreadTemplate(id) => ThreadTemplate::cache().remove(id),
+        CacheKey::User(id) => User::cache().remove(id),
+    }
+
+    evict_rendered(key);
+}
+
+/// Remove all values from the local caches.
+fn clear_all() {
+    Event::cache().clear();
+    PresetEvent::cache().clear();
+    Section::cache().clear();
+    Thread::cache().clear();
+    ThreadTemplate::cache().clear();
+    User::cache().clear();
+    clear_rendered();
+}
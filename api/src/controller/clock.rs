@@ -0,0 +1,261 @@
+use derive_more::Display;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{error::Error, io::Write, mem};
+
+/// A period during which the countdown was stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hold {
+    /// When the hold began (a UNIX timestamp).
+    pub started_at:  i64,
+    /// The T-time at which the countdown was held,
+    /// in seconds relative to T-0.
+    pub held_at:     i64,
+    /// When the countdown resumed (a UNIX timestamp), if it has.
+    pub ended_at:    Option<i64>,
+    /// The T-time the countdown resumed from, if it was recycled.
+    /// Otherwise, the countdown resumed from where it was held.
+    pub recycled_to: Option<i64>,
+}
+
+/// The state of a thread's countdown,
+/// in addition to its T-0.
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[serde(default)]
+#[sql_type = "Jsonb"]
+pub struct Clock {
+    /// All holds, in the order they occurred.
+    /// Only the last hold may be ongoing.
+    pub holds:       Vec<Hold>,
+    /// When the attempt was scrubbed (a UNIX timestamp), if it was.
+    pub scrubbed_at: Option<i64>,
+    /// All previous scrubs, in the order they occurred.
+    pub scrubs:      Vec<Scrub>,
+}
+
+/// A scrubbed (or rescheduled) attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scrub {
+    /// When the scrub occurred (a UNIX timestamp).
+    pub scrubbed_at: i64,
+    pub reason: String,
+    pub scrubbed_by_user_id: i32,
+    pub previous_t0: Option<i64>,
+    /// The T-0 of the next attempt, if known at the time.
+    pub new_t0: Option<i64>,
+    /// The holds of the scrubbed attempt.
+    pub holds: Vec<Hold>,
+    /// The section containing the events of the scrubbed attempt, if they were archived.
+    pub archive_section_id: Option<i32>,
+}
+
+/// What the countdown is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockState {
+    /// There is no T-0.
+    Unset,
+    Counting,
+    Holding,
+    Scrubbed,
+}
+
+/// The state of a thread's countdown at a particular time.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockStatus {
+    pub t0:      Option<i64>,
+    pub state:   ClockState,
+    /// The T-time, in seconds relative to T-0.
+    pub t_minus: Option<i64>,
+    #[serde(flatten)]
+    pub clock:   Clock,
+}
+
+/// Why the clock could not be changed.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum ClockError {
+    #[display(fmt = "the thread does not have a T-0")]
+    NoT0,
+    #[display(fmt = "the countdown is already holding")]
+    AlreadyHolding,
+    #[display(fmt = "the countdown is not holding")]
+    NotHolding,
+    #[display(fmt = "the attempt has been scrubbed")]
+    Scrubbed,
+    #[display(fmt = "the T-time is out of range")]
+    OutOfRange,
+}
+
+impl Error for ClockError {}
+
+impl Clock {
+    /// The ongoing hold, if any.
+    fn current_hold(&self) -> Option<&Hold> {
+        self.holds.last().filter(|hold| hold.ended_at.is_none())
+    }
+
+    /// What the countdown is currently doing, given the thread's T-0.
+    pub fn state(&self, t0: Option<i64>) -> ClockState {
+        if t0.is_none() {
+            ClockState::Unset
+        } else if self.scrubbed_at.is_some() {
+            ClockState::Scrubbed
+        } else if self.current_hold().is_some() {
+            ClockState::Holding
+        } else {
+            ClockState::Counting
+        }
+    }
+
+    /// The current T-time, in seconds relative to T-0.
+    ///
+    /// While holding, this is the time the countdown was held at.
+    /// Once scrubbed, this is the time of the scrub.
+    ///
+    /// Returns `None` if there is no T-0, or it is too far from the time to be represented.
+    pub fn t_minus(&self, t0: Option<i64>, now: i64) -> Option<i64> {
+        let t0 = t0?;
+
+        if let Some(scrubbed_at) = self.scrubbed_at {
+            return match self.current_hold() {
+                Some(hold) => Some(hold.held_at),
+                None => scrubbed_at.checked_sub(t0),
+            };
+        }
+
+        match self.current_hold() {
+            Some(hold) => Some(hold.held_at),
+            None => now.checked_sub(t0),
+        }
+    }
+
+    /// The full state of the countdown at the provided time.
+    pub fn status(&self, t0: Option<i64>, now: i64) -> ClockStatus {
+        ClockStatus {
+            t0,
+            state: self.state(t0),
+            t_minus: self.t_minus(t0, now),
+            clock: self.clone(),
+        }
+    }
+
+    /// Hold the countdown at the current time.
+    pub fn hold(&mut self, t0: Option<i64>, now: i64) -> Result<(), ClockError> {
+        match self.state(t0) {
+            ClockState::Unset => return Err(ClockError::NoT0),
+            ClockState::Scrubbed => return Err(ClockError::Scrubbed),
+            ClockState::Holding => return Err(ClockError::AlreadyHolding),
+            ClockState::Counting => {}
+        }
+
+        self.holds.push(Hold {
+            started_at:  now,
+            held_at:     now
+                .checked_sub(t0.ok_or(ClockError::NoT0)?)
+                .ok_or(ClockError::OutOfRange)?,
+            ended_at:    None,
+            recycled_to: None,
+        });
+
+        Ok(())
+    }
+
+    /// Resume the countdown at the current time,
+    /// either from where it was held or from the T-time it was recycled to.
+    ///
+    /// Returns the new T-0, which should be stored on the thread.
+    pub fn resume(
+        &mut self,
+        t0: Option<i64>,
+        now: i64,
+        recycled_to: Option<i64>,
+    ) -> Result<i64, ClockError> {
+        match self.state(t0) {
+            ClockState::Unset => return Err(ClockError::NoT0),
+            ClockState::Scrubbed => return Err(ClockError::Scrubbed),
+            ClockState::Counting => return Err(ClockError::NotHolding),
+            ClockState::Holding => {}
+        }
+
+        let hold = self.holds.last_mut().ok_or(ClockError::NotHolding)?;
+        let new_t0 = now
+            .checked_sub(recycled_to.unwrap_or(hold.held_at))
+            .ok_or(ClockError::OutOfRange)?;
+        hold.ended_at = Some(now);
+        hold.recycled_to = recycled_to;
+
+        Ok(new_t0)
+    }
+}
+
+/// The details of a scrub, as provided by a user.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScrubRequest {
+    pub reason:         String,
+    /// The T-0 of the next attempt, if known.
+    #[serde(default)]
+    pub new_t0:         Option<i64>,
+    /// Whether to move the posted events into a section of their own.
+    #[serde(default)]
+    pub archive_events: bool,
+}
+
+impl Clock {
+    /// Scrub the current attempt, recording it (and its holds) in the history.
+    ///
+    /// If the T-0 of the next attempt is known, the countdown is reset to it.
+    /// Otherwise, the countdown remains scrubbed until rescheduled
+    /// (by scrubbing again with a new T-0).
+    pub fn scrub(
+        &mut self,
+        t0: Option<i64>,
+        now: i64,
+        user_id: i32,
+        request: &ScrubRequest,
+        archive_section_id: Option<i32>,
+    ) -> Result<(), ClockError> {
+        if self.state(t0) == ClockState::Unset {
+            return Err(ClockError::NoT0);
+        }
+
+        let holds = mem::replace(&mut self.holds, vec![]);
+        self.scrubs.push(Scrub {
+            scrubbed_at: now,
+            reason: request.reason.clone(),
+            scrubbed_by_user_id: user_id,
+            previous_t0: t0,
+            new_t0: request.new_t0,
+            holds,
+            archive_section_id,
+        });
+        self.scrubbed_at = match request.new_t0 {
+            Some(_) => None,
+            None => Some(now),
+        };
+
+        Ok(())
+    }
+}
+
+impl FromSql<Jsonb, Pg> for Clock {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for Clock {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
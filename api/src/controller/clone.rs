@@ -0,0 +1,112 @@
+#![allow(non_snake_case)]
+
+use super::{
+    Clock,
+    Event,
+    InsertThread,
+    LaunchSync,
+    PostAt,
+    Repository,
+    Section,
+    TemplateSection,
+    Thread,
+    ThreadState,
+    TransferredEvent,
+    Webhooks,
+};
+use crate::Database;
+use rocket_contrib::databases::diesel::QueryResult;
+use serde::Deserialize;
+
+/// How a `Thread` should be cloned.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloneRequest {
+    pub thread_name:    String,
+    /// Defaults to that of the original thread.
+    pub display_name:   Option<String>,
+    /// The subreddit to post the new thread to, if any.
+    pub subreddit:      Option<String>,
+    pub space__t0:      Option<i64>,
+    /// Whether to copy the events of the original thread.
+    #[serde(default)]
+    pub include_events: bool,
+}
+
+impl From<&Section> for TemplateSection {
+    fn from(section: &Section) -> Self {
+        TemplateSection {
+            name:    section.name.clone(),
+            content: section.content.clone(),
+            kind:    section.kind.clone(),
+            data:    section.data.clone(),
+        }
+    }
+}
+
+impl Thread {
+    /// Create a copy of a thread as a draft, owned by the provided user.
+    ///
+    /// The sections, event columns, and time format are copied,
+    /// along with the events if requested.
+    /// Copied events are unposted;
+    /// they remain scheduled only if relative to T-0, as a fixed time would already have passed.
+    /// Launch data and the countdown's history are not copied.
+    pub fn duplicate(
+        conn: &Database,
+        id: i32,
+        request: &CloneRequest,
+        user_id: i32,
+    ) -> QueryResult<Self> {
+        let thread = Thread::find_id(conn, id)?;
+
+        let sections: Vec<TemplateSection> = Section::find_ids(conn, &thread.sections_id)?
+            .values
+            .iter()
+            .map(Into::into)
+            .collect();
+
+        let events: Vec<TransferredEvent> = if request.include_events {
+            Event::find_ids(conn, &thread.events_id)?
+                .values
+                .iter()
+                .map(|event| TransferredEvent {
+                    cols:    event.cols.clone(),
+                    posted:  false,
+                    post_at: match event.post_at {
+                        Some(PostAt::TMinus(t_minus)) => Some(PostAt::TMinus(t_minus)),
+                        _ => None,
+                    },
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let insertable_thread = InsertThread {
+            thread_name: request.thread_name.clone(),
+            display_name: request
+                .display_name
+                .clone()
+                .unwrap_or_else(|| thread.display_name.clone()),
+            post_id: None,
+            subreddit: request.subreddit.clone(),
+            space__t0: request.space__t0,
+            video_url: None,
+            spacex__api_id: None,
+            created_by_user_id: user_id,
+            sections_id: vec![],
+            events_id: vec![],
+            is_live: false,
+            event_columns: thread.event_columns.clone(),
+            time_format: thread.time_format.clone(),
+            clock: Clock::default(),
+            launch_sync: LaunchSync::default(),
+            state: ThreadState::Draft,
+            scheduled_at: None,
+            webhooks: Webhooks::default(),
+        };
+
+        Thread::create_with_contents(conn, &insertable_thread, &sections, &events)
+    }
+}
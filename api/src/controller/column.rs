@@ -0,0 +1,311 @@
+use super::TimeRenderer;
+use derive_deref::Deref;
+use derive_more::Display;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{error::Error, io::Write};
+
+/// The type of value permitted in a column of an `Event`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ColumnType {
+    /// Any string.
+    Text,
+    /// A UNIX timestamp, formatted according to the thread's `TimeFormat`.
+    Utc,
+    /// Either a string of the form `T-H:MM[:SS]` or `T+H:MM[:SS]`,
+    /// or an integer number of seconds relative to T-0.
+    TMinus,
+    /// Any number.
+    Number,
+    /// An absolute HTTP(S) URL.
+    Url,
+    /// One of a fixed set of strings.
+    Enum { options: Vec<String> },
+}
+
+/// A single column in the schema of a thread's events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventColumn {
+    pub name:     String,
+    #[serde(flatten)]
+    pub kind:     ColumnType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub default:  Option<Value>,
+}
+
+/// The ordered schema of a thread's events,
+/// stored as JSON in the database.
+#[derive(
+    Debug, Clone, Default, PartialEq, Deref, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[serde(transparent)]
+#[sql_type = "Jsonb"]
+pub struct EventColumns(pub Vec<EventColumn>);
+
+/// Why a schema or row of event columns was rejected.
+#[derive(Debug, Display, Clone, PartialEq)]
+pub enum ColumnError {
+    #[display(fmt = "expected columns to be an array")]
+    NotAnArray,
+    #[display(fmt = "expected at most {} columns, found {}", expected, found)]
+    TooManyValues { expected: usize, found: usize },
+    #[display(fmt = r#"column "{}" is required"#, _0)]
+    Missing(String),
+    #[display(fmt = r#"column "{}" has an invalid value"#, _0)]
+    InvalidValue(String),
+    #[display(fmt = r#"column "{}" has an invalid default"#, _0)]
+    InvalidDefault(String),
+    #[display(fmt = "an enum column must have at least one option")]
+    EmptyEnum,
+    #[display(fmt = "column names must not be empty")]
+    EmptyName,
+    #[display(fmt = "there is no column at index {}", _0)]
+    NoSuchColumn(usize),
+}
+
+impl Error for ColumnError {}
+
+/// Is the string of the form `T-H:MM[:SS]` or `T+H:MM[:SS]`?
+fn is_t_minus(string: &str) -> bool {
+    if !string.starts_with("T-") && !string.starts_with("T+") {
+        return false;
+    }
+
+    let parts: Vec<_> = string[2..].split(':').collect();
+    (2..=3).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Format a number of seconds relative to T-0 as `T-H:MM:SS` or `T+H:MM:SS`.
+pub fn format_t_minus(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    // Widened, as the magnitude of `i64::MIN` does not fit in an `i64`.
+    let seconds = i128::from(seconds).abs();
+
+    format!(
+        "T{}{}:{:02}:{:02}",
+        sign,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// Is the string an absolute HTTP(S) URL?
+fn is_url(string: &str) -> bool {
+    let rest = if string.starts_with("https://") {
+        &string["https://".len()..]
+    } else if string.starts_with("http://") {
+        &string["http://".len()..]
+    } else {
+        return false;
+    };
+
+    !rest.is_empty() && !string.chars().any(char::is_whitespace)
+}
+
+impl ColumnType {
+    /// Is the (non-null) value permitted in a column of this type?
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            ColumnType::Text => value.is_string(),
+            ColumnType::Utc => value.is_i64(),
+            ColumnType::TMinus => value.is_i64() || value.as_str().map_or(false, is_t_minus),
+            ColumnType::Number => value.is_number(),
+            ColumnType::Url => value.as_str().map_or(false, is_url),
+            ColumnType::Enum { options } => value
+                .as_str()
+                .map_or(false, |value| options.iter().any(|option| option == value)),
+        }
+    }
+
+    /// Format a value for display,
+    /// with timestamps being displayed by the provided renderer.
+    ///
+    /// Returns `None` if the value is not permitted in a column of this type.
+    pub fn format(&self, value: &Value, time: &TimeRenderer) -> Option<String> {
+        if !self.accepts(value) {
+            return None;
+        }
+
+        match (self, value) {
+            (ColumnType::Utc, Value::Number(n)) => time.render(n.as_i64()?),
+            (ColumnType::TMinus, Value::Number(n)) => Some(format_t_minus(n.as_i64()?)),
+            (_, Value::Number(n)) => Some(n.to_string()),
+            (_, Value::String(s)) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    /// Interpret plain text (such as a CSV field) as a value of this type.
+    ///
+    /// An empty string is `null`.
+    /// Text that cannot be interpreted is kept as a string,
+    /// to be rejected by validation if necessary.
+    pub fn parse_text(&self, text: &str) -> Value {
+        if text.is_empty() {
+            return Value::Null;
+        }
+
+        let number = match self {
+            ColumnType::Utc | ColumnType::TMinus => text.parse::<i64>().ok().map(Value::from),
+            ColumnType::Number => text
+                .parse::<i64>()
+                .map(Value::from)
+                .or_else(|_| text.parse::<f64>().map(Value::from))
+                .ok(),
+            _ => None,
+        };
+
+        number.unwrap_or_else(|| Value::String(text.into()))
+    }
+}
+
+/// Represent a value as plain text (such as a CSV field),
+/// such that `ColumnType::parse_text` returns the original value.
+pub fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl EventColumn {
+    /// The value to use for this column,
+    /// falling back to the default if the provided value is absent or `null`.
+    fn resolve<'a>(&'a self, value: Option<&'a Value>) -> Option<&'a Value> {
+        match value {
+            None | Some(Value::Null) => self.default.as_ref().filter(|value| !value.is_null()),
+            Some(value) => Some(value),
+        }
+    }
+}
+
+impl EventColumns {
+    /// Ensure the schema itself is valid:
+    /// every column is named, every enum has at least one option,
+    /// and every default is permitted in its column.
+    pub fn check(&self) -> Result<(), ColumnError> {
+        for column in self.iter() {
+            if column.name.is_empty() {
+                return Err(ColumnError::EmptyName);
+            }
+
+            if let ColumnType::Enum { options } = &column.kind {
+                if options.is_empty() {
+                    return Err(ColumnError::EmptyEnum);
+                }
+            }
+
+            if let Some(default) = column.default.as_ref().filter(|value| !value.is_null()) {
+                if !column.kind.accepts(default) {
+                    return Err(ColumnError::InvalidDefault(column.name.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate a row of event columns against the schema.
+    ///
+    /// Values that are absent (beyond the end of the row) or `null`
+    /// take the column's default, if any.
+    /// On success, the row is returned with one value per column.
+    pub fn validate(&self, cols: &Value) -> Result<Value, ColumnError> {
+        let values = cols.as_array().ok_or(ColumnError::NotAnArray)?;
+
+        if values.len() > self.len() {
+            return Err(ColumnError::TooManyValues {
+                expected: self.len(),
+                found:    values.len(),
+            });
+        }
+
+        self.iter()
+            .enumerate()
+            .map(|(i, column)| match column.resolve(values.get(i)) {
+                Some(value) if column.kind.accepts(value) => Ok(value.clone()),
+                Some(_) => Err(ColumnError::InvalidValue(column.name.clone())),
+                None if column.required => Err(ColumnError::Missing(column.name.clone())),
+                None => Ok(Value::Null),
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array)
+    }
+
+    /// Replace individual values of a row, given pairs of the column index and new value.
+    ///
+    /// The row is extended with `null`s as necessary.
+    /// The result must still be validated.
+    pub fn apply_partial(
+        &self,
+        cols: &Value,
+        changes: &[(usize, Value)],
+    ) -> Result<Value, ColumnError> {
+        let mut values = cols.as_array().cloned().unwrap_or_default();
+
+        for (index, value) in changes {
+            if *index >= self.len() {
+                return Err(ColumnError::NoSuchColumn(*index));
+            }
+            if *index >= values.len() {
+                values.resize(index + 1, Value::Null);
+            }
+            values[*index] = value.clone();
+        }
+
+        Ok(Value::Array(values))
+    }
+
+    /// All timestamps in a row, in `utc` columns.
+    pub fn timestamps<'a>(&'a self, cols: &'a Value) -> impl Iterator<Item = i64> + 'a {
+        self.iter()
+            .zip(cols.as_array().map(Vec::as_slice).unwrap_or_default())
+            .filter(|(column, _)| column.kind == ColumnType::Utc)
+            .filter_map(|(_, value)| value.as_i64())
+    }
+
+    /// Format each value of a row for display,
+    /// with absent values being formatted as an empty string.
+    pub fn format(&self, cols: &Value, time: &TimeRenderer) -> Result<Vec<String>, ColumnError> {
+        let values = cols.as_array().ok_or(ColumnError::NotAnArray)?;
+
+        self.iter()
+            .enumerate()
+            .map(|(i, column)| match column.resolve(values.get(i)) {
+                Some(value) => column
+                    .kind
+                    .format(value, time)
+                    .ok_or_else(|| ColumnError::InvalidValue(column.name.clone())),
+                None => Ok(String::new()),
+            })
+            .collect()
+    }
+}
+
+impl FromSql<Jsonb, Pg> for EventColumns {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for EventColumns {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
@@ -1,30 +1,48 @@
-use super::{Thread, ToMarkdown, UpdateThread, EVENT_CACHE_SIZE};
+use super::{
+    invalidate,
+    Cache,
+    CacheKey,
+    EventColumns,
+    Page,
+    RenderContext,
+    RenderError,
+    Repository,
+    Sort,
+    Thread,
+    TimeRenderer,
+    ToMarkdown,
+    UpdateThread,
+    User,
+    EVENT_CACHE_SIZE,
+};
 use crate::{
     schema::event,
-    websocket::{Action, DataType, Message, Room, Update},
+    websocket::{Action, DataType, Message, Room},
     Database,
 };
-use lru_cache::LruCache;
+use diesel::{
+    deserialize::{self, FromSql},
+    dsl::sql,
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::{BigInt, Bool, Jsonb},
+};
 use macros::generate_structs;
 use once_cell::sync::Lazy;
-use parking_lot::Mutex;
-use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
-use serde_json::json;
-use std::{error::Error, fmt::Write};
+use rocket::FromForm;
+use rocket_contrib::databases::diesel::{
+    Connection,
+    ExpressionMethods,
+    QueryDsl,
+    QueryResult,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeSet, fmt::Write, io, str::FromStr};
 
 /// A global cache, containing a mapping of IDs to their respective `Event`.
-///
-/// The cache is protected by a `Mutex`,
-/// ensuring there is only ever at most one writer at a time.
-/// Note that even when reading,
-/// there must be a lock on mutability,
-/// as the `LruCache` must be able to update itself.
-///
-/// To read from the cache,
-/// you'll want to call `CACHE.lock()` before performing normal operations.
-/// ```
-static CACHE: Lazy<Mutex<LruCache<i32, Event>>> =
-    Lazy::new(|| Mutex::new(LruCache::new(EVENT_CACHE_SIZE)));
+static CACHE: Lazy<Cache<Event>> = Lazy::new(|| Cache::new(*EVENT_CACHE_SIZE));
 
 generate_structs! {
     Event("event") {
@@ -32,98 +50,325 @@ generate_structs! {
         posted: bool = false,
         readonly in_thread_id: i32,
         cols: serde_json::Value,
+        post_at: Option<PostAt>,
+        auto version: i32,
+    }
+}
+
+/// When an unposted `Event` should be posted automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Jsonb"]
+pub enum PostAt {
+    /// A UNIX timestamp.
+    Utc(i64),
+    /// A number of seconds relative to the thread's T-0.
+    /// If T-0 changes, the event is rescheduled accordingly.
+    TMinus(i64),
+}
+
+impl PostAt {
+    /// Is an event with this schedule due in the provided thread
+    /// at the provided time (a UNIX timestamp)?
+    ///
+    /// Events scheduled relative to T-0 follow the thread's countdown,
+    /// so are never due while the thread has no T-0 or is holding beforehand.
+    pub fn is_due(self, thread: &Thread, now: i64) -> bool {
+        match self {
+            PostAt::Utc(timestamp) => timestamp <= now,
+            PostAt::TMinus(offset) => thread
+                .t_minus(now)
+                .map_or(false, |t_minus| offset <= t_minus),
+        }
+    }
+}
+
+impl FromSql<Jsonb, Pg> for PostAt {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for PostAt {
+    fn to_sql<W: io::Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
+
+/// Criteria that all `Event`s returned by `Event::find_all` must match.
+/// Any criteria not provided are not checked.
+#[derive(Debug, Default, FromForm)]
+pub struct EventFilter {
+    pub in_thread_id: Option<i32>,
+    pub posted:       Option<bool>,
+}
+
+/// Fields that a list of `Event`s can be sorted by.
+#[derive(Debug, Clone, Copy)]
+pub enum EventSortKey {
+    Id,
+    InThreadId,
+    Posted,
+}
+
+impl FromStr for EventSortKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "id" => Ok(EventSortKey::Id),
+            "in_thread_id" => Ok(EventSortKey::InThreadId),
+            "posted" => Ok(EventSortKey::Posted),
+            _ => Err("unknown sort key"),
+        }
     }
 }
 
 impl Event {
-    /// Find all `Event`s in the database.
+    /// Find a page of `Event`s in the database matching the provided filter.
+    /// Unless otherwise specified, the results are sorted by ID.
+    ///
+    /// Only events of threads visible to `viewer` are included.
     ///
     /// Does _not_ use cache (reading or writing),
     /// so as to avoid storing values rarely accessed.
-    pub fn find_all(conn: &Database) -> QueryResult<Vec<Self>> {
-        use crate::schema::event::dsl::event;
-        event.load(conn)
+    pub fn find_all(
+        conn: &Database,
+        filter: &EventFilter,
+        sort: Option<Sort<EventSortKey>>,
+        page: Page,
+        viewer: Option<&User>,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::event::dsl::{event, id, in_thread_id, posted};
+
+        let mut query = event
+            .filter(in_thread_id.eq_any(Thread::visible_ids(viewer)))
+            .into_boxed();
+
+        if let Some(value) = filter.in_thread_id {
+            query = query.filter(in_thread_id.eq(value));
+        }
+        if let Some(value) = filter.posted {
+            query = query.filter(posted.eq(value));
+        }
+
+        let sort = sort.unwrap_or_else(|| Sort::ascending(EventSortKey::Id));
+        query = match sort.key {
+            EventSortKey::Id => order_by!(query, id, sort),
+            EventSortKey::InThreadId => order_by!(query, in_thread_id, sort),
+            EventSortKey::Posted => order_by!(query, posted, sort),
+        };
+
+        query
+            .then_order_by(id.asc())
+            .limit(page.limit)
+            .offset(page.offset)
+            .load(conn)
     }
 
-    /// Find a given `Event` by its ID.
+    /// Create a number of `Event`s in a single thread, all in one transaction.
     ///
-    /// Internally uses a cache to limit database accesses.
-    pub fn find_id(conn: &Database, event_id: i32) -> QueryResult<Self> {
+    /// The events are appended to the thread in the order provided.
+    /// Rather than one message per event,
+    /// WebSocket clients receive a single `create_many` message with all events.
+    pub fn create_many(
+        conn: &Database,
+        thread_id: i32,
+        data: &[InsertEvent],
+    ) -> QueryResult<Vec<Self>> {
         use crate::schema::event::dsl::event;
 
-        let mut cache = CACHE.lock();
-        if cache.contains_key(&event_id) {
-            Ok(cache.get_mut(&event_id).unwrap().clone())
-        } else {
-            let result: Self = event.find(event_id).first(conn)?;
-            cache.insert(event_id, result.clone());
-            Ok(result)
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let (events, thread, thread_changes) =
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                let events: Vec<Self> =
+                    diesel::insert_into(event).values(data).get_results(conn)?;
+
+                let mut events_id = Thread::load_id(conn, thread_id)?.events_id;
+                events_id.extend(events.iter().map(|value| value.id));
+                let thread_changes = UpdateThread {
+                    events_id: events_id.into(),
+                    ..UpdateThread::default()
+                };
+                let thread = Thread::update_row(conn, thread_id, None, &thread_changes)?;
+
+                Ok((events, thread, thread_changes))
+            })?;
+
+        for value in &events {
+            Self::cache().insert(value.id, value.clone());
+            invalidate(conn, CacheKey::Event(value.id));
         }
+
+        if thread.is_public(conn) {
+            let _ = Message {
+                room:      Room::Thread(thread_id),
+                action:    Action::CreateMany,
+                data_type: DataType::Event,
+                data:      &events,
+            }
+            .send();
+        }
+
+        Thread::updated(conn, &thread, &thread_changes);
+
+        Ok(events)
     }
 
-    /// Create an `Event` given the data.
+    /// Post all scheduled `Event`s that are due at the provided time (a UNIX timestamp).
     ///
-    /// The inserted row is added to the global cache and returned.
-    pub fn create(conn: &Database, data: &InsertEvent) -> QueryResult<Self> {
-        use crate::schema::event::dsl::event;
+    /// Events scheduled relative to T-0 are resolved against the thread's _current_ countdown,
+    /// so a slip or hold automatically reschedules them.
+    /// Each event is only posted if it has not changed since being loaded,
+    /// so concurrent schedulers (or users) cannot post an event twice.
+    /// Events in archived threads are never posted.
+    ///
+    /// Returns the IDs of all threads with newly posted events.
+    pub fn post_due(conn: &Database, now: i64) -> QueryResult<BTreeSet<i32>> {
+        use crate::schema::event::dsl::{event, post_at, posted};
+
+        // Only load events that may be due: those scheduled at or before the time,
+        // or relative to a T-0 that has since been reached by their offset.
+        // A hold or scrub only ever delays the latter, so is checked below.
+        // The comparisons are made as `numeric` so that extreme values cannot overflow.
+        let may_be_due = sql::<Bool>(
+            "EXISTS (SELECT 1 FROM thread WHERE thread.id = event.in_thread_id \
+             AND thread.state <> 'archived' \
+             AND ((event.post_at ->> 'utc')::numeric <= ",
+        )
+        .bind::<BigInt, _>(now)
+        .sql(" OR (event.post_at ->> 't_minus')::numeric + thread.space__t0 <= ")
+        .bind::<BigInt, _>(now)
+        .sql("))");
+
+        let scheduled: Vec<Self> = event
+            .filter(posted.eq(false))
+            .filter(post_at.is_not_null())
+            .filter(may_be_due)
+            .load(conn)?;
+
+        if scheduled.is_empty() {
+            return Ok(BTreeSet::new());
+        }
+
+        let thread_ids: Vec<_> = scheduled.iter().map(|event| event.in_thread_id).collect();
+        let threads = Thread::find_ids(conn, &thread_ids)?.values;
+
+        let mut updated_threads = BTreeSet::new();
+        for scheduled_event in scheduled {
+            let thread = threads
+                .iter()
+                .find(|thread| thread.id == scheduled_event.in_thread_id);
+            let is_due = match (scheduled_event.post_at, thread) {
+                (Some(schedule), Some(thread)) => schedule.is_due(thread, now),
+                _ => false,
+            };
 
-        let result: Self = diesel::insert_into(event).values(data).get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
+            if !is_due {
+                continue;
+            }
 
-        let _ = Message {
-            room:      Room::Thread(result.in_thread_id),
-            action:    Action::Create,
-            data_type: DataType::Event,
-            data:      &result,
+            let result = Self::update_if_version(
+                conn,
+                scheduled_event.id,
+                Some(scheduled_event.version),
+                &UpdateEvent {
+                    posted: Some(true),
+                    ..UpdateEvent::default()
+                },
+            );
+
+            match result {
+                Ok(_) => {
+                    updated_threads.insert(scheduled_event.in_thread_id);
+                }
+                // The event was modified since being loaded.
+                // It will be reconsidered on the next run.
+                Err(diesel::result::Error::NotFound) => {}
+                Err(err) => return Err(err),
+            }
         }
-        .send();
 
-        // Add the event ID to the relevant Thread.
-        let mut thread = Thread::find_id(conn, data.in_thread_id)?;
-        thread.events_id.push(result.id);
+        Ok(updated_threads)
+    }
+}
+
+impl Repository for Event {
+    type Insert = InsertEvent;
+    type Update = UpdateEvent;
+
+    const DATA_TYPE: DataType = DataType::Event;
+
+    repository_primitives!(event, versioned);
+
+    fn cache() -> &'static Cache<Self> {
+        &CACHE
+    }
+
+    fn cache_key(id: i32) -> CacheKey {
+        CacheKey::Event(id)
+    }
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn version(&self) -> Option<i32> {
+        Some(self.version)
+    }
+
+    fn room(&self, _action: Action) -> Room {
+        Room::Thread(self.in_thread_id)
+    }
+
+    /// Events of a draft are only visible to those able to modify the thread.
+    fn is_public(&self, conn: &Database) -> bool {
+        Thread::has_public_contents(conn, self.in_thread_id)
+    }
+
+    /// Add the event ID to the relevant `Thread`,
+    /// and send the event to the thread's webhooks if it is posted.
+    fn after_create(&self, conn: &Database) -> QueryResult<()> {
+        let mut thread = Thread::find_id(conn, self.in_thread_id)?;
+        thread.events_id.push(self.id);
         Thread::update(
             conn,
-            data.in_thread_id,
+            self.in_thread_id,
             &UpdateThread {
                 events_id: thread.events_id.into(),
                 ..UpdateThread::default()
             },
         )?;
 
-        Ok(result)
+        if self.posted {
+            if let Err(err) = self.notify_posted(conn) {
+                eprintln!("error notifying webhooks of event {}: {}", self.id, err);
+            }
+        }
+
+        Ok(())
     }
 
-    /// Update an `Event` given an ID and the data to update.
-    ///
-    /// The entry is updated in the database, added to cache, and returned.
-    pub fn update(conn: &Database, event_id: i32, data: &UpdateEvent) -> QueryResult<Self> {
-        use crate::schema::event::dsl::{event, id};
-
-        let result: Self = diesel::update(event)
-            .filter(id.eq(event_id))
-            .set(data)
-            .get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::Thread(result.in_thread_id),
-            action:    Action::Update,
-            data_type: DataType::Event,
-            data:      &Update::new(event_id, data),
+    /// Send the event to the thread's webhooks if it was marked as posted.
+    fn after_update(&self, conn: &Database, changes: &UpdateEvent) {
+        if changes.posted != Some(true) {
+            return;
         }
-        .send();
 
-        Ok(result)
+        if let Err(err) = self.notify_posted(conn) {
+            eprintln!("error notifying webhooks of event {}: {}", self.id, err);
+        }
     }
 
-    /// Delete an `Event` given its ID.
-    ///
-    /// Removes the entry from cache and returns the number of rows deleted (should be `1`).
-    pub fn delete(conn: &Database, event_id: i32) -> QueryResult<usize> {
-        use crate::schema::event::dsl::{event, id};
-
-        let mut thread = Thread::find_id(conn, Self::find_id(conn, event_id)?.in_thread_id)?;
-        thread.events_id.retain(|&cur_id| cur_id != event_id);
+    /// Remove the event ID from the relevant `Thread`.
+    fn before_delete(&self, conn: &Database) -> QueryResult<()> {
+        let mut thread = Thread::find_id(conn, self.in_thread_id)?;
+        thread.events_id.retain(|&cur_id| cur_id != self.id);
         Thread::update(
             conn,
             thread.id,
@@ -133,68 +378,35 @@ impl Event {
             },
         )?;
 
-        let _ = Message {
-            room:      Room::Thread(thread.id),
-            action:    Action::Delete,
-            data_type: DataType::Event,
-            data:      &json!({ "id": event_id }),
-        }
-        .send();
-
-        CACHE.lock().remove(&event_id);
-
-        let removed_count = diesel::delete(event).filter(id.eq(event_id)).execute(conn);
-
-        if let Ok(removed_count) = removed_count {
-            debug_assert_eq!(removed_count, 1);
-        }
-
-        removed_count
+        Ok(())
     }
 }
 
-impl ToMarkdown for Event {
-    /// Convert the `Event` object to valid markdown.
-    /// The resulting string is intended for consumption by Reddit,
-    /// but should be valid for any markdown flavor supporting tables.
+impl Event {
+    /// Convert the `Event` to a row of a markdown table,
+    /// given the thread's schema and a renderer for its timestamps.
     ///
-    /// The designated UTC column (if any) will be formatted as `HH:MM`.
-    fn to_markdown(&self, conn: &Database) -> Result<String, Box<dyn Error>> {
+    /// Unposted events are an empty string.
+    pub fn to_markdown_row(
+        &self,
+        columns: &EventColumns,
+        time: &TimeRenderer,
+    ) -> Result<String, RenderError> {
         if !self.posted {
             return Ok("".into());
         }
 
-        let mut md = String::new();
+        let values = columns
+            .format(&self.cols, time)
+            .map_err(|error| RenderError::Event {
+                event_id: self.id,
+                error,
+            })?;
 
-        if !self.cols.is_array() {
-            panic!("Expected columns to be array");
-        }
+        let mut md = String::new();
 
-        let utc_col_index = Thread::find_id(conn, self.in_thread_id)?.space__utc_col_index;
-
-        for (val, i) in self.cols.as_array().unwrap().iter().zip(0..) {
-            write!(
-                &mut md,
-                "|{}",
-                // If the column in question is the designated UTC timestamp,
-                // format it as such.
-                if utc_col_index == Some(i) {
-                    let timestamp = val.as_i64().expect("expected i64 in UTC column");
-                    let hours = timestamp % 86_400 / 3_600;
-                    let minutes = timestamp % 3_600 / 60;
-
-                    format!("{:02}:{:02}", hours, minutes)
-                } else {
-                    use serde_json::Value::{Number, String};
-                    match val {
-                        Number(n) => n.clone().as_i64().unwrap().to_string(),
-                        String(s) => s.clone().to_owned(),
-                        _ => panic!("expected number or string"),
-                    }
-                }
-                .replace('\n', " ")
-                .replace('|', "\\|")
-            )?;
+        for value in values {
+            write!(&mut md, "|{}", value.replace('\n', " ").replace('|', "\\|"))?;
         }
 
         writeln!(&mut md, "|")?;
@@ -202,3 +414,17 @@ impl ToMarkdown for Event {
         Ok(md)
     }
 }
+
+impl ToMarkdown for Event {
+    /// Convert the `Event` object to valid markdown.
+    /// The resulting string is intended for consumption by Reddit,
+    /// but should be valid for any markdown flavor supporting tables.
+    ///
+    /// Each column is formatted according to its type in the thread's schema,
+    /// with timestamps formatted according to the thread's `TimeFormat`.
+    ///
+    /// The context must be that of the event's thread.
+    fn to_markdown(&self, ctx: &RenderContext) -> Result<String, RenderError> {
+        self.to_markdown_row(&ctx.thread().event_columns, ctx.time())
+    }
+}
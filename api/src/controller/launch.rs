@@ -0,0 +1,502 @@
+use super::{
+    ExternalInsertThread,
+    InsertThread,
+    Repository,
+    Section,
+    StatsKind,
+    StatsTable,
+    TemplateSection,
+    Thread,
+    UpdateSection,
+    UpdateThread,
+};
+use crate::Database;
+use derive_more::Display;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+};
+use rocket_contrib::databases::diesel::{
+    result::Error as DieselError,
+    ExpressionMethods,
+    QueryDsl,
+    QueryResult,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, error::Error, fs, io::Write};
+
+/// The base URL of the SpaceX API, used unless another is provided.
+pub const SPACEX_API_URL: &str = "https://api.spacexdata.com/v3";
+
+/// The name of the section created to contain a launch's details.
+const STATS_SECTION_NAME: &str = "Stats";
+
+/// How long after T-0 a thread's launch data continues to be refreshed, in seconds.
+const REFRESH_AFTER_T0: i64 = 24 * 60 * 60;
+
+/// Information about a launch, as known by a `LaunchProvider`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Launch {
+    /// The ID of the launch, as stored in a thread's `spacex__api_id`.
+    pub id:           String,
+    pub name:         String,
+    /// The launch date (a UNIX timestamp), no earlier than which the launch will occur.
+    pub net:          Option<i64>,
+    /// Whether the NET is only an estimate,
+    /// rather than a precise T-0.
+    #[serde(default)]
+    pub is_tentative: bool,
+    #[serde(default)]
+    pub vehicle:      Option<String>,
+    #[serde(default)]
+    pub payloads:     Vec<String>,
+    #[serde(default)]
+    pub pad:          Option<String>,
+}
+
+impl Launch {
+    /// The T-0 of the launch, if it is known precisely.
+    pub fn t0(&self) -> Option<i64> {
+        if self.is_tentative {
+            None
+        } else {
+            self.net
+        }
+    }
+
+    /// A table of the launch's details, suitable for a thread's stats section.
+    pub fn stats(&self) -> StatsTable {
+        let mut stats = StatsTable::default();
+
+        stats.set("Mission", self.name.clone());
+        if let Some(net) = self.net {
+            let net = chrono::NaiveDateTime::from_timestamp(net, 0);
+            let format = if self.is_tentative {
+                "%Y-%m-%d"
+            } else {
+                "%Y-%m-%d %H:%M:%S UTC"
+            };
+            stats.set("NET", net.format(format).to_string());
+        }
+        if let Some(vehicle) = &self.vehicle {
+            stats.set("Vehicle", vehicle.clone());
+        }
+        if !self.payloads.is_empty() {
+            stats.set("Payload", self.payloads.join(", "));
+        }
+        if let Some(pad) = &self.pad {
+            stats.set("Launch site", pad.clone());
+        }
+
+        stats
+    }
+}
+
+/// Why a launch could not be provided.
+#[derive(Debug, Display)]
+pub enum LaunchError {
+    #[display(fmt = "the thread is not associated with a launch")]
+    NoLaunch,
+    #[display(fmt = r#"launch "{}" not found"#, _0)]
+    NotFound(String),
+    #[display(fmt = "request failed: {}", _0)]
+    Request(String),
+    #[display(fmt = "invalid launch data: {}", _0)]
+    Invalid(String),
+    #[display(fmt = "{}", _0)]
+    Database(DieselError),
+}
+
+impl Error for LaunchError {}
+
+impl From<DieselError> for LaunchError {
+    fn from(err: DieselError) -> Self {
+        LaunchError::Database(err)
+    }
+}
+
+/// A source of launch data.
+pub trait LaunchProvider: Send + Sync {
+    /// Look up the launch with the provided ID.
+    fn launch(&self, id: &str) -> Result<Launch, LaunchError>;
+}
+
+/// Look up launches using the SpaceX API (v3),
+/// where the ID of a launch is its flight number.
+#[derive(Debug, Clone)]
+pub struct SpaceXApi {
+    base_url: String,
+    client:   reqwest::Client,
+}
+
+impl SpaceXApi {
+    /// Use the API at the provided base URL, such as `SPACEX_API_URL`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            client:   reqwest::Client::new(),
+        }
+    }
+}
+
+/// Only the fields of a launch (as returned by the SpaceX API) that are used.
+#[derive(Deserialize)]
+struct ApiLaunch {
+    flight_number:    i64,
+    mission_name:     String,
+    launch_date_unix: Option<i64>,
+    is_tentative:     bool,
+    rocket:           ApiRocket,
+    launch_site:      Option<ApiLaunchSite>,
+}
+
+#[derive(Deserialize)]
+struct ApiRocket {
+    rocket_name:  String,
+    second_stage: Option<ApiSecondStage>,
+}
+
+#[derive(Deserialize)]
+struct ApiSecondStage {
+    payloads: Vec<ApiPayload>,
+}
+
+#[derive(Deserialize)]
+struct ApiPayload {
+    payload_id: String,
+}
+
+#[derive(Deserialize)]
+struct ApiLaunchSite {
+    site_name_long: String,
+}
+
+impl From<ApiLaunch> for Launch {
+    fn from(launch: ApiLaunch) -> Self {
+        Launch {
+            id:           launch.flight_number.to_string(),
+            name:         launch.mission_name,
+            net:          launch.launch_date_unix,
+            is_tentative: launch.is_tentative,
+            vehicle:      Some(launch.rocket.rocket_name),
+            payloads:     launch
+                .rocket
+                .second_stage
+                .map(|stage| {
+                    stage
+                        .payloads
+                        .into_iter()
+                        .map(|payload| payload.payload_id)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            pad:          launch.launch_site.map(|site| site.site_name_long),
+        }
+    }
+}
+
+impl LaunchProvider for SpaceXApi {
+    fn launch(&self, id: &str) -> Result<Launch, LaunchError> {
+        let mut response = self
+            .client
+            .get(&format!("{}/launches/{}", self.base_url, id))
+            .send()
+            .map_err(|err| LaunchError::Request(err.to_string()))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(LaunchError::NotFound(id.into()));
+        }
+        if !response.status().is_success() {
+            return Err(LaunchError::Request(response.status().to_string()));
+        }
+
+        response
+            .json::<ApiLaunch>()
+            .map(Launch::from)
+            .map_err(|err| LaunchError::Invalid(err.to_string()))
+    }
+}
+
+/// Look up launches from a fixed set,
+/// for use in tests and when working offline.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureProvider {
+    launches: HashMap<String, Launch>,
+}
+
+impl FixtureProvider {
+    /// Provide the launches in a JSON file,
+    /// which must contain an array of `Launch`es.
+    pub fn from_file(path: &str) -> Result<Self, LaunchError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| LaunchError::Invalid(format!("{}: {}", path, err)))?;
+        let launches: Vec<Launch> = serde_json::from_str(&contents)
+            .map_err(|err| LaunchError::Invalid(format!("{}: {}", path, err)))?;
+
+        Ok(Self {
+            launches: launches
+                .into_iter()
+                .map(|launch| (launch.id.clone(), launch))
+                .collect(),
+        })
+    }
+}
+
+impl LaunchProvider for FixtureProvider {
+    fn launch(&self, id: &str) -> Result<Launch, LaunchError> {
+        self.launches
+            .get(id)
+            .cloned()
+            .ok_or_else(|| LaunchError::NotFound(id.into()))
+    }
+}
+
+/// A difference between the launch data and a manual change to the thread,
+/// which was therefore not overwritten.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchConflict {
+    /// The field of the thread (or `stats.<key>` for a row of the stats section).
+    pub field:       String,
+    /// When the conflict was detected (a UNIX timestamp).
+    pub detected_at: i64,
+    /// The value according to the launch data.
+    pub launch:      Value,
+}
+
+/// The last launch data applied to a thread,
+/// used to determine what has changed (and what has been manually edited) since.
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[serde(default)]
+#[sql_type = "Jsonb"]
+pub struct LaunchSync {
+    /// When the launch data was last fetched (a UNIX timestamp).
+    pub synced_at:        Option<i64>,
+    /// The launch data as of that time.
+    pub launch:           Option<Launch>,
+    /// The section containing the launch's details, if one was created.
+    pub stats_section_id: Option<i32>,
+    /// Changes that could not be applied as of the last refresh.
+    pub conflicts:        Vec<LaunchConflict>,
+}
+
+impl FromSql<Jsonb, Pg> for LaunchSync {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for LaunchSync {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
+
+impl ExternalInsertThread {
+    /// Fill in anything not provided by the user from the launch data.
+    pub fn prefill(&mut self, launch: &Launch) {
+        if self.thread_name.is_empty() {
+            self.thread_name = format!("{} Launch Thread", launch.name);
+        }
+        if self.display_name.is_empty() {
+            self.display_name = launch.name.clone();
+        }
+        if self.space__t0.is_none() {
+            self.space__t0 = launch.t0();
+        }
+    }
+}
+
+/// Determine what to do with a single value from the launch data,
+/// given the value currently in the thread and that from the last refresh.
+///
+/// A value is only changed if it has not been manually edited since the last refresh.
+/// If it has (and differs from the launch data), the edit is kept and reported as a conflict.
+fn reconcile<T: PartialEq>(current: &T, previous: &T, launch: &T) -> Reconciled {
+    if current == launch {
+        Reconciled::Unchanged
+    } else if current == previous {
+        Reconciled::Apply
+    } else {
+        Reconciled::Conflict
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reconciled {
+    Unchanged,
+    Apply,
+    Conflict,
+}
+
+impl Thread {
+    /// Find all `Thread`s with launch data that may still change,
+    /// being those without a T-0 or with one no more than a day ago.
+    pub fn find_refreshable(conn: &Database, now: i64) -> QueryResult<Vec<Self>> {
+        use crate::schema::thread::dsl::{space__t0, spacex__api_id, thread};
+
+        thread
+            .filter(spacex__api_id.is_not_null())
+            .filter(space__t0.is_null().or(space__t0.ge(now - REFRESH_AFTER_T0)))
+            .load(conn)
+    }
+
+    /// Create a `Thread` with the provided sections as in `Thread::create_with_contents`,
+    /// filling in its stats section from the launch data
+    /// and recording the data for later refreshes, all in one transaction.
+    ///
+    /// The first empty stats section (such as one from a template) is used if present;
+    /// otherwise a stats section is added after the others.
+    pub fn create_with_launch(
+        conn: &Database,
+        data: &InsertThread,
+        sections: &[TemplateSection],
+        launch: Launch,
+        now: i64,
+    ) -> QueryResult<Self> {
+        let mut sections = sections.to_vec();
+        let empty_section = sections.iter().position(|section| {
+            section.kind == StatsKind::NAME
+                && serde_json::from_value::<StatsTable>(section.data.clone())
+                    .map_or(false, |stats| stats.rows.is_empty())
+        });
+
+        let stats_index = match empty_section {
+            Some(index) => {
+                sections[index].data = json!(launch.stats());
+                index
+            }
+            None => {
+                sections.push(TemplateSection {
+                    name:    STATS_SECTION_NAME.into(),
+                    content: String::new(),
+                    kind:    StatsKind::NAME.into(),
+                    data:    json!(launch.stats()),
+                });
+                sections.len() - 1
+            }
+        };
+
+        Thread::create_with(conn, data, &sections, &[], |sections| UpdateThread {
+            launch_sync: Some(LaunchSync {
+                synced_at:        Some(now),
+                launch:           Some(launch),
+                stats_section_id: Some(sections[stats_index].id),
+                conflicts:        vec![],
+            }),
+            ..UpdateThread::default()
+        })
+    }
+
+    /// Fetch the latest launch data for a `Thread`,
+    /// applying any changes to its T-0 and stats section.
+    ///
+    /// Values that have been manually edited since the last refresh are not overwritten;
+    /// they are instead recorded as conflicts in the thread's `launch_sync`.
+    ///
+    /// Returns the updated thread, and whether its rendered content may have changed.
+    pub fn refresh_launch(
+        conn: &Database,
+        id: i32,
+        provider: &dyn LaunchProvider,
+        now: i64,
+    ) -> Result<(Self, bool), LaunchError> {
+        let thread = Thread::find_id(conn, id)?;
+        let launch = match &thread.spacex__api_id {
+            Some(api_id) => provider.launch(api_id)?,
+            None => return Err(LaunchError::NoLaunch),
+        };
+        let previous = thread.launch_sync.launch.as_ref();
+
+        let mut changes = UpdateThread::default();
+        let mut conflicts = vec![];
+        let mut changed = false;
+
+        match reconcile(
+            &thread.space__t0,
+            &previous.and_then(Launch::t0),
+            &launch.t0(),
+        ) {
+            Reconciled::Unchanged => {}
+            Reconciled::Apply => changes.space__t0 = Some(launch.t0()),
+            Reconciled::Conflict => conflicts.push(LaunchConflict {
+                field:       "space__t0".into(),
+                detected_at: now,
+                launch:      json!(launch.t0()),
+            }),
+        }
+
+        let stats_section = match thread.launch_sync.stats_section_id {
+            Some(section_id) if thread.sections_id.contains(&section_id) => {
+                Some(Section::find_id(conn, section_id)?)
+            }
+            _ => None,
+        };
+        // A locked section is left alone until the next refresh,
+        // which must therefore compare against the same previous launch data.
+        let mut deferred = false;
+        if let Some((section, mut stats)) =
+            stats_section.and_then(|section| section.stats().map(|stats| (section, stats)))
+        {
+            let previous_stats = previous.map(Launch::stats).unwrap_or_default();
+            let mut modified = false;
+
+            for row in launch.stats().rows {
+                let reconciled = reconcile(
+                    &stats.get(&row.key),
+                    &previous_stats.get(&row.key),
+                    &Some(row.value.as_str()),
+                );
+
+                match reconciled {
+                    Reconciled::Unchanged => {}
+                    Reconciled::Apply => {
+                        stats.set(&row.key, row.value);
+                        modified = true;
+                    }
+                    Reconciled::Conflict => conflicts.push(LaunchConflict {
+                        field:       format!("stats.{}", row.key),
+                        detected_at: now,
+                        launch:      json!(row.value),
+                    }),
+                }
+            }
+
+            if modified && section.lock_held_by_user_id.is_some() {
+                deferred = true;
+            } else if modified {
+                Section::update(
+                    conn,
+                    section.id,
+                    &UpdateSection {
+                        data: Some(json!(stats)),
+                        ..UpdateSection::default()
+                    },
+                )?;
+                changed = true;
+            }
+        }
+
+        changed |= changes.space__t0.is_some();
+        changes.launch_sync = Some(LaunchSync {
+            synced_at: Some(now),
+            launch: if deferred {
+                previous.cloned()
+            } else {
+                Some(launch)
+            },
+            stats_section_id: thread.launch_sync.stats_section_id,
+            conflicts,
+        });
+
+        Ok((Thread::update(conn, id, &changes)?, changed))
+    }
+}
@@ -0,0 +1,436 @@
+use super::{
+    ExternalInsertThread,
+    RenderContext,
+    RenderError,
+    Repository,
+    Thread,
+    ToMarkdown,
+    User,
+};
+use crate::{
+    schema::thread,
+    websocket::{Action, DataType, Message, Room},
+    Database,
+};
+use derive_more::Display;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
+use rocket::{http::RawStr, request::FromFormValue};
+use rocket_contrib::databases::diesel::{
+    result::Error as DieselError,
+    Connection,
+    ExpressionMethods,
+    OptionalExtension,
+    QueryDsl,
+    QueryResult,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, io::Write, str::FromStr};
+
+/// The stage of a thread's life.
+#[derive(
+    Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum ThreadState {
+    /// Only visible to those able to modify the thread,
+    /// and not broadcast to WebSocket clients.
+    #[display(fmt = "draft")]
+    Draft,
+    /// To be posted automatically at the thread's `scheduled_at`.
+    #[display(fmt = "scheduled")]
+    Scheduled,
+    /// Posted (to Reddit, if the thread has a subreddit), but not yet live.
+    #[display(fmt = "posted")]
+    Posted,
+    #[display(fmt = "live")]
+    Live,
+    #[display(fmt = "concluded")]
+    Concluded,
+    /// No longer of interest; no further transitions are possible.
+    #[display(fmt = "archived")]
+    Archived,
+}
+
+impl ThreadState {
+    /// Can a thread move from this state to the provided state?
+    ///
+    /// A scheduled thread may be rescheduled,
+    /// and a concluded thread may go live again (such as after a scrub).
+    pub fn can_become(self, to: ThreadState) -> bool {
+        use ThreadState::*;
+
+        match (self, to) {
+            (Draft, Scheduled) | (Draft, Posted) => true,
+            (Scheduled, Draft) | (Scheduled, Scheduled) | (Scheduled, Posted) => true,
+            (Posted, Live) | (Posted, Concluded) => true,
+            (Live, Concluded) => true,
+            (Concluded, Live) | (Concluded, Archived) => true,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for ThreadState {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "draft" => Ok(ThreadState::Draft),
+            "scheduled" => Ok(ThreadState::Scheduled),
+            "posted" => Ok(ThreadState::Posted),
+            "live" => Ok(ThreadState::Live),
+            "concluded" => Ok(ThreadState::Concluded),
+            "archived" => Ok(ThreadState::Archived),
+            _ => Err("unknown thread state"),
+        }
+    }
+}
+
+impl<'v> FromFormValue<'v> for ThreadState {
+    type Error = &'v RawStr;
+
+    fn from_form_value(value: &'v RawStr) -> Result<Self, Self::Error> {
+        value.parse().map_err(|_| value)
+    }
+}
+
+impl FromSql<Text, Pg> for ThreadState {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        Ok(value.parse()?)
+    }
+}
+
+impl ToSql<Text, Pg> for ThreadState {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(&self.to_string(), out)
+    }
+}
+
+/// A request to move a thread to another state.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionRequest {
+    pub state:        ThreadState,
+    /// When to post the thread, for the `scheduled` state (a UNIX timestamp).
+    pub scheduled_at: Option<i64>,
+}
+
+/// A thread moving from one state to another, as broadcast to WebSocket clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transition {
+    pub id:      i32,
+    pub version: i32,
+    pub from:    ThreadState,
+    pub to:      ThreadState,
+    pub at:      i64,
+}
+
+/// Why a thread could not be moved to another state.
+#[derive(Debug, Display)]
+pub enum TransitionError {
+    #[display(fmt = "a thread cannot move from {} to {}", from, to)]
+    Invalid {
+        from: ThreadState,
+        to:   ThreadState,
+    },
+    #[display(fmt = "a scheduled thread must have a time to be posted")]
+    NoSchedule,
+    #[display(fmt = "the thread has already been posted")]
+    AlreadyPosted,
+    #[display(fmt = "the thread does not have a subreddit to post to")]
+    NoSubreddit,
+    #[display(fmt = "could not render the thread: {}", _0)]
+    Render(RenderError),
+    #[display(fmt = "could not post to Reddit: {}", _0)]
+    Reddit(String),
+    #[display(fmt = "the thread was changed by another request")]
+    Conflict,
+    #[display(fmt = "{}", _0)]
+    Database(DieselError),
+}
+
+impl Error for TransitionError {}
+
+impl From<DieselError> for TransitionError {
+    fn from(err: DieselError) -> Self {
+        TransitionError::Database(err)
+    }
+}
+
+/// Where threads are submitted once posted.
+pub trait Publisher: Send + Sync {
+    /// Submit a self post with the provided title and content to the subreddit,
+    /// on behalf of the user with the provided ID.
+    /// Returns the ID of the post.
+    fn submit(
+        &self,
+        conn: &Database,
+        user_id: i32,
+        subreddit: &str,
+        title: &str,
+        markdown: &str,
+    ) -> Result<String, TransitionError>;
+}
+
+/// Submits threads to Reddit itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RedditPublisher;
+
+impl Publisher for RedditPublisher {
+    fn submit(
+        &self,
+        conn: &Database,
+        user_id: i32,
+        subreddit: &str,
+        title: &str,
+        markdown: &str,
+    ) -> Result<String, TransitionError> {
+        let mut user: reddit::User<'_> = User::find_id(conn, user_id)?.into();
+        let post_id = user
+            .submit_self_post(subreddit, title, Some(markdown))
+            .map_err(|err| TransitionError::Reddit(err.to_string()))?;
+        User::update_access_token_if_necessary(conn, user_id, &mut user)?;
+
+        Ok(post_id)
+    }
+}
+
+/// Only these fields may be internally present when changing a thread's state.
+#[derive(Serialize, AsChangeset, Debug)]
+#[table_name = "thread"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ThreadStateChange {
+    pub state:        ThreadState,
+    pub scheduled_at: Option<i64>,
+    pub is_live:      bool,
+    pub post_id:      Option<String>,
+    pub subreddit:    Option<String>,
+}
+
+impl ExternalInsertThread {
+    /// The state a newly created thread should be in,
+    /// or `None` if the requested options conflict.
+    ///
+    /// Threads are posted immediately unless they are a draft or scheduled.
+    pub fn state(&self) -> Option<ThreadState> {
+        match (self.draft, self.scheduled_at, self.is_live.unwrap_or(false)) {
+            (false, None, false) => Some(ThreadState::Posted),
+            (false, None, true) => Some(ThreadState::Live),
+            (true, None, false) => Some(ThreadState::Draft),
+            (false, Some(_), false) => Some(ThreadState::Scheduled),
+            _ => None,
+        }
+    }
+}
+
+impl Thread {
+    /// Can the provided user (if any) see the thread?
+    ///
+    /// Drafts are only visible to those able to modify them:
+    /// their creator, hosts of their subreddit, and global admins.
+    pub fn is_visible_to(&self, user: Option<&User>) -> bool {
+        self.state != ThreadState::Draft
+            || user.map_or(false, |user| {
+                user.is_global_admin
+                    || user.is_host_for(self.subreddit.as_ref().map(String::as_str))
+                    || user.id == self.created_by_user_id
+            })
+    }
+
+    /// Find the IDs of all scheduled `Thread`s due to be posted at the provided time
+    /// (a UNIX timestamp).
+    pub fn find_scheduled_due(conn: &Database, now: i64) -> QueryResult<Vec<i32>> {
+        use crate::schema::thread::dsl::{id, scheduled_at, state, thread};
+
+        thread
+            .select(id)
+            .filter(state.eq(ThreadState::Scheduled))
+            .filter(scheduled_at.le(now))
+            .load(conn)
+    }
+
+    /// Submit the thread to the provided subreddit on behalf of its creator,
+    /// with its content fully rendered.
+    /// Returns the ID of the post.
+    fn submit_to_reddit(
+        &self,
+        conn: &Database,
+        publisher: &dyn Publisher,
+        subreddit: &str,
+    ) -> Result<String, TransitionError> {
+        let ctx = RenderContext::load(conn, self)?;
+        let markdown = self.to_markdown(&ctx).map_err(TransitionError::Render)?;
+
+        publisher.submit(
+            conn,
+            self.created_by_user_id,
+            subreddit,
+            &self.thread_name,
+            &markdown,
+        )
+    }
+
+    /// Move a thread to another state at the provided time (a UNIX timestamp).
+    ///
+    /// Moving a thread to `posted` submits it to Reddit if it has a subreddit
+    /// (and has not already been posted).
+    /// The transition is broadcast to both the `thread_create` room and that of the thread.
+    pub fn transition(
+        conn: &Database,
+        publisher: &dyn Publisher,
+        thread_id: i32,
+        request: &TransitionRequest,
+        now: i64,
+    ) -> Result<Self, TransitionError> {
+        let current = Thread::load_id(conn, thread_id)?;
+        let subreddit = current.subreddit.clone();
+
+        current.change_state(
+            conn,
+            publisher,
+            request.state,
+            request.scheduled_at,
+            subreddit,
+            now,
+        )
+    }
+
+    /// Post a draft or scheduled thread at the provided time (a UNIX timestamp),
+    /// submitting it to Reddit complete with its content.
+    ///
+    /// The subreddit, if provided, replaces that of the thread.
+    pub fn publish(
+        conn: &Database,
+        publisher: &dyn Publisher,
+        thread_id: i32,
+        subreddit: Option<String>,
+        now: i64,
+    ) -> Result<Self, TransitionError> {
+        let current = Thread::load_id(conn, thread_id)?;
+
+        if current.post_id.is_some() {
+            return Err(TransitionError::AlreadyPosted);
+        }
+        let subreddit = subreddit
+            .or_else(|| current.subreddit.clone())
+            .ok_or(TransitionError::NoSubreddit)?;
+
+        current.change_state(
+            conn,
+            publisher,
+            ThreadState::Posted,
+            None,
+            Some(subreddit),
+            now,
+        )
+    }
+
+    /// Move the thread (as currently stored) to another state,
+    /// belonging to the provided subreddit.
+    fn change_state(
+        self,
+        conn: &Database,
+        publisher: &dyn Publisher,
+        to: ThreadState,
+        scheduled_at: Option<i64>,
+        subreddit: Option<String>,
+        now: i64,
+    ) -> Result<Self, TransitionError> {
+        use crate::schema::thread::dsl::{id, thread, version};
+
+        let from = self.state;
+        if !from.can_become(to) {
+            return Err(TransitionError::Invalid { from, to });
+        }
+
+        let scheduled_at = match to {
+            ThreadState::Scheduled => Some(scheduled_at.ok_or(TransitionError::NoSchedule)?),
+            _ => None,
+        };
+
+        // The row is locked before anything is submitted to Reddit,
+        // so that a concurrent transition of the same version waits for this one
+        // and then fails, rather than posting the thread a second time.
+        let (result, changes) = conn.transaction::<_, TransitionError, _>(|| {
+            thread
+                .find(self.id)
+                .filter(version.eq(self.version))
+                .select(id)
+                .for_update()
+                .first::<i32>(conn)
+                .optional()?
+                .ok_or(TransitionError::Conflict)?;
+
+            let post_id = match &subreddit {
+                Some(subreddit) if to == ThreadState::Posted && self.post_id.is_none() => {
+                    Some(self.submit_to_reddit(conn, publisher, subreddit)?)
+                }
+                _ => self.post_id.clone(),
+            };
+
+            let changes = ThreadStateChange {
+                state: to,
+                scheduled_at,
+                is_live: to == ThreadState::Live,
+                post_id,
+                subreddit,
+            };
+            let result: Self = diesel::update(thread.filter(id.eq(self.id)))
+                .set((&changes, version.eq(version + 1)))
+                .get_result(conn)?;
+
+            Ok((result, changes))
+        })?;
+        Thread::updated(conn, &result, &changes);
+
+        let transition = Transition {
+            id: result.id,
+            version: result.version,
+            from,
+            to,
+            at: now,
+        };
+        // Clients have not been told of a draft, so are sent it in full once it leaves that state.
+        if from == ThreadState::Draft {
+            let _ = Message {
+                room:      Room::ThreadCreate,
+                action:    Action::Create,
+                data_type: DataType::Thread,
+                data:      &result,
+            }
+            .send();
+        }
+        // A thread returning to a draft is hidden again, so clients are told to drop it.
+        if to == ThreadState::Draft {
+            for &room in &[Room::ThreadCreate, Room::Thread(result.id)] {
+                let _ = Message {
+                    room,
+                    action: Action::Delete,
+                    data_type: DataType::Thread,
+                    data: &json!({ "id": result.id }),
+                }
+                .send();
+            }
+        } else {
+            for &room in &[Room::ThreadCreate, Room::Thread(result.id)] {
+                let _ = Message {
+                    room,
+                    action: Action::Transition,
+                    data_type: DataType::Thread,
+                    data: &transition,
+                }
+                .send();
+            }
+        }
+
+        Ok(result)
+    }
+}
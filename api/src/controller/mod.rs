@@ -1,23 +1,81 @@
-use crate::Database;
-use std::error::Error;
-
-const EVENT_CACHE_SIZE: usize = 100;
-const SECTION_CACHE_SIZE: usize = 50;
-const THREAD_CACHE_SIZE: usize = 5;
-const USER_CACHE_SIZE: usize = 100;
+use cache::{
+    invalidate,
+    EVENT_CACHE_SIZE,
+    PRESET_EVENT_CACHE_SIZE,
+    RENDER_CACHE_SIZE,
+    SECTION_CACHE_SIZE,
+    THREAD_CACHE_SIZE,
+    THREAD_TEMPLATE_CACHE_SIZE,
+    USER_CACHE_SIZE,
+};
 
 pub trait ToMarkdown {
-    fn to_markdown(&self, conn: &Database) -> Result<String, Box<dyn Error>>;
+    fn to_markdown(&self, ctx: &RenderContext) -> Result<String, RenderError>;
+}
+
+pub trait ToHtml {
+    fn to_html(&self, ctx: &RenderContext) -> Result<String, RenderError>;
 }
 
+#[macro_use]
+mod query;
+#[macro_use]
+mod repository;
+
+mod archive;
+mod batch;
+mod cache;
 mod claim;
+mod clock;
+mod clone;
+mod column;
 mod event;
+mod launch;
+mod lifecycle;
+mod preset_event;
+mod preview;
+mod render;
+mod scrub;
 mod section;
+mod section_kind;
+mod stats;
 mod thread;
+mod thread_template;
+mod time_format;
+mod transfer;
 mod user;
+mod webhook;
 
+pub use archive::*;
+pub use batch::*;
+pub use cache::{
+    cache_size,
+    set_invalidation_channel,
+    Cache,
+    CacheKey,
+    InvalidationChannel,
+    NoInvalidation,
+    PgNotify,
+};
 pub use claim::*;
+pub use clock::*;
+pub use clone::*;
+pub use column::*;
 pub use event::*;
+pub use launch::*;
+pub use lifecycle::*;
+pub use preset_event::*;
+pub use preview::*;
+pub use query::*;
+pub use render::*;
+pub use repository::{Found, Repository};
+pub use scrub::*;
 pub use section::*;
+pub use section_kind::*;
+pub use stats::*;
 pub use thread::*;
+pub use thread_template::*;
+pub use time_format::*;
+pub use transfer::*;
 pub use user::*;
+pub use webhook::*;
@@ -0,0 +1,261 @@
+use super::{
+    format_t_minus,
+    Cache,
+    CacheKey,
+    Page,
+    Repository,
+    Sort,
+    Thread,
+    User,
+    PRESET_EVENT_CACHE_SIZE,
+};
+use crate::{
+    schema::preset_event,
+    websocket::{Action, DataType, Room},
+    Database,
+};
+use derive_more::Display;
+use macros::generate_structs;
+use once_cell::sync::Lazy;
+use rocket::FromForm;
+use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{error::Error, str::FromStr};
+
+/// Replaced with the current time when a preset is instantiated.
+const UTC_PLACEHOLDER: &str = "{utc}";
+
+/// Replaced with the current time relative to the thread's T-0
+/// when a preset is instantiated.
+const T_MINUS_PLACEHOLDER: &str = "{t_minus}";
+
+/// A global cache, containing a mapping of IDs to their respective `PresetEvent`.
+static CACHE: Lazy<Cache<PresetEvent>> = Lazy::new(|| Cache::new(*PRESET_EVENT_CACHE_SIZE));
+
+generate_structs! {
+    PresetEvent("preset_event") {
+        auto id: i32,
+        name: String,
+        cols: serde_json::Value,
+        readonly subreddit: Option<String>,
+        readonly created_by_user_id: i32,
+        auto version: i32,
+    }
+}
+
+// The creator of a preset is always the current user.
+// Use an `ExternalInsertPresetEvent` wherever user input is expected.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalInsertPresetEvent {
+    pub name:      String,
+    pub cols:      serde_json::Value,
+    pub subreddit: Option<String>,
+}
+
+/// Criteria that all `PresetEvent`s returned by `PresetEvent::find_all` must match.
+/// Any criteria not provided are not checked.
+#[derive(Debug, Default, FromForm)]
+pub struct PresetEventFilter {
+    pub subreddit:          Option<String>,
+    pub created_by_user_id: Option<i32>,
+}
+
+/// Fields that a list of `PresetEvent`s can be sorted by.
+#[derive(Debug, Clone, Copy)]
+pub enum PresetEventSortKey {
+    Id,
+    Name,
+}
+
+impl FromStr for PresetEventSortKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "id" => Ok(PresetEventSortKey::Id),
+            "name" => Ok(PresetEventSortKey::Name),
+            _ => Err("unknown sort key"),
+        }
+    }
+}
+
+/// Why a preset could not be instantiated.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderError {
+    #[display(fmt = "expected columns to be an array")]
+    NotAnArray,
+    #[display(fmt = "the thread does not have a T-0")]
+    NoT0,
+}
+
+impl Error for PlaceholderError {}
+
+impl PresetEvent {
+    /// Find a page of `PresetEvent`s in the database matching the provided filter.
+    /// Unless otherwise specified, the results are sorted by ID.
+    ///
+    /// Presets without a subreddit are only included if they are visible to the provided user.
+    ///
+    /// Does _not_ use cache (reading or writing),
+    /// so as to avoid storing values rarely accessed.
+    pub fn find_all(
+        conn: &Database,
+        filter: &PresetEventFilter,
+        sort: Option<Sort<PresetEventSortKey>>,
+        page: Page,
+        viewer: Option<&User>,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::preset_event::dsl::{
+            created_by_user_id,
+            id,
+            name,
+            preset_event,
+            subreddit,
+        };
+
+        let mut query = preset_event.into_boxed();
+
+        match viewer {
+            Some(user) if user.is_global_admin => {}
+            Some(user) => {
+                query = query.filter(subreddit.is_not_null().or(created_by_user_id.eq(user.id)))
+            }
+            None => query = query.filter(subreddit.is_not_null()),
+        }
+
+        if let Some(value) = &filter.subreddit {
+            query = query.filter(subreddit.eq(value));
+        }
+        if let Some(value) = filter.created_by_user_id {
+            query = query.filter(created_by_user_id.eq(value));
+        }
+
+        let sort = sort.unwrap_or_else(|| Sort::ascending(PresetEventSortKey::Id));
+        query = match sort.key {
+            PresetEventSortKey::Id => order_by!(query, id, sort),
+            PresetEventSortKey::Name => order_by!(query, name, sort),
+        };
+
+        query
+            .then_order_by(id.asc())
+            .limit(page.limit)
+            .offset(page.offset)
+            .load(conn)
+    }
+
+    /// Create a `PresetEvent` given the data provided by a user,
+    /// along with the ID of the user.
+    ///
+    /// The inserted row is added to the global cache and returned.
+    pub fn create(
+        conn: &Database,
+        data: &ExternalInsertPresetEvent,
+        user_id: i32,
+    ) -> QueryResult<Self> {
+        let insertable_preset = InsertPresetEvent {
+            name: data.name.clone(),
+            cols: data.cols.clone(),
+            subreddit: data.subreddit.clone(),
+            created_by_user_id: user_id,
+        };
+
+        <Self as Repository>::create(conn, &insertable_preset)
+    }
+
+    /// Can the provided user (if any) see the preset?
+    ///
+    /// Presets belonging to a subreddit are visible to everyone;
+    /// those without a subreddit are only visible to their creator and global admins.
+    pub fn is_visible_to(&self, user: Option<&User>) -> bool {
+        self.subreddit.is_some()
+            || user.map_or(false, |user| {
+                user.is_global_admin || user.id == self.created_by_user_id
+            })
+    }
+
+    /// Can the preset be used in the provided thread?
+    ///
+    /// Presets belonging to a subreddit can only be used in threads of that subreddit.
+    /// Presets without a subreddit can only be used by the user that created them.
+    pub fn is_available_to(&self, thread: &Thread, user_id: i32) -> bool {
+        match &self.subreddit {
+            Some(subreddit) => thread.subreddit.as_ref().map_or(false, |thread_subreddit| {
+                thread_subreddit.eq_ignore_ascii_case(subreddit)
+            }),
+            None => self.created_by_user_id == user_id,
+        }
+    }
+
+    /// Create the columns of an event from the preset,
+    /// substituting placeholders for the provided thread at the provided time
+    /// (a UNIX timestamp).
+    ///
+    /// A value consisting solely of a placeholder is replaced with an integer,
+    /// suitable for `utc` and `t_minus` columns respectively.
+    /// The T-time takes into account any hold of the thread's countdown.
+    /// Placeholders within a longer string are replaced with their formatted value,
+    /// according to the thread's `TimeFormat`.
+    pub fn instantiate(&self, thread: &Thread, now: i64) -> Result<Value, PlaceholderError> {
+        let values = self.cols.as_array().ok_or(PlaceholderError::NotAnArray)?;
+        let t_minus = thread.t_minus(now);
+        let time = thread.time_format.renderer(thread.space__t0, None);
+
+        values
+            .iter()
+            .map(|value| match value {
+                Value::String(s) if s == UTC_PLACEHOLDER => Ok(now.into()),
+                Value::String(s) if s == T_MINUS_PLACEHOLDER => {
+                    t_minus.map(Into::into).ok_or(PlaceholderError::NoT0)
+                }
+                Value::String(s) => {
+                    let mut s = s.replace(UTC_PLACEHOLDER, &time.render(now).unwrap_or_default());
+
+                    if s.contains(T_MINUS_PLACEHOLDER) {
+                        let t_minus = t_minus.ok_or(PlaceholderError::NoT0)?;
+                        s = s.replace(T_MINUS_PLACEHOLDER, &format_t_minus(t_minus));
+                    }
+
+                    Ok(Value::String(s))
+                }
+                value => Ok(value.clone()),
+            })
+            .collect::<Result<_, _>>()
+            .map(Value::Array)
+    }
+}
+
+impl Repository for PresetEvent {
+    type Insert = InsertPresetEvent;
+    type Update = UpdatePresetEvent;
+
+    const DATA_TYPE: DataType = DataType::PresetEvent;
+
+    repository_primitives!(preset_event, versioned);
+
+    fn cache() -> &'static Cache<Self> {
+        &CACHE
+    }
+
+    fn cache_key(id: i32) -> CacheKey {
+        CacheKey::PresetEvent(id)
+    }
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn version(&self) -> Option<i32> {
+        Some(self.version)
+    }
+
+    fn room(&self, _action: Action) -> Room {
+        Room::Preset
+    }
+
+    /// Presets without a subreddit are private to their creator.
+    fn is_public(&self, _conn: &Database) -> bool {
+        self.subreddit.is_some()
+    }
+}
@@ -0,0 +1,259 @@
+use super::{
+    section_kind,
+    ColumnError,
+    Event,
+    MarkdownKind,
+    RenderContext,
+    RenderError,
+    Repository,
+    Section,
+    SectionKindError,
+    Thread,
+    ToMarkdown,
+};
+use crate::Database;
+use derive_more::Display;
+use rocket_contrib::databases::diesel::result::Error as DieselError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{error::Error, fmt::Write};
+
+/// A proposed change to a section of a thread.
+///
+/// Without an ID, a new section is added after all others.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionChange {
+    pub id:      Option<i32>,
+    pub name:    Option<String>,
+    pub content: Option<String>,
+    pub kind:    Option<String>,
+    pub data:    Option<Value>,
+    #[serde(default)]
+    pub delete:  bool,
+}
+
+/// A proposed change to an event of a thread.
+///
+/// Without an ID, a new event is added after all others.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventChange {
+    pub id:     Option<i32>,
+    pub cols:   Option<Value>,
+    pub posted: Option<bool>,
+    #[serde(default)]
+    pub delete: bool,
+}
+
+/// Changes to a thread to preview, applied in order.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreviewRequest {
+    #[serde(default)]
+    pub sections: Vec<SectionChange>,
+    #[serde(default)]
+    pub events:   Vec<EventChange>,
+}
+
+/// The thread as it would be posted to Reddit were the changes made.
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    pub markdown:   String,
+    /// A line-by-line diff against the thread as currently saved,
+    /// with each line prefixed by `+` (added), `-` (removed), or a space (unchanged).
+    ///
+    /// This is not fetched from Reddit,
+    /// so does not reflect any edits made there directly.
+    pub saved_diff: String,
+    pub changed:    bool,
+}
+
+/// Why a preview could not be created.
+#[derive(Debug, Display)]
+pub enum PreviewError {
+    #[display(fmt = "section {} is not in the thread", _0)]
+    UnknownSection(i32),
+    #[display(fmt = "event {} is not in the thread", _0)]
+    UnknownEvent(i32),
+    #[display(fmt = "a new section must have a name")]
+    MissingName,
+    #[display(fmt = "a new event must have columns")]
+    MissingCols,
+    #[display(fmt = "{}", _0)]
+    Section(SectionKindError),
+    #[display(fmt = "{}", _0)]
+    Event(ColumnError),
+    #[display(fmt = "could not render the thread: {}", _0)]
+    Render(RenderError),
+    #[display(fmt = "{}", _0)]
+    Database(DieselError),
+}
+
+impl Error for PreviewError {}
+
+impl From<DieselError> for PreviewError {
+    fn from(err: DieselError) -> Self {
+        PreviewError::Database(err)
+    }
+}
+
+/// Apply the changes to the sections of a thread, in memory.
+///
+/// New sections are given negative IDs, so as to never conflict with existing sections.
+fn apply_section_changes(
+    thread_id: i32,
+    mut sections: Vec<Section>,
+    changes: &[SectionChange],
+) -> Result<Vec<Section>, PreviewError> {
+    let mut next_id = -1;
+
+    for change in changes {
+        let index = match change.id {
+            Some(id) => sections
+                .iter()
+                .position(|section| section.id == id)
+                .ok_or(PreviewError::UnknownSection(id))?,
+            None => {
+                sections.push(Section {
+                    id: next_id,
+                    name: change.name.clone().ok_or(PreviewError::MissingName)?,
+                    content: String::new(),
+                    lock_held_by_user_id: None,
+                    in_thread_id: thread_id,
+                    lock_assigned_at_utc: 0,
+                    version: 0,
+                    kind: MarkdownKind::NAME.into(),
+                    data: Value::Null,
+                });
+                next_id -= 1;
+                sections.len() - 1
+            }
+        };
+
+        if change.delete {
+            sections.remove(index);
+            continue;
+        }
+
+        let section = &mut sections[index];
+        if let Some(name) = &change.name {
+            section.name = name.clone();
+        }
+        if let Some(content) = &change.content {
+            section.content = content.clone();
+        }
+        if let Some(kind) = &change.kind {
+            section.kind = kind.clone();
+        }
+        if let Some(data) = &change.data {
+            section.data = data.clone();
+        }
+        section_kind(&section.kind)
+            .and_then(|kind| kind.check(&section.data))
+            .map_err(PreviewError::Section)?;
+    }
+
+    Ok(sections)
+}
+
+/// Apply the changes to the events of a thread, in memory.
+///
+/// New events are given negative IDs, so as to never conflict with existing events.
+fn apply_event_changes(
+    thread: &Thread,
+    mut events: Vec<Event>,
+    changes: &[EventChange],
+) -> Result<Vec<Event>, PreviewError> {
+    let mut next_id = -1;
+
+    for change in changes {
+        let index = match change.id {
+            Some(id) => events
+                .iter()
+                .position(|event| event.id == id)
+                .ok_or(PreviewError::UnknownEvent(id))?,
+            None => {
+                events.push(Event {
+                    id:           next_id,
+                    posted:       false,
+                    in_thread_id: thread.id,
+                    cols:         change.cols.clone().ok_or(PreviewError::MissingCols)?,
+                    post_at:      None,
+                    version:      0,
+                });
+                next_id -= 1;
+                events.len() - 1
+            }
+        };
+
+        if change.delete {
+            events.remove(index);
+            continue;
+        }
+
+        let event = &mut events[index];
+        if let Some(cols) = &change.cols {
+            event.cols = thread
+                .event_columns
+                .validate(cols)
+                .map_err(PreviewError::Event)?;
+        }
+        if let Some(posted) = change.posted {
+            event.posted = posted;
+        }
+    }
+
+    Ok(events)
+}
+
+/// A line-by-line diff between two strings.
+fn diff_lines(saved: &str, proposed: &str) -> String {
+    let mut out = String::new();
+
+    for line in diff::lines(saved, proposed) {
+        // Writing to a `String` cannot fail.
+        let _ = match line {
+            diff::Result::Left(line) => writeln!(&mut out, "-{}", line),
+            diff::Result::Right(line) => writeln!(&mut out, "+{}", line),
+            diff::Result::Both(line, _) => writeln!(&mut out, " {}", line),
+        };
+    }
+
+    out
+}
+
+impl Thread {
+    /// Render the thread as it would be posted to Reddit were the proposed changes made,
+    /// along with a diff against the thread as currently saved.
+    ///
+    /// Nothing is written to the database, broadcast to WebSocket clients, or sent to Reddit.
+    /// Values not already cached are read without being added to the cache.
+    pub fn preview(
+        conn: &Database,
+        id: i32,
+        request: &PreviewRequest,
+    ) -> Result<Preview, PreviewError> {
+        let thread = Thread::peek_id(conn, id)?;
+        let sections = Section::peek_ids(conn, &thread.sections_id)?.values;
+        let events = Event::peek_ids(conn, &thread.events_id)?.values;
+
+        let ctx = RenderContext::new(thread.clone(), sections.clone(), events.clone());
+        let saved = thread.to_markdown(&ctx).map_err(PreviewError::Render)?;
+
+        let sections = apply_section_changes(thread.id, sections, &request.sections)?;
+        let events = apply_event_changes(&thread, events, &request.events)?;
+
+        let mut proposed = thread;
+        proposed.sections_id = sections.iter().map(|section| section.id).collect();
+        proposed.events_id = events.iter().map(|event| event.id).collect();
+        let ctx = RenderContext::new(proposed.clone(), sections, events);
+        let markdown = proposed.to_markdown(&ctx).map_err(PreviewError::Render)?;
+
+        Ok(Preview {
+            saved_diff: diff_lines(&saved, &markdown),
+            changed: saved != markdown,
+            markdown,
+        })
+    }
+}
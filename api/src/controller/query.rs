@@ -0,0 +1,72 @@
+use std::str::FromStr;
+
+/// The number of rows returned when the client does not specify a limit.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// The maximum number of rows that can be returned at once.
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Order a boxed query by the provided column,
+/// in the direction indicated by the `Sort`.
+macro_rules! order_by {
+    ($query:expr, $column:expr, $sort:expr) => {
+        if $sort.descending {
+            $query.order($column.desc())
+        } else {
+            $query.order($column.asc())
+        }
+    };
+}
+
+/// A window into a list of rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit:  i64,
+    pub offset: i64,
+}
+
+impl Page {
+    /// Create a `Page` from the (optional) values provided by the client,
+    /// clamping them to sensible values.
+    pub fn new(limit: Option<i64>, offset: Option<i64>) -> Self {
+        Self {
+            limit:  limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1).min(MAX_PAGE_SIZE),
+            offset: offset.unwrap_or(0).max(0),
+        }
+    }
+}
+
+/// A key to sort by, along with the direction.
+///
+/// When parsed from a string,
+/// a leading `-` indicates the sort should be descending.
+#[derive(Debug, Clone, Copy)]
+pub struct Sort<K> {
+    pub key:        K,
+    pub descending: bool,
+}
+
+impl<K> Sort<K> {
+    /// Sort by the provided key in ascending order.
+    pub const fn ascending(key: K) -> Self {
+        Self {
+            key,
+            descending: false,
+        }
+    }
+}
+
+impl<K: FromStr> FromStr for Sort<K> {
+    type Err = K::Err;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        if string.starts_with('-') {
+            Ok(Self {
+                key:        string[1..].parse()?,
+                descending: true,
+            })
+        } else {
+            Ok(Self::ascending(string.parse()?))
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use super::{
+    events_table,
+    Cache,
+    CacheKey,
+    ColumnError,
+    Event,
+    Repository,
+    Section,
+    SectionKindError,
+    Thread,
+    TimeRenderer,
+    ToHtml,
+    ToMarkdown,
+    RENDER_CACHE_SIZE,
+};
+use crate::Database;
+use derive_more::Display;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, Options, Parser};
+use rocket::http::ContentType;
+use rocket_contrib::databases::diesel::QueryResult;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Write},
+    slice,
+    str::FromStr,
+};
+
+/// Global caches, containing a mapping of thread IDs to their rendered content.
+static MARKDOWN_CACHE: Lazy<Cache<String>> = Lazy::new(|| Cache::new(*RENDER_CACHE_SIZE));
+static HTML_CACHE: Lazy<Cache<String>> = Lazy::new(|| Cache::new(*RENDER_CACHE_SIZE));
+
+/// Everything needed to render a thread, loaded ahead of time.
+///
+/// Rendering only ever reads from the context, and never from the database.
+/// As such, a thread can be rendered with changes that have not been saved.
+#[derive(Debug, Clone)]
+pub struct RenderContext {
+    thread:   Thread,
+    sections: HashMap<i32, Section>,
+    events:   Vec<Event>,
+    time:     TimeRenderer,
+}
+
+impl RenderContext {
+    /// Create a context from a thread, its sections (in any order), and its events (in order).
+    pub fn new(thread: Thread, sections: Vec<Section>, events: Vec<Event>) -> Self {
+        RenderContext {
+            time: thread.time_renderer(&events),
+            sections: sections
+                .into_iter()
+                .map(|section| (section.id, section))
+                .collect(),
+            events,
+            thread,
+        }
+    }
+
+    /// Load the sections and events of a thread, checking the cache first.
+    pub fn load(conn: &Database, thread: &Thread) -> QueryResult<Self> {
+        let sections = Section::find_ids(conn, &thread.sections_id)?.values;
+        let events = Event::find_ids(conn, &thread.events_id)?.values;
+
+        Ok(Self::new(thread.clone(), sections, events))
+    }
+
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// The section with the provided ID.
+    pub fn section(&self, id: i32) -> Result<&Section, RenderError> {
+        self.sections
+            .get(&id)
+            .ok_or(RenderError::MissingSection(id))
+    }
+
+    /// All events of the thread, in order.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// The renderer for the thread's timestamps.
+    pub fn time(&self) -> &TimeRenderer {
+        &self.time
+    }
+}
+
+/// Why a thread (or part of it) could not be rendered.
+#[derive(Debug, Display, Clone, PartialEq)]
+pub enum RenderError {
+    #[display(fmt = "section {} is not in the render context", _0)]
+    MissingSection(i32),
+    #[display(fmt = "section {}: {}", section_id, error)]
+    Section {
+        section_id: i32,
+        error:      SectionKindError,
+    },
+    #[display(fmt = "event {}: {}", event_id, error)]
+    Event {
+        event_id: i32,
+        error:    ColumnError,
+    },
+    #[display(fmt = "{}", _0)]
+    Format(fmt::Error),
+}
+
+impl Error for RenderError {}
+
+impl From<fmt::Error> for RenderError {
+    fn from(err: fmt::Error) -> Self {
+        RenderError::Format(err)
+    }
+}
+
+/// A format a thread can be rendered to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// Reddit-flavored markdown, as posted to Reddit.
+    Markdown,
+    /// Sanitized HTML, suitable for embedding in a website.
+    Html,
+}
+
+impl FromStr for RenderFormat {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "markdown" => Ok(RenderFormat::Markdown),
+            "html" => Ok(RenderFormat::Html),
+            _ => Err("unknown format"),
+        }
+    }
+}
+
+impl RenderFormat {
+    /// The type of content rendered in this format.
+    pub fn content_type(self) -> ContentType {
+        match self {
+            RenderFormat::Markdown => ContentType::new("text", "markdown"),
+            RenderFormat::Html => ContentType::HTML,
+        }
+    }
+
+    fn cache(self) -> &'static Cache<String> {
+        match self {
+            RenderFormat::Markdown => &MARKDOWN_CACHE,
+            RenderFormat::Html => &HTML_CACHE,
+        }
+    }
+}
+
+/// Convert markdown to HTML, removing anything unsafe to embed
+/// (such as scripts, styles, and event handlers).
+///
+/// Tables and strikethrough are supported, as they are on Reddit.
+pub fn markdown_to_html(md: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(md, options));
+
+    ammonia::clean(&unsafe_html)
+}
+
+/// Escape text for inclusion in HTML.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Evict the rendered content of anything affected by a change to the value.
+///
+/// Rendering a thread involves its sections and events,
+/// which do not necessarily identify their thread once changed (or deleted).
+/// As such, a change to any section or event evicts everything.
+pub(super) fn evict_rendered(key: CacheKey) {
+    match key {
+        CacheKey::Thread(id) => {
+            MARKDOWN_CACHE.remove(id);
+            HTML_CACHE.remove(id);
+        }
+        CacheKey::Section(_) | CacheKey::Event(_) => clear_rendered(),
+        CacheKey::PresetEvent(_) | CacheKey::ThreadTemplate(_) | CacheKey::User(_) => {}
+    }
+}
+
+/// Remove all rendered content.
+pub(super) fn clear_rendered() {
+    MARKDOWN_CACHE.clear();
+    HTML_CACHE.clear();
+}
+
+/// The number of hits and misses of the render caches since this was last called.
+pub fn take_render_stats() -> (usize, usize) {
+    let (markdown_hits, markdown_misses) = MARKDOWN_CACHE.take_stats();
+    let (html_hits, html_misses) = HTML_CACHE.take_stats();
+
+    (markdown_hits + html_hits, markdown_misses + html_misses)
+}
+
+impl Thread {
+    /// Render the thread with the provided ID, including all its sections.
+    ///
+    /// The result is cached until the thread, or any section or event, is changed.
+    pub fn render(
+        conn: &Database,
+        id: i32,
+        format: RenderFormat,
+    ) -> Result<String, Box<dyn Error>> {
+        if let Some(rendered) = format.cache().get(id) {
+            return Ok(rendered);
+        }
+
+        let thread = Thread::find_id(conn, id)?;
+        let ctx = RenderContext::load(conn, &thread)?;
+        let rendered = match format {
+            RenderFormat::Markdown => thread.to_markdown(&ctx)?,
+            RenderFormat::Html => thread.to_html(&ctx)?,
+        };
+        format.cache().insert(id, rendered.clone());
+
+        Ok(rendered)
+    }
+}
+
+impl ToHtml for Thread {
+    /// Convert the `Thread` object to sanitized HTML,
+    /// with each section in order.
+    fn to_html(&self, ctx: &RenderContext) -> Result<String, RenderError> {
+        let mut html = String::new();
+
+        writeln!(&mut html, "<article>")?;
+        for &section_id in &self.sections_id {
+            writeln!(&mut html, "{}", ctx.section(section_id)?.to_html(ctx)?)?;
+        }
+        write!(&mut html, "</article>")?;
+
+        Ok(html)
+    }
+}
+
+impl ToHtml for Section {
+    /// Convert the `Section` object to sanitized HTML.
+    ///
+    /// The section is rendered exactly as it is for Reddit,
+    /// with the resulting markdown converted to HTML.
+    fn to_html(&self, ctx: &RenderContext) -> Result<String, RenderError> {
+        Ok(format!(
+            "<section>\n{}</section>",
+            markdown_to_html(&self.to_markdown(ctx)?)
+        ))
+    }
+}
+
+impl ToHtml for Event {
+    /// Convert the `Event` object to a sanitized HTML table,
+    /// with a header containing the name of each column.
+    ///
+    /// An unposted event has no content.
+    /// The context must be that of the event's thread.
+    fn to_html(&self, ctx: &RenderContext) -> Result<String, RenderError> {
+        if !self.posted {
+            return Ok("".into());
+        }
+
+        let md = events_table(
+            &ctx.thread().event_columns,
+            slice::from_ref(self),
+            ctx.time(),
+        )?;
+
+        Ok(markdown_to_html(&md))
+    }
+}
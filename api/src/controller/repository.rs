@@ -0,0 +1,388 @@
+use super::{invalidate, Cache, CacheKey};
+use crate::{
+    websocket::{Action, DataType, Message, Room, Update},
+    Database,
+};
+use rocket_contrib::databases::diesel::QueryResult;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Implement the primitive database operations of `Repository`
+/// (`load_id`, `load_ids`, `insert_row`, `update_row`, and `delete_row`)
+/// for the provided table.
+///
+/// Pass `versioned` for tables with a `version` column.
+/// The version is then incremented on every update,
+/// and checked against the expected version (if any).
+macro_rules! repository_primitives {
+    (@common $table:ident) => {
+        fn load_id(
+            conn: &crate::Database,
+            row_id: i32,
+        ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+            use crate::schema::$table::dsl::$table;
+            use rocket_contrib::databases::diesel::{QueryDsl, RunQueryDsl};
+
+            $table.find(row_id).first(conn)
+        }
+
+        fn load_ids(
+            conn: &crate::Database,
+            row_ids: &[i32],
+        ) -> rocket_contrib::databases::diesel::QueryResult<Vec<Self>> {
+            use crate::schema::$table::dsl::{id, $table};
+            use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+            $table.filter(id.eq_any(row_ids)).load(conn)
+        }
+
+        fn insert_row(
+            conn: &crate::Database,
+            data: &Self::Insert,
+        ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+            use crate::schema::$table::dsl::$table;
+            use rocket_contrib::databases::diesel::RunQueryDsl;
+
+            diesel::insert_into($table).values(data).get_result(conn)
+        }
+
+        fn delete_row(
+            conn: &crate::Database,
+            row_id: i32,
+        ) -> rocket_contrib::databases::diesel::QueryResult<usize> {
+            use crate::schema::$table::dsl::$table;
+            use rocket_contrib::databases::diesel::{QueryDsl, RunQueryDsl};
+
+            diesel::delete($table.find(row_id)).execute(conn)
+        }
+    };
+
+    ($table:ident) => {
+        repository_primitives!(@common $table);
+
+        fn update_row(
+            conn: &crate::Database,
+            row_id: i32,
+            _expected_version: Option<i32>,
+            data: &Self::Update,
+        ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+            use crate::schema::$table::dsl::$table;
+            use rocket_contrib::databases::diesel::{QueryDsl, RunQueryDsl};
+
+            diesel::update($table.find(row_id)).set(data).get_result(conn)
+        }
+    };
+
+    ($table:ident, versioned) => {
+        repository_primitives!(@common $table);
+
+        fn update_row(
+            conn: &crate::Database,
+            row_id: i32,
+            expected_version: Option<i32>,
+            data: &Self::Update,
+        ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+            use crate::schema::$table::dsl::{$table, version};
+            use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+            let query = diesel::update($table.find(row_id));
+            let changes = (data, version.eq(version + 1));
+            match expected_version {
+                Some(expected_version) => query
+                    .filter(version.eq(expected_version))
+                    .set(changes)
+                    .get_result(conn),
+                None => query.set(changes).get_result(conn),
+            }
+        }
+    };
+}
+
+/// The result of finding a number of values by their IDs.
+#[derive(Debug, Clone)]
+pub struct Found<T> {
+    /// The values that were found, in the order requested.
+    pub values:  Vec<T>,
+    /// The IDs that do not correspond to any value, in the order requested.
+    pub missing: Vec<i32>,
+}
+
+/// A type stored in the database, with a global cache in front of it.
+///
+/// Implementors provide the primitive database operations
+/// (typically via `repository_primitives!`)
+/// and the location of their cache.
+/// In return, they receive cached lookups,
+/// along with creation, updates, and deletion that keep the cache coherent
+/// and inform both WebSocket clients and other instances of any changes.
+///
+/// Custom behaviour can be added with the `after_create`, `after_update`,
+/// and `before_delete` hooks.
+pub trait Repository: Clone + Serialize + Sized + 'static {
+    /// The data necessary to create a value.
+    type Insert;
+
+    /// The data that may be changed on an existing value.
+    type Update: Serialize;
+
+    /// How the type is indicated in WebSocket messages.
+    const DATA_TYPE: DataType;
+
+    /// The global cache for this type.
+    fn cache() -> &'static Cache<Self>;
+
+    /// The key used when informing other instances of a change.
+    fn cache_key(id: i32) -> CacheKey;
+
+    /// The ID of the value.
+    fn id(&self) -> i32;
+
+    /// The current version of the value,
+    /// if the type is versioned.
+    fn version(&self) -> Option<i32> {
+        None
+    }
+
+    /// The WebSocket room that should be informed of the action.
+    fn room(&self, action: Action) -> Room;
+
+    /// Can everyone be informed of the value?
+    ///
+    /// WebSocket clients are not authenticated,
+    /// so nothing is broadcast about values that are not public.
+    fn is_public(&self, _conn: &Database) -> bool {
+        true
+    }
+
+    /// Load a value from the database, bypassing the cache.
+    fn load_id(conn: &Database, id: i32) -> QueryResult<Self>;
+
+    /// Load all values with the provided IDs from the database, bypassing the cache.
+    /// The values may be returned in any order.
+    fn load_ids(conn: &Database, ids: &[i32]) -> QueryResult<Vec<Self>>;
+
+    /// Insert a row into the database.
+    fn insert_row(conn: &Database, data: &Self::Insert) -> QueryResult<Self>;
+
+    /// Update a row in the database,
+    /// provided the stored version matches `expected_version` (if any).
+    /// Returns `NotFound` if no row is updated.
+    fn update_row(
+        conn: &Database,
+        id: i32,
+        expected_version: Option<i32>,
+        data: &Self::Update,
+    ) -> QueryResult<Self>;
+
+    /// Delete a row from the database,
+    /// returning the number of rows deleted.
+    fn delete_row(conn: &Database, id: i32) -> QueryResult<usize>;
+
+    /// Called once a value has been created and broadcast.
+    fn after_create(&self, _conn: &Database) -> QueryResult<()> {
+        Ok(())
+    }
+
+    /// Called once a value has been updated and broadcast.
+    fn after_update(&self, _conn: &Database, _changes: &Self::Update) {}
+
+    /// Called before a value is deleted.
+    /// Returning an error prevents the deletion.
+    fn before_delete(&self, _conn: &Database) -> QueryResult<()> {
+        Ok(())
+    }
+
+    /// Find a given value by its ID.
+    ///
+    /// Internally uses a cache to limit database accesses.
+    fn find_id(conn: &Database, id: i32) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+        if let Some(value) = Self::cache().get(id) {
+            return Ok(value);
+        }
+
+        let value = Self::load_id(conn, id)?;
+        Self::cache().insert(id, value.clone());
+        Ok(value)
+    }
+
+    /// Find a number of values given their IDs.
+    ///
+    /// The cache is checked first,
+    /// with any remaining values fetched in a single query.
+    /// IDs that do not correspond to any value are reported, rather than being an error.
+    fn find_ids(conn: &Database, ids: &[i32]) -> QueryResult<Found<Self>> {
+        lookup_ids(conn, ids, true)
+    }
+
+    /// Find a given value by its ID,
+    /// without adding it to the cache if it must be loaded from the database.
+    ///
+    /// Used for one-off reads that should not displace frequently used values.
+    fn peek_id(conn: &Database, id: i32) -> QueryResult<Self> {
+        match Self::cache().get(id) {
+            Some(value) => Ok(value),
+            None => Self::load_id(conn, id),
+        }
+    }
+
+    /// Find a number of values given their IDs, as with `find_ids`,
+    /// without adding any to the cache that must be loaded from the database.
+    ///
+    /// Used for one-off reads that should not displace frequently used values.
+    fn peek_ids(conn: &Database, ids: &[i32]) -> QueryResult<Found<Self>> {
+        lookup_ids(conn, ids, false)
+    }
+
+    /// Create a value given the data.
+    ///
+    /// The inserted row is added to the global cache and returned.
+    fn create(
+        conn: &Database,
+        data: &Self::Insert,
+    ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+        let result = Self::insert_row(conn, data)?;
+        Self::cache().insert(result.id(), result.clone());
+        invalidate(conn, Self::cache_key(result.id()));
+
+        if result.is_public(conn) {
+            let _ = Message {
+                room:      result.room(Action::Create),
+                action:    Action::Create,
+                data_type: Self::DATA_TYPE,
+                data:      &result,
+            }
+            .send();
+        }
+
+        result.after_create(conn)?;
+
+        Ok(result)
+    }
+
+    /// Update a value given an ID and the data to update.
+    ///
+    /// The entry is updated in the database, added to cache, and returned.
+    fn update(
+        conn: &Database,
+        id: i32,
+        data: &Self::Update,
+    ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+        Self::update_if_version(conn, id, None, data)
+    }
+
+    /// Update a value given an ID and the data to update,
+    /// provided the stored version matches `expected_version` (if any).
+    ///
+    /// If the versions do not match, no row is updated and `NotFound` is returned.
+    /// Otherwise, the entry is updated in the database, added to cache, and returned.
+    fn update_if_version(
+        conn: &Database,
+        id: i32,
+        expected_version: Option<i32>,
+        data: &Self::Update,
+    ) -> rocket_contrib::databases::diesel::QueryResult<Self> {
+        let result = Self::update_row(conn, id, expected_version, data)?;
+        Self::updated(conn, &result, data);
+        result.after_update(conn, data);
+        Ok(result)
+    }
+
+    /// Store a value that has been updated in the database,
+    /// informing other instances and WebSocket clients of the changes.
+    ///
+    /// This is only necessary when updating a row without `update_row`.
+    fn updated(conn: &Database, value: &Self, changes: &impl Serialize) {
+        Self::cache().insert(value.id(), value.clone());
+        invalidate(conn, Self::cache_key(value.id()));
+
+        if value.is_public(conn) {
+            let _ = Message {
+                room:      value.room(Action::Update),
+                action:    Action::Update,
+                data_type: Self::DATA_TYPE,
+                data:      &Update::new(value.id(), value.version(), changes),
+            }
+            .send();
+        }
+    }
+
+    /// Delete a value given its ID.
+    ///
+    /// Removes the entry from cache and returns the number of rows deleted (should be `1`).
+    fn delete(conn: &Database, id: i32) -> rocket_contrib::databases::diesel::QueryResult<usize> {
+        let value = Self::find_id(conn, id)?;
+        value.before_delete(conn)?;
+
+        if value.is_public(conn) {
+            let _ = Message {
+                room:      value.room(Action::Delete),
+                action:    Action::Delete,
+                data_type: Self::DATA_TYPE,
+                data:      &json!({ "id": id }),
+            }
+            .send();
+        }
+
+        Self::cache().remove(id);
+
+        let removed_count = Self::delete_row(conn, id);
+        invalidate(conn, Self::cache_key(id));
+
+        if let Ok(removed_count) = removed_count {
+            debug_assert_eq!(removed_count, 1);
+        }
+
+        removed_count
+    }
+}
+
+/// Find a number of values given their IDs,
+/// checking the cache first and fetching any remaining values in a single query.
+/// Values fetched from the database are only added to the cache if `fill_cache` is set.
+fn lookup_ids<T: Repository>(
+    conn: &Database,
+    ids: &[i32],
+    fill_cache: bool,
+) -> QueryResult<Found<T>> {
+    let mut found = HashMap::with_capacity(ids.len());
+    let mut misses = vec![];
+
+    for &id in ids {
+        if found.contains_key(&id) {
+            continue;
+        }
+
+        match T::cache().get(id) {
+            Some(value) => {
+                found.insert(id, value);
+            }
+            None => misses.push(id),
+        }
+    }
+
+    if !misses.is_empty() {
+        misses.sort_unstable();
+        misses.dedup();
+
+        for value in T::load_ids(conn, &misses)? {
+            if fill_cache {
+                T::cache().insert(value.id(), value.clone());
+            }
+            found.insert(value.id(), value);
+        }
+    }
+
+    let mut result = Found {
+        values:  Vec::with_capacity(ids.len()),
+        missing: vec![],
+    };
+    for &id in ids {
+        match found.get(&id) {
+            Some(value) => result.values.push(value.clone()),
+            None => result.missing.push(id),
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,208 @@
+use super::{
+    events_table,
+    invalidate,
+    BatchResult,
+    ClockError,
+    ClockState,
+    Event,
+    InsertSection,
+    MarkdownKind,
+    PostAt,
+    Repository,
+    ScrubRequest,
+    Section,
+    Thread,
+    UpdateEvent,
+    UpdateThread,
+};
+use crate::{
+    websocket::{Action, DataType, Message},
+    Database,
+};
+use derive_more::Display;
+use rocket_contrib::databases::diesel::{result::Error as DieselError, Connection};
+use serde_json::Value;
+use std::error::Error;
+
+/// The name of the section that the events of a scrubbed attempt are archived to.
+const ARCHIVE_SECTION_NAME: &str = "Previous attempt";
+
+/// Why a thread could not be scrubbed.
+#[derive(Debug, Display)]
+pub enum ScrubError {
+    #[display(fmt = "{}", _0)]
+    Clock(ClockError),
+    #[display(fmt = "could not archive events: {}", _0)]
+    Archive(String),
+    #[display(fmt = "{}", _0)]
+    Database(DieselError),
+}
+
+impl Error for ScrubError {}
+
+impl From<DieselError> for ScrubError {
+    fn from(err: DieselError) -> Self {
+        ScrubError::Database(err)
+    }
+}
+
+impl From<ClockError> for ScrubError {
+    fn from(err: ClockError) -> Self {
+        ScrubError::Clock(err)
+    }
+}
+
+impl Thread {
+    /// Scrub the current attempt of a thread at the provided time (a UNIX timestamp),
+    /// moving it to the new T-0 (if provided).
+    ///
+    /// If requested, the posted events are moved into a new section
+    /// containing the reason for the scrub.
+    /// All remaining events are unposted,
+    /// and events scheduled at a fixed time are moved along with T-0.
+    /// The scrub is recorded in the thread's clock.
+    ///
+    /// All changes are made in a single transaction.
+    pub fn scrub(
+        conn: &Database,
+        id: i32,
+        user_id: i32,
+        request: &ScrubRequest,
+        now: i64,
+    ) -> Result<Self, ScrubError> {
+        let (thread, thread_changes, section, events) =
+            conn.transaction::<_, ScrubError, _>(|| {
+                let thread = Thread::load_id(conn, id)?;
+                let t0 = thread.space__t0;
+
+                if thread.clock.state(t0) == ClockState::Unset {
+                    return Err(ClockError::NoT0.into());
+                }
+
+                let events = Event::load_ids(conn, &thread.events_id)?;
+                let events: Vec<_> = thread
+                    .events_id
+                    .iter()
+                    .filter_map(|&event_id| events.iter().find(|event| event.id == event_id))
+                    .cloned()
+                    .collect();
+
+                // Archive the posted events.
+                let posted: Vec<_> = events
+                    .iter()
+                    .filter(|event| event.posted)
+                    .cloned()
+                    .collect();
+                let section = if request.archive_events && !posted.is_empty() {
+                    let timestamps = posted
+                        .iter()
+                        .flat_map(|event| thread.event_columns.timestamps(&event.cols))
+                        .collect::<Vec<_>>();
+                    let table = events_table(
+                        &thread.event_columns,
+                        &posted,
+                        &thread.time_format.renderer(t0, timestamps),
+                    )
+                    .map_err(|err| ScrubError::Archive(err.to_string()))?;
+
+                    let section = Section::insert_row(
+                        conn,
+                        &InsertSection {
+                            name:         ARCHIVE_SECTION_NAME.into(),
+                            content:      format!("Scrubbed: {}\n\n{}", request.reason, table),
+                            in_thread_id: id,
+                            kind:         MarkdownKind::NAME.into(),
+                            data:         Value::Null,
+                        },
+                    )?;
+
+                    for event in &posted {
+                        Event::delete_row(conn, event.id)?;
+                    }
+
+                    Some(section)
+                } else {
+                    None
+                };
+
+                // Reset the remaining events for the next attempt.
+                let delta = match (t0, request.new_t0) {
+                    (Some(t0), Some(new_t0)) => {
+                        new_t0.checked_sub(t0).ok_or(ClockError::OutOfRange)?
+                    }
+                    _ => 0,
+                };
+                let mut result = BatchResult {
+                    updated:   vec![],
+                    deleted:   vec![],
+                    events_id: vec![],
+                };
+                for event in events {
+                    if section.is_some() && event.posted {
+                        result.deleted.push(event.id);
+                        continue;
+                    }
+                    result.events_id.push(event.id);
+
+                    let changes = UpdateEvent {
+                        posted: if event.posted { Some(false) } else { None },
+                        post_at: match event.post_at {
+                            Some(PostAt::Utc(timestamp)) if delta != 0 => Some(Some(PostAt::Utc(
+                                timestamp.checked_add(delta).ok_or(ClockError::OutOfRange)?,
+                            ))),
+                            _ => None,
+                        },
+                        ..UpdateEvent::default()
+                    };
+                    if changes.posted.is_some() || changes.post_at.is_some() {
+                        result
+                            .updated
+                            .push(Event::update_row(conn, event.id, None, &changes)?);
+                    }
+                }
+
+                let mut clock = thread.clock.clone();
+                clock.scrub(
+                    t0,
+                    now,
+                    user_id,
+                    request,
+                    section.as_ref().map(|section| section.id),
+                )?;
+
+                let mut sections_id = thread.sections_id.clone();
+                sections_id.extend(section.as_ref().map(|section| section.id));
+
+                let thread_changes = UpdateThread {
+                    space__t0: request.new_t0.map(Some),
+                    events_id: Some(result.events_id.clone()),
+                    sections_id: Some(sections_id),
+                    clock: Some(clock),
+                    ..UpdateThread::default()
+                };
+                let thread = Thread::update_row(conn, id, None, &thread_changes)?;
+
+                Ok((thread, thread_changes, section, result))
+            })?;
+
+        if let Some(section) = section {
+            Section::cache().insert(section.id, section.clone());
+            invalidate(conn, Section::cache_key(section.id));
+
+            if thread.is_public(conn) {
+                let _ = Message {
+                    room:      section.room(Action::Create),
+                    action:    Action::Create,
+                    data_type: DataType::Section,
+                    data:      &section,
+                }
+                .send();
+            }
+        }
+
+        events.publish(conn, id);
+        Thread::updated(conn, &thread, &thread_changes);
+
+        Ok(thread)
+    }
+}
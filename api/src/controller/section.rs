@@ -1,43 +1,54 @@
-use super::{Event, Thread, ToMarkdown, UpdateThread, SECTION_CACHE_SIZE};
+use super::{
+    section_kind,
+    Cache,
+    CacheKey,
+    Event,
+    EventColumns,
+    MarkdownKind,
+    Page,
+    RenderContext,
+    RenderError,
+    Repository,
+    Sort,
+    StatsKind,
+    StatsTable,
+    Thread,
+    TimeRenderer,
+    ToMarkdown,
+    UpdateThread,
+    User,
+    SECTION_CACHE_SIZE,
+};
 use crate::{
     schema::section,
-    websocket::{Action, DataType, Message, Room, Update},
+    websocket::{Action, DataType, Room},
     Database,
 };
 use itertools::Itertools;
-use lru_cache::LruCache;
 use macros::generate_structs;
 use once_cell::sync::Lazy;
-use parking_lot::Mutex;
+use rocket::FromForm;
 use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::{error::Error, fmt::Write, iter::repeat};
-
-/// A global cache, containing a mapping of IDs to their respective `Event`.
-///
-/// The cache is protected by a `Mutex`,
-/// ensuring there is only ever at most one writer at a time.
-/// Note that even when reading,
-/// there must be a lock on mutability,
-/// as the `LruCache` must be able to update itself.
-///
-/// To read from the cache,
-/// you'll want to call `CACHE.lock()` before performing normal operations.
-static CACHE: Lazy<Mutex<LruCache<i32, Section>>> =
-    Lazy::new(|| Mutex::new(LruCache::new(SECTION_CACHE_SIZE)));
+use serde_json::Value as Json;
+use std::{fmt::Write, iter::repeat, str::FromStr};
+
+/// A global cache, containing a mapping of IDs to their respective `Section`.
+static CACHE: Lazy<Cache<Section>> = Lazy::new(|| Cache::new(*SECTION_CACHE_SIZE));
 
 // Fields relating to the lock are not necessarily `auto`,
 // but are declared as such as they are handled by the `LockSection` struct.
 generate_structs! {
     Section("section") {
         auto id: i32,
-        readonly is_events_section: bool = false,
         name: String = "",
         content: String = "",
         auto lock_held_by_user_id: Option<i32>,
         readonly in_thread_id: i32,
         auto lock_assigned_at_utc: i64,
+        auto version: i32,
+        kind: String = MarkdownKind::NAME,
+        data: Json = Json::Null,
     }
 }
 
@@ -56,115 +67,179 @@ pub struct LockSection {
     pub lock_assigned_at_utc: i64,
 }
 
+/// Criteria that all `Section`s returned by `Section::find_all` must match.
+/// Any criteria not provided are not checked.
+#[derive(Debug, Default, FromForm)]
+pub struct SectionFilter {
+    pub in_thread_id: Option<i32>,
+}
+
+/// Fields that a list of `Section`s can be sorted by.
+#[derive(Debug, Clone, Copy)]
+pub enum SectionSortKey {
+    Id,
+    Name,
+    InThreadId,
+}
+
+impl FromStr for SectionSortKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "id" => Ok(SectionSortKey::Id),
+            "name" => Ok(SectionSortKey::Name),
+            "in_thread_id" => Ok(SectionSortKey::InThreadId),
+            _ => Err("unknown sort key"),
+        }
+    }
+}
+
 impl Section {
-    /// Find all `Section`s in the database.
+    /// The stats table of the section, if it is a stats section.
+    pub fn stats(&self) -> Option<StatsTable> {
+        if self.kind == StatsKind::NAME {
+            serde_json::from_value(self.data.clone()).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Find a page of `Section`s in the database matching the provided filter.
+    /// Unless otherwise specified, the results are sorted by ID.
+    ///
+    /// Only sections of threads visible to `viewer` are included.
     ///
     /// Does _not_ use cache (reading or writing),
     /// so as to avoid storing values rarely accessed.
-    pub fn find_all(conn: &Database) -> QueryResult<Vec<Self>> {
-        use crate::schema::section::dsl::section;
-        section.load(conn)
-    }
+    pub fn find_all(
+        conn: &Database,
+        filter: &SectionFilter,
+        sort: Option<Sort<SectionSortKey>>,
+        page: Page,
+        viewer: Option<&User>,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::section::dsl::{id, in_thread_id, name, section};
 
-    /// Find a given `Section` by its ID.
-    ///
-    /// Internally uses a cache to limit database accesses.
-    pub fn find_id(conn: &Database, section_id: i32) -> QueryResult<Self> {
-        use crate::schema::section::dsl::section;
+        let mut query = section
+            .filter(in_thread_id.eq_any(Thread::visible_ids(viewer)))
+            .into_boxed();
 
-        let mut cache = CACHE.lock();
-        if cache.contains_key(&section_id) {
-            Ok(cache.get_mut(&section_id).unwrap().clone())
-        } else {
-            let result: Self = section.find(section_id).first(conn)?;
-            cache.insert(section_id, result.clone());
-            Ok(result)
+        if let Some(value) = filter.in_thread_id {
+            query = query.filter(in_thread_id.eq(value));
         }
+
+        let sort = sort.unwrap_or_else(|| Sort::ascending(SectionSortKey::Id));
+        query = match sort.key {
+            SectionSortKey::Id => order_by!(query, id, sort),
+            SectionSortKey::Name => order_by!(query, name, sort),
+            SectionSortKey::InThreadId => order_by!(query, in_thread_id, sort),
+        };
+
+        query
+            .then_order_by(id.asc())
+            .limit(page.limit)
+            .offset(page.offset)
+            .load(conn)
     }
 
-    /// Create a `Section` given the data.
+    /// Set a lock on a `Section`,
+    /// provided the stored version matches `expected_version` (if any).
+    /// Integrity and authority to perform this action is _not_ verified here.
     ///
-    /// The inserted row is added to the global cache and returned.
-    pub fn create(conn: &Database, data: &InsertSection) -> QueryResult<Self> {
-        use crate::schema::section::dsl::section;
-        let result: Self = diesel::insert_into(section).values(data).get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::Thread(result.in_thread_id),
-            action:    Action::Create,
-            data_type: DataType::Section,
-            data:      &result,
-        }
-        .send();
+    /// If the versions do not match, no row is updated and `NotFound` is returned.
+    /// Otherwise, the entry is updated in the database, added to cache, and returned.
+    pub fn set_lock(
+        conn: &Database,
+        section_id: i32,
+        expected_version: Option<i32>,
+        data: &LockSection,
+    ) -> QueryResult<Self> {
+        use crate::schema::section::dsl::{section, version};
+
+        let query = diesel::update(section.find(section_id));
+        let changes = (data, version.eq(version + 1));
+        let result: Self = match expected_version {
+            Some(expected_version) => query
+                .filter(version.eq(expected_version))
+                .set(changes)
+                .get_result(conn)?,
+            None => query.set(changes).get_result(conn)?,
+        };
+        Self::updated(conn, &result, data);
+
+        Ok(result)
+    }
+}
+
+impl Repository for Section {
+    type Insert = InsertSection;
+    type Update = UpdateSection;
+
+    const DATA_TYPE: DataType = DataType::Section;
+
+    repository_primitives!(section, versioned);
+
+    fn cache() -> &'static Cache<Self> {
+        &CACHE
+    }
+
+    fn cache_key(id: i32) -> CacheKey {
+        CacheKey::Section(id)
+    }
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn version(&self) -> Option<i32> {
+        Some(self.version)
+    }
+
+    fn room(&self, _action: Action) -> Room {
+        Room::Thread(self.in_thread_id)
+    }
+
+    /// Sections of a draft are only visible to those able to modify the thread.
+    fn is_public(&self, conn: &Database) -> bool {
+        Thread::has_public_contents(conn, self.in_thread_id)
+    }
 
-        // Add the section ID to the relevant Thread.
-        let mut thread = Thread::find_id(conn, data.in_thread_id)?;
-        thread.sections_id.push(result.id);
+    /// Add the section ID to the relevant `Thread`.
+    fn after_create(&self, conn: &Database) -> QueryResult<()> {
+        let mut thread = Thread::find_id(conn, self.in_thread_id)?;
+        thread.sections_id.push(self.id);
         Thread::update(
             conn,
-            data.in_thread_id,
+            self.in_thread_id,
             &UpdateThread {
                 sections_id: thread.sections_id.into(),
                 ..UpdateThread::default()
             },
         )?;
 
-        Ok(result)
+        Ok(())
     }
 
-    /// Update a `Section` given an ID and the data to update.
-    ///
-    /// The entry is updated in the database, added to cache, and returned.
-    pub fn update(conn: &Database, section_id: i32, data: &UpdateSection) -> QueryResult<Self> {
-        use crate::schema::section::dsl::{id, section};
-        let result: Self = diesel::update(section)
-            .filter(id.eq(section_id))
-            .set(data)
-            .get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::Thread(result.in_thread_id),
-            action:    Action::Update,
-            data_type: DataType::Section,
-            data:      &Update::new(section_id, data),
+    /// Send the section to any webhooks designated for it if its content was changed.
+    fn after_update(&self, conn: &Database, changes: &UpdateSection) {
+        if changes.name.is_none()
+            && changes.content.is_none()
+            && changes.kind.is_none()
+            && changes.data.is_none()
+        {
+            return;
         }
-        .send();
-
-        Ok(result)
-    }
 
-    /// Set a lock on a `Section`.
-    /// Integrity and authority to perform this action is _not_ verified here.
-    ///
-    /// The entry is updated in the database, added to cache, and returned.
-    pub fn set_lock(conn: &Database, section_id: i32, data: &LockSection) -> QueryResult<Self> {
-        use crate::schema::section::dsl::{id, section};
-        let result: Self = diesel::update(section)
-            .filter(id.eq(section_id))
-            .set(data)
-            .get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::Thread(result.in_thread_id),
-            action:    Action::Update,
-            data_type: DataType::Section,
-            data:      &Update::new(section_id, data),
+        if let Err(err) = self.notify_changed(conn) {
+            eprintln!("error notifying webhooks of section {}: {}", self.id, err);
         }
-        .send();
-
-        Ok(result)
     }
 
-    /// Delete a `Section` given its ID.
-    ///
-    /// Removes the entry from cache and returns the number of rows deleted (should be `1`).
-    pub fn delete(conn: &Database, section_id: i32) -> QueryResult<usize> {
-        use crate::schema::section::dsl::{id, section};
-        let mut thread = Thread::find_id(conn, Self::find_id(conn, section_id)?.in_thread_id)?;
-        thread.sections_id.retain(|&cur_id| cur_id != section_id);
+    /// Remove the section ID from the relevant `Thread`.
+    fn before_delete(&self, conn: &Database) -> QueryResult<()> {
+        let mut thread = Thread::find_id(conn, self.in_thread_id)?;
+        thread.sections_id.retain(|&cur_id| cur_id != self.id);
         Thread::update(
             conn,
             thread.id,
@@ -174,59 +249,50 @@ impl Section {
             },
         )?;
 
-        let _ = Message {
-            room:      Room::Thread(thread.id),
-            action:    Action::Delete,
-            data_type: DataType::Section,
-            data:      &json!({ "id": section_id }),
-        }
-        .send();
-
-        CACHE.lock().remove(&section_id);
+        Ok(())
+    }
+}
 
-        let removed_count = diesel::delete(section)
-            .filter(id.eq(section_id))
-            .execute(conn);
+/// Convert events to a markdown table,
+/// with a header containing the name of each column.
+/// Only posted events are included.
+pub fn events_table(
+    columns: &EventColumns,
+    events: &[Event],
+    time: &TimeRenderer,
+) -> Result<String, RenderError> {
+    let mut md = String::new();
 
-        if let Ok(removed_count) = removed_count {
-            debug_assert_eq!(removed_count, 1);
-        }
+    writeln!(
+        &mut md,
+        "|{}|",
+        columns.iter().map(|column| &column.name).join("|")
+    )?;
+    writeln!(&mut md, "|{}|", repeat("---").take(columns.len()).join("|"))?;
 
-        removed_count
+    for event in events {
+        write!(&mut md, "{}", event.to_markdown_row(columns, time)?)?;
     }
+
+    Ok(md)
 }
 
 impl ToMarkdown for Section {
     /// Convert the `Section` object to valid markdown.
     /// The resulting string is intended for consumption by Reddit,
     /// but should be valid for any markdown flavor supporting tables.
-    fn to_markdown(&self, conn: &Database) -> Result<String, Box<dyn Error>> {
+    ///
+    /// The body of the section is rendered according to its kind.
+    fn to_markdown(&self, ctx: &RenderContext) -> Result<String, RenderError> {
+        let kind = section_kind(&self.kind).map_err(|error| RenderError::Section {
+            section_id: self.id,
+            error,
+        })?;
+
         let mut md = String::new();
 
         writeln!(&mut md, "# {}", self.name)?;
-
-        if self.is_events_section {
-            let thread = Thread::find_id(conn, self.in_thread_id)?;
-
-            writeln!(&mut md, "|{}|", thread.event_column_headers.join("|"))?;
-            writeln!(
-                &mut md,
-                "|{}|",
-                repeat("---")
-                    .take(thread.event_column_headers.len())
-                    .join("|")
-            )?;
-
-            for &event_id in &thread.events_id {
-                write!(
-                    &mut md,
-                    "{}",
-                    Event::find_id(conn, event_id)?.to_markdown(conn)?
-                )?;
-            }
-        } else {
-            write!(&mut md, "{}", self.content)?;
-        }
+        write!(&mut md, "{}", kind.to_markdown(self, ctx)?)?;
 
         Ok(md)
     }
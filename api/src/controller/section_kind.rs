@@ -0,0 +1,323 @@
+use super::{events_table, RenderContext, RenderError, Section, StatsTable};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{error::Error, fmt::Write};
+
+/// Every kind of section, in the order they are presented to clients.
+///
+/// To add a kind of section, implement `SectionKind` and add it here.
+static SECTION_KINDS: &[&dyn SectionKind] = &[
+    &MarkdownKind,
+    &EventsKind,
+    &StatsKind,
+    &LinksKind,
+    &VideoKind,
+];
+
+/// A kind of `Section`, determining how its `data` is validated and how it is rendered.
+pub trait SectionKind: Sync {
+    /// The name of the kind, as stored in the section's `kind`.
+    fn name(&self) -> &'static str;
+
+    /// A short description of the kind, for clients.
+    fn description(&self) -> &'static str;
+
+    /// An example of valid `data` for the kind.
+    fn example(&self) -> Value;
+
+    /// Ensure the `data` of a section is valid for the kind.
+    fn check(&self, data: &Value) -> Result<(), SectionKindError>;
+
+    /// Convert the body of the section (everything after its heading) to markdown.
+    fn to_markdown(&self, section: &Section, ctx: &RenderContext) -> Result<String, RenderError>;
+}
+
+/// Why the kind or data of a `Section` was rejected.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum SectionKindError {
+    #[display(fmt = r#"unknown section kind "{}""#, _0)]
+    Unknown(String),
+    #[display(fmt = "invalid data for {} section: {}", kind, message)]
+    InvalidData {
+        kind:    &'static str,
+        message: String,
+    },
+}
+
+impl Error for SectionKindError {}
+
+/// A kind of section, as presented to clients.
+#[derive(Debug, Clone, Serialize)]
+pub struct SectionKindInfo {
+    pub name:        &'static str,
+    pub description: &'static str,
+    pub example:     Value,
+}
+
+/// Find the kind of section with the provided name.
+pub fn section_kind(name: &str) -> Result<&'static dyn SectionKind, SectionKindError> {
+    SECTION_KINDS
+        .iter()
+        .find(|kind| kind.name() == name)
+        .copied()
+        .ok_or_else(|| SectionKindError::Unknown(name.into()))
+}
+
+/// All kinds of section, as presented to clients.
+pub fn section_kinds() -> Vec<SectionKindInfo> {
+    SECTION_KINDS
+        .iter()
+        .map(|kind| SectionKindInfo {
+            name:        kind.name(),
+            description: kind.description(),
+            example:     kind.example(),
+        })
+        .collect()
+}
+
+/// Ensure the `data` of a section is valid for a kind,
+/// given the type it should deserialize to.
+fn check_data<'de, T: Deserialize<'de>>(
+    kind: &'static str,
+    data: &'de Value,
+) -> Result<T, SectionKindError> {
+    T::deserialize(data).map_err(|err| SectionKindError::InvalidData {
+        kind,
+        message: err.to_string(),
+    })
+}
+
+/// The `data` of a section being rendered, as the type it should deserialize to for its kind.
+fn render_data<'de, T: Deserialize<'de>>(
+    kind: &'static str,
+    section: &'de Section,
+) -> Result<T, RenderError> {
+    check_data(kind, &section.data).map_err(|error| RenderError::Section {
+        section_id: section.id,
+        error,
+    })
+}
+
+/// Free-form markdown, stored in the section's `content`.
+#[derive(Debug, Clone, Copy)]
+pub struct MarkdownKind;
+
+impl MarkdownKind {
+    pub const NAME: &'static str = "markdown";
+}
+
+impl SectionKind for MarkdownKind {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn description(&self) -> &'static str {
+        "Free-form markdown, as provided in the section's content"
+    }
+
+    fn example(&self) -> Value {
+        Value::Null
+    }
+
+    fn check(&self, data: &Value) -> Result<(), SectionKindError> {
+        check_data::<()>(Self::NAME, data)
+    }
+
+    fn to_markdown(&self, section: &Section, _ctx: &RenderContext) -> Result<String, RenderError> {
+        Ok(section.content.clone())
+    }
+}
+
+/// A table of the posted events of the section's thread.
+#[derive(Debug, Clone, Copy)]
+pub struct EventsKind;
+
+impl EventsKind {
+    pub const NAME: &'static str = "events";
+}
+
+impl SectionKind for EventsKind {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn description(&self) -> &'static str {
+        "A table of the thread's posted events"
+    }
+
+    fn example(&self) -> Value {
+        Value::Null
+    }
+
+    fn check(&self, data: &Value) -> Result<(), SectionKindError> {
+        check_data::<()>(Self::NAME, data)
+    }
+
+    fn to_markdown(&self, _section: &Section, ctx: &RenderContext) -> Result<String, RenderError> {
+        events_table(&ctx.thread().event_columns, ctx.events(), ctx.time())
+    }
+}
+
+/// A table of key/value pairs (a `StatsTable`), followed by the section's content.
+#[derive(Debug, Clone, Copy)]
+pub struct StatsKind;
+
+impl StatsKind {
+    pub const NAME: &'static str = "stats";
+}
+
+impl SectionKind for StatsKind {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn description(&self) -> &'static str {
+        "A table of key/value pairs, such as the details of a launch"
+    }
+
+    fn example(&self) -> Value {
+        json!({ "rows": [{ "key": "Vehicle", "value": "Falcon 9" }] })
+    }
+
+    fn check(&self, data: &Value) -> Result<(), SectionKindError> {
+        check_data::<StatsTable>(Self::NAME, data)?
+            .check()
+            .map_err(|err| SectionKindError::InvalidData {
+                kind:    Self::NAME,
+                message: err.to_string(),
+            })
+    }
+
+    fn to_markdown(&self, section: &Section, _ctx: &RenderContext) -> Result<String, RenderError> {
+        let stats: StatsTable = render_data(Self::NAME, section)?;
+        Ok(format!("{}{}", stats.to_markdown(), section.content))
+    }
+}
+
+/// A single link in a `LinksKind` section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Link {
+    title: String,
+    url:   String,
+}
+
+/// The data of a `LinksKind` section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Links {
+    links: Vec<Link>,
+}
+
+/// Is the URL one that can be linked to?
+fn is_valid_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// A list of links, followed by the section's content.
+#[derive(Debug, Clone, Copy)]
+pub struct LinksKind;
+
+impl LinksKind {
+    pub const NAME: &'static str = "links";
+}
+
+impl SectionKind for LinksKind {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn description(&self) -> &'static str {
+        "A list of links, such as press kits and other coverage"
+    }
+
+    fn example(&self) -> Value {
+        json!({ "links": [{ "title": "Press kit", "url": "https://www.spacex.com/press" }] })
+    }
+
+    fn check(&self, data: &Value) -> Result<(), SectionKindError> {
+        let links: Links = check_data(Self::NAME, data)?;
+
+        match links.links.iter().find(|link| !is_valid_url(&link.url)) {
+            Some(link) => Err(SectionKindError::InvalidData {
+                kind:    Self::NAME,
+                message: format!(r#"invalid URL "{}""#, link.url),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn to_markdown(&self, section: &Section, _ctx: &RenderContext) -> Result<String, RenderError> {
+        let links: Links = render_data(Self::NAME, section)?;
+        let mut md = String::new();
+
+        for link in &links.links {
+            writeln!(&mut md, "- [{}]({})", link.title, link.url)?;
+        }
+        write!(&mut md, "{}", section.content)?;
+
+        Ok(md)
+    }
+}
+
+/// The data of a `VideoKind` section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Video {
+    url:   String,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+/// A link to a video (such as the webcast), followed by the section's content.
+///
+/// Reddit does not support embedding videos in posts,
+/// so other renderers are expected to embed the video from the section's `data`.
+#[derive(Debug, Clone, Copy)]
+pub struct VideoKind;
+
+impl VideoKind {
+    pub const NAME: &'static str = "video";
+}
+
+impl SectionKind for VideoKind {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn description(&self) -> &'static str {
+        "A video, such as the launch webcast"
+    }
+
+    fn example(&self) -> Value {
+        json!({ "url": "https://www.youtube.com/watch?v=...", "title": "Webcast" })
+    }
+
+    fn check(&self, data: &Value) -> Result<(), SectionKindError> {
+        let video: Video = check_data(Self::NAME, data)?;
+
+        if is_valid_url(&video.url) {
+            Ok(())
+        } else {
+            Err(SectionKindError::InvalidData {
+                kind:    Self::NAME,
+                message: format!(r#"invalid URL "{}""#, video.url),
+            })
+        }
+    }
+
+    fn to_markdown(&self, section: &Section, _ctx: &RenderContext) -> Result<String, RenderError> {
+        let video: Video = render_data(Self::NAME, section)?;
+
+        Ok(format!(
+            "[{}]({})\n\n{}",
+            video
+                .title
+                .as_ref()
+                .map_or("Watch the video", String::as_str),
+            video.url,
+            section.content
+        ))
+    }
+}
@@ -0,0 +1,115 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, error::Error, fmt::Write as _};
+
+/// A single row of a `StatsTable`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsRow {
+    pub key:   String,
+    pub value: String,
+}
+
+/// A change to a single row of a `StatsTable`.
+/// A value of `None` removes the row.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsChange {
+    pub key:   String,
+    pub value: Option<String>,
+}
+
+/// A table of key/value pairs, such as the details of a launch,
+/// rendered in place of hand-written markdown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsTable {
+    /// The rows of the table, in the order they are displayed.
+    pub rows: Vec<StatsRow>,
+}
+
+/// Why a `StatsTable` was rejected.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum StatsError {
+    #[display(fmt = "a key must not be empty")]
+    EmptyKey,
+    #[display(fmt = r#"duplicate key "{}""#, _0)]
+    DuplicateKey(String),
+    #[display(fmt = "the section does not contain a stats table")]
+    NotStats,
+    #[display(fmt = "the thread has no launch data")]
+    NoLaunch,
+}
+
+impl Error for StatsError {}
+
+/// Escape a value so it remains in a single cell of a markdown table.
+fn escape_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+impl StatsTable {
+    /// Ensure every key is present and unique.
+    pub fn check(&self) -> Result<(), StatsError> {
+        let mut keys = HashSet::with_capacity(self.rows.len());
+
+        for row in &self.rows {
+            if row.key.is_empty() {
+                return Err(StatsError::EmptyKey);
+            }
+            if !keys.insert(&row.key) {
+                return Err(StatsError::DuplicateKey(row.key.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The value of the row with the provided key, if present.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.rows
+            .iter()
+            .find(|row| row.key == key)
+            .map(|row| row.value.as_str())
+    }
+
+    /// Set the value of the row with the provided key,
+    /// adding the row to the end of the table if not present.
+    pub fn set(&mut self, key: &str, value: String) {
+        match self.rows.iter_mut().find(|row| row.key == key) {
+            Some(row) => row.value = value,
+            None => self.rows.push(StatsRow {
+                key: key.into(),
+                value,
+            }),
+        }
+    }
+
+    /// Update individual rows of the table, in order.
+    pub fn apply(&mut self, changes: &[StatsChange]) -> Result<(), StatsError> {
+        for change in changes {
+            match &change.value {
+                Some(value) => self.set(&change.key, value.clone()),
+                None => self.rows.retain(|row| row.key != change.key),
+            }
+        }
+
+        self.check()
+    }
+
+    /// Convert the table to markdown, with a row per key.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("|||\n|-|-|\n");
+
+        for row in &self.rows {
+            let _ = writeln!(
+                &mut md,
+                "|{}|{}|",
+                escape_cell(&row.key),
+                escape_cell(&row.value)
+            );
+        }
+
+        md
+    }
+}
@@ -1,33 +1,61 @@
 #![allow(non_snake_case)]
 
-use super::{Event, Section, ToMarkdown, User, THREAD_CACHE_SIZE};
+use super::{
+    invalidate,
+    Cache,
+    CacheKey,
+    Clock,
+    Event,
+    EventColumns,
+    InsertEvent,
+    InsertSection,
+    LaunchSync,
+    Page,
+    RenderContext,
+    RenderError,
+    Repository,
+    Section,
+    Sort,
+    TemplateSection,
+    ThreadState,
+    TimeFormat,
+    TimeRenderer,
+    ToMarkdown,
+    TransferredEvent,
+    User,
+    Webhooks,
+    THREAD_CACHE_SIZE,
+};
 use crate::{
     schema::thread,
-    websocket::{Action, DataType, Message, Room, Update},
+    websocket::{Action, DataType, Message, Room},
     Database,
 };
-use lru_cache::LruCache;
+use diesel::{
+    pg::Pg,
+    sql_types::{Integer, Nullable, Text},
+};
 use macros::generate_structs;
 use once_cell::sync::Lazy;
-use parking_lot::Mutex;
-use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use rocket::FromForm;
+use rocket_contrib::databases::diesel::{
+    Connection,
+    ExpressionMethods,
+    QueryDsl,
+    QueryResult,
+    RunQueryDsl,
+};
 use serde::Deserialize;
 use serde_json::{json, value::Value as Json};
-use std::{error::Error, fmt::Write};
-
-/// A global cache, containing a mapping of IDs to their respective `Event`.
-///
-/// The cache is protected by a `Mutex`,
-/// ensuring there is only ever at most one writer at a time.
-/// Note that even when reading,
-/// there must be a lock on mutability,
-/// as the `LruCache` must be able to update itself.
-///
-/// To read from the cache,
-/// you'll want to call `CACHE.lock()` before performing normal operations.
-/// ```
-static CACHE: Lazy<Mutex<LruCache<i32, Thread>>> =
-    Lazy::new(|| Mutex::new(LruCache::new(THREAD_CACHE_SIZE)));
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+sql_function! {
+    /// Convert the string to lowercase.
+    fn lower(x: Nullable<Text>) -> Nullable<Text>;
+}
+
+/// A global cache, containing a mapping of IDs to their respective `Thread`.
+static CACHE: Lazy<Cache<Thread>> = Lazy::new(|| Cache::new(*THREAD_CACHE_SIZE));
 
 generate_structs! {
     Thread("thread") {
@@ -42,9 +70,15 @@ generate_structs! {
         readonly created_by_user_id: i32,
         sections_id: Vec<i32> = vec![],
         events_id: Vec<i32> = vec![],
-        event_column_headers: Vec<String>,
-        readonly space__utc_col_index: Option<i16>,
-        is_live: bool = false,
+        readonly is_live: bool = false,
+        auto version: i32,
+        event_columns: EventColumns,
+        time_format: TimeFormat,
+        clock: Clock = Clock::default(),
+        launch_sync: LaunchSync = LaunchSync::default(),
+        readonly state: ThreadState = ThreadState::Draft,
+        readonly scheduled_at: Option<i64>,
+        private webhooks: Webhooks = Webhooks::default(),
     }
 }
 
@@ -53,25 +87,176 @@ generate_structs! {
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ExternalInsertThread {
-    pub thread_name: String,
-    pub display_name: String,
-    pub subreddit: Option<String>,
-    pub space__t0: Option<i64>,
-    pub video_url: Option<String>,
+    #[serde(default)]
+    pub thread_name:    String,
+    #[serde(default)]
+    pub display_name:   String,
+    pub subreddit:      Option<String>,
+    pub space__t0:      Option<i64>,
+    pub video_url:      Option<String>,
     pub spacex__api_id: Option<String>,
-    pub event_column_headers: Vec<String>,
-    pub space__utc_col_index: Option<i16>,
+    pub event_columns:  Option<EventColumns>,
+    pub time_format:    Option<TimeFormat>,
+    pub is_live:        Option<bool>,
+    pub template_id:    Option<i32>,
+    #[serde(default)]
+    pub draft:          bool,
+    pub scheduled_at:   Option<i64>,
+}
+
+impl ExternalInsertThread {
+    /// The row to insert for the thread in the provided state,
+    /// given the ID of the user creating it.
+    /// It has not yet been posted to Reddit.
+    pub fn insertable(&self, user_id: i32, state: ThreadState) -> InsertThread {
+        InsertThread {
+            thread_name: self.thread_name.clone(),
+            display_name: self.display_name.clone(),
+            post_id: None,
+            subreddit: self.subreddit.clone(),
+            space__t0: self.space__t0,
+            video_url: self.video_url.clone(),
+            spacex__api_id: self.spacex__api_id.clone(),
+            created_by_user_id: user_id,
+            events_id: vec![],
+            sections_id: vec![],
+            is_live: state == ThreadState::Live,
+            event_columns: self.event_columns.clone().unwrap_or_default(),
+            time_format: self.time_format.clone().unwrap_or_default(),
+            clock: Clock::default(),
+            launch_sync: LaunchSync::default(),
+            state,
+            scheduled_at: self.scheduled_at,
+            webhooks: Webhooks::default(),
+        }
+    }
+}
+
+/// Criteria that all `Thread`s returned by `Thread::find_all` must match.
+/// Any criteria not provided are not checked.
+#[derive(Debug, Default, FromForm)]
+pub struct ThreadFilter {
+    pub subreddit: Option<String>,
     pub is_live: Option<bool>,
+    pub state: Option<ThreadState>,
+    pub created_by_user_id: Option<i32>,
+    pub space__t0_after: Option<i64>,
+    pub space__t0_before: Option<i64>,
+}
+
+/// Fields that a list of `Thread`s can be sorted by.
+#[derive(Debug, Clone, Copy)]
+pub enum ThreadSortKey {
+    Id,
+    ThreadName,
+    DisplayName,
+    SpaceT0,
+}
+
+impl FromStr for ThreadSortKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "id" => Ok(ThreadSortKey::Id),
+            "thread_name" => Ok(ThreadSortKey::ThreadName),
+            "display_name" => Ok(ThreadSortKey::DisplayName),
+            "space__t0" => Ok(ThreadSortKey::SpaceT0),
+            _ => Err("unknown sort key"),
+        }
+    }
 }
 
 impl Thread {
-    /// Find all `Thread`s in the database.
+    /// A query for the IDs of all `Thread`s visible to the provided user (if any).
+    ///
+    /// Drafts are only visible to their creator, hosts of their subreddit, and global admins.
+    pub fn visible_ids(viewer: Option<&User>) -> thread::BoxedQuery<'static, Pg, Integer> {
+        use crate::schema::thread::dsl::{created_by_user_id, id, state, subreddit, thread};
+
+        let query = thread.select(id).into_boxed();
+
+        match viewer {
+            Some(user) if user.is_global_admin => query,
+            Some(user) => query.filter(
+                state
+                    .ne(ThreadState::Draft)
+                    .or(created_by_user_id.eq(user.id))
+                    .or(lower(subreddit).eq_any(user.hosted_subreddits())),
+            ),
+            None => query.filter(state.ne(ThreadState::Draft)),
+        }
+    }
+
+    /// Can everyone be informed of the sections and events of the `Thread` with the provided ID?
+    ///
+    /// This is not the case while it is a draft (or if it cannot be found).
+    pub fn has_public_contents(conn: &Database, id: i32) -> bool {
+        Thread::find_id(conn, id).map_or(false, |thread| thread.is_public(conn))
+    }
+
+    /// Find a page of `Thread`s in the database matching the provided filter.
+    /// Unless otherwise specified, the results are sorted by ID.
+    ///
+    /// Drafts are only included if they are visible to the provided user.
     ///
     /// Does _not_ use cache (reading or writing),
     /// so as to avoid storing values rarely accessed.
-    pub fn find_all(conn: &Database) -> QueryResult<Vec<Self>> {
-        use crate::schema::thread::dsl::thread;
-        thread.load(conn)
+    pub fn find_all(
+        conn: &Database,
+        filter: &ThreadFilter,
+        sort: Option<Sort<ThreadSortKey>>,
+        page: Page,
+        viewer: Option<&User>,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::thread::dsl::{
+            created_by_user_id,
+            display_name,
+            id,
+            is_live,
+            space__t0,
+            state,
+            subreddit,
+            thread,
+            thread_name,
+        };
+
+        let mut query = thread.into_boxed();
+
+        query = query.filter(id.eq_any(Thread::visible_ids(viewer)));
+
+        if let Some(value) = &filter.subreddit {
+            query = query.filter(subreddit.eq(value));
+        }
+        if let Some(value) = filter.is_live {
+            query = query.filter(is_live.eq(value));
+        }
+        if let Some(value) = filter.state {
+            query = query.filter(state.eq(value));
+        }
+        if let Some(value) = filter.created_by_user_id {
+            query = query.filter(created_by_user_id.eq(value));
+        }
+        if let Some(value) = filter.space__t0_after {
+            query = query.filter(space__t0.ge(value));
+        }
+        if let Some(value) = filter.space__t0_before {
+            query = query.filter(space__t0.lt(value));
+        }
+
+        let sort = sort.unwrap_or_else(|| Sort::ascending(ThreadSortKey::Id));
+        query = match sort.key {
+            ThreadSortKey::Id => order_by!(query, id, sort),
+            ThreadSortKey::ThreadName => order_by!(query, thread_name, sort),
+            ThreadSortKey::DisplayName => order_by!(query, display_name, sort),
+            ThreadSortKey::SpaceT0 => order_by!(query, space__t0, sort),
+        };
+
+        query
+            .then_order_by(id.asc())
+            .limit(page.limit)
+            .offset(page.offset)
+            .load(conn)
     }
 
     /// Find a given `Thread` by its ID,
@@ -82,40 +267,75 @@ impl Thread {
     /// so as to take advantage of cache wherever possible.
     /// Additionally, directly querying the database would make it more difficult
     /// to preserve the tree structure of the result.
+    ///
+    /// Any sections, events, or lock holders that cannot be found
+    /// are listed under `missing`, rather than causing an error.
     pub fn find_id_with_foreign_keys(conn: &Database, thread_id: i32) -> QueryResult<Json> {
         // Get the values, represented as normal structs.
-        // For sections, we also add the relation to `User`,
-        // so we represent those as raw, untyped JSON values.
+        // Children are fetched in batches, checking the cache first.
         let raw_thread = Self::find_id(conn, thread_id)?;
         let created_by_user = User::find_id(conn, raw_thread.created_by_user_id)?;
-        let sections: Vec<_> = raw_thread
-            .sections_id
+        let sections = Section::find_ids(conn, &raw_thread.sections_id)?;
+        let events = Event::find_ids(conn, &raw_thread.events_id)?;
+
+        let lock_holders_id: Vec<_> = sections
+            .values
             .iter()
-            .map(|section_id| Section::find_id(conn, *section_id).unwrap())
+            .filter_map(|section| section.lock_held_by_user_id)
+            .collect();
+        let lock_holders = User::find_ids(conn, &lock_holders_id)?;
+        let lock_holders_by_id: HashMap<_, _> = lock_holders
+            .values
+            .into_iter()
+            .map(|user| (user.id, user))
+            .collect();
+
+        // For sections, we also add the relation to `User`,
+        // so we represent those as raw, untyped JSON values.
+        let sections_json: Vec<_> = sections
+            .values
+            .into_iter()
             .map(|section| {
                 let user_id = section.lock_held_by_user_id;
                 let mut section = serde_json::to_value(section).unwrap();
-                section["lock_held_by_user"] = user_id.map_or(json!(null), |user_id| {
-                    serde_json::to_value(User::find_id(conn, user_id).unwrap()).unwrap()
-                });
+                section["lock_held_by_user"] =
+                    json!(user_id.and_then(|user_id| lock_holders_by_id.get(&user_id)));
                 section
             })
             .collect();
-        let events: Vec<_> = raw_thread
-            .events_id
-            .iter()
-            .map(|event_id| Event::find_id(conn, *event_id).unwrap())
-            .collect();
 
         // Convert the values to JSON,
         let mut thread_json = serde_json::to_value(raw_thread).unwrap();
         thread_json["created_by_user"] = serde_json::to_value(created_by_user).unwrap();
-        thread_json["sections"] = serde_json::to_value(sections).unwrap();
-        thread_json["events"] = serde_json::to_value(events).unwrap();
+        thread_json["sections"] = serde_json::to_value(sections_json).unwrap();
+        thread_json["events"] = serde_json::to_value(events.values).unwrap();
+        thread_json["missing"] = json!({
+            "sections_id": sections.missing,
+            "events_id": events.missing,
+            "users_id": lock_holders.missing,
+        });
 
         Ok(thread_json)
     }
 
+    /// The current T-time of the thread, in seconds relative to T-0,
+    /// taking into account any holds.
+    pub fn t_minus(&self, now: i64) -> Option<i64> {
+        self.clock.t_minus(self.space__t0, now)
+    }
+
+    /// Create a renderer for the thread's timestamps,
+    /// taking into account the timestamps of the provided events (where posted).
+    pub fn time_renderer(&self, events: &[Event]) -> TimeRenderer {
+        let timestamps: Vec<_> = events
+            .iter()
+            .filter(|event| event.posted)
+            .flat_map(|event| self.event_columns.timestamps(&event.cols))
+            .collect();
+
+        self.time_format.renderer(self.space__t0, timestamps)
+    }
+
     /// Update a `Thread` on Reddit.
     ///
     /// This method will return `Ok(())` if the thread is not posted on Reddit.
@@ -126,9 +346,10 @@ impl Thread {
 
         let mut user: reddit::User<'_> = User::find_id(conn, self.created_by_user_id)?.into();
 
+        let ctx = RenderContext::load(conn, self)?;
         user.edit_self_post(
             &format!("t3_{}", self.post_id.clone().unwrap()),
-            &self.to_markdown(conn).unwrap(),
+            &self.to_markdown(&ctx).expect("error rendering thread"),
         )
         .expect("error updating post on Reddit");
 
@@ -138,113 +359,194 @@ impl Thread {
         Ok(())
     }
 
-    /// Find a given `Thread` by its ID.
-    ///
-    /// Internally uses a cache to limit database accesses.
-    pub fn find_id(conn: &Database, thread_id: i32) -> QueryResult<Self> {
-        use crate::schema::thread::dsl::thread;
-
-        let mut cache = CACHE.lock();
-        if cache.contains_key(&thread_id) {
-            Ok(cache.get_mut(&thread_id).unwrap().clone())
-        } else {
-            let result: Self = thread.find(thread_id).first(conn)?;
-            cache.insert(thread_id, result.clone());
-            Ok(result)
-        }
-    }
-
-    /// Create a `Thread` given the data.
+    /// Create a `Thread` in the provided state given the data provided by a user,
+    /// along with the ID of the user.
     ///
     /// The inserted row is added to the global cache and returned.
     pub fn create(
         conn: &Database,
         data: &ExternalInsertThread,
         user_id: i32,
-        reddit_post_id: Option<String>,
+        state: ThreadState,
     ) -> QueryResult<Self> {
-        use crate::schema::thread::dsl::thread;
-
-        let insertable_thread = InsertThread {
-            thread_name: data.thread_name.clone(),
-            display_name: data.display_name.clone(),
-            post_id: reddit_post_id,
-            subreddit: data.subreddit.clone(),
-            space__t0: data.space__t0,
-            video_url: data.video_url.clone(),
-            spacex__api_id: data.spacex__api_id.clone(),
-            created_by_user_id: user_id,
-            events_id: vec![],
-            sections_id: vec![],
-            event_column_headers: data.event_column_headers.clone(),
-            space__utc_col_index: data.space__utc_col_index,
-            is_live: data.is_live.unwrap_or(false),
-        };
+        <Self as Repository>::create(conn, &data.insertable(user_id, state))
+    }
 
-        let result: Self = diesel::insert_into(thread)
-            .values(insertable_thread)
-            .get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
+    /// Create a `Thread` along with the provided sections and events, all in one transaction.
+    ///
+    /// The sections and events are added to the thread in the order provided.
+    /// Rather than one message per value,
+    /// WebSocket clients receive a single `create_many` message with all sections,
+    /// and another with all events.
+    pub fn create_with_contents(
+        conn: &Database,
+        data: &InsertThread,
+        sections: &[TemplateSection],
+        events: &[TransferredEvent],
+    ) -> QueryResult<Self> {
+        Thread::create_with(conn, data, sections, events, |_| UpdateThread::default())
+    }
+
+    /// Delete a `Thread` along with all of its sections and events, all in one transaction.
+    pub fn delete_with_contents(conn: &Database, id: i32) -> QueryResult<()> {
+        let thread = Thread::find_id(conn, id)?;
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            for &event_id in &thread.events_id {
+                Event::delete(conn, event_id)?;
+            }
+            for &section_id in &thread.sections_id {
+                Section::delete(conn, section_id)?;
+            }
+            Thread::delete(conn, id)?;
+
+            Ok(())
+        })
+    }
 
-        let _ = Message {
-            room:      Room::ThreadCreate,
-            action:    Action::Create,
-            data_type: DataType::Thread,
-            data:      &result,
+    /// Create a `Thread` as in `create_with_contents`,
+    /// additionally making the changes returned by `changes` (given the created sections)
+    /// in the same transaction.
+    pub(super) fn create_with(
+        conn: &Database,
+        data: &InsertThread,
+        sections: &[TemplateSection],
+        events: &[TransferredEvent],
+        changes: impl FnOnce(&[Section]) -> UpdateThread,
+    ) -> QueryResult<Self> {
+        use crate::schema::{event::dsl::event, section::dsl::section};
+
+        let (thread, sections, events) = conn.transaction::<_, diesel::result::Error, _>(|| {
+            let thread = Thread::insert_row(conn, data)?;
+
+            let sections: Vec<Section> = if sections.is_empty() {
+                vec![]
+            } else {
+                let insertable_sections: Vec<_> = sections
+                    .iter()
+                    .map(|value| InsertSection {
+                        name:         value.name.clone(),
+                        content:      value.content.clone(),
+                        in_thread_id: thread.id,
+                        kind:         value.kind.clone(),
+                        data:         value.data.clone(),
+                    })
+                    .collect();
+                diesel::insert_into(section)
+                    .values(&insertable_sections)
+                    .get_results(conn)?
+            };
+
+            let events: Vec<Event> = if events.is_empty() {
+                vec![]
+            } else {
+                let insertable_events: Vec<_> = events
+                    .iter()
+                    .map(|value| InsertEvent {
+                        posted:       value.posted,
+                        in_thread_id: thread.id,
+                        cols:         value.cols.clone(),
+                        post_at:      value.post_at,
+                    })
+                    .collect();
+                diesel::insert_into(event)
+                    .values(&insertable_events)
+                    .get_results(conn)?
+            };
+
+            let thread_changes = UpdateThread {
+                sections_id: Some(sections.iter().map(|value| value.id).collect()),
+                events_id: Some(events.iter().map(|value| value.id).collect()),
+                ..changes(&sections)
+            };
+            let thread = Thread::update_row(conn, thread.id, None, &thread_changes)?;
+
+            Ok((thread, sections, events))
+        })?;
+
+        Self::cache().insert(thread.id, thread.clone());
+        invalidate(conn, CacheKey::Thread(thread.id));
+
+        if thread.is_public(conn) {
+            let _ = Message {
+                room:      thread.room(Action::Create),
+                action:    Action::Create,
+                data_type: DataType::Thread,
+                data:      &thread,
+            }
+            .send();
         }
-        .send();
 
-        Ok(result)
-    }
+        if !sections.is_empty() {
+            for value in &sections {
+                Section::cache().insert(value.id, value.clone());
+                invalidate(conn, CacheKey::Section(value.id));
+            }
+        }
+        if !sections.is_empty() && thread.is_public(conn) {
+            let _ = Message {
+                room:      Room::Thread(thread.id),
+                action:    Action::CreateMany,
+                data_type: DataType::Section,
+                data:      &sections,
+            }
+            .send();
+        }
 
-    /// Update a `Thread` given an ID and the data to update.
-    ///
-    /// The entry is updated in the database, added to cache, and returned.
-    pub fn update(conn: &Database, thread_id: i32, data: &UpdateThread) -> QueryResult<Self> {
-        use crate::schema::thread::dsl::{id, thread};
-
-        let result: Self = diesel::update(thread)
-            .filter(id.eq(thread_id))
-            .set(data)
-            .get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::Thread(thread_id),
-            action:    Action::Update,
-            data_type: DataType::Thread,
-            data:      &Update::new(thread_id, data),
+        if !events.is_empty() {
+            for value in &events {
+                Event::cache().insert(value.id, value.clone());
+                invalidate(conn, CacheKey::Event(value.id));
+            }
+        }
+        if !events.is_empty() && thread.is_public(conn) {
+            let _ = Message {
+                room:      Room::Thread(thread.id),
+                action:    Action::CreateMany,
+                data_type: DataType::Event,
+                data:      &events,
+            }
+            .send();
         }
-        .send();
 
-        Ok(result)
+        Ok(thread)
     }
+}
 
-    /// Delete a `Thread` given its ID.
-    ///
-    /// Removes the entry from cache and returns the number of rows deleted (should be `1`).
-    pub fn delete(conn: &Database, thread_id: i32) -> QueryResult<usize> {
-        use crate::schema::thread::dsl::{id, thread};
+impl Repository for Thread {
+    type Insert = InsertThread;
+    type Update = UpdateThread;
 
-        CACHE.lock().remove(&thread_id);
+    const DATA_TYPE: DataType = DataType::Thread;
 
-        let _ = Message {
-            room:      Room::Thread(thread_id),
-            action:    Action::Delete,
-            data_type: DataType::Thread,
-            data:      &json!({ "id": thread_id }),
-        }
-        .send();
+    repository_primitives!(thread, versioned);
+
+    fn cache() -> &'static Cache<Self> {
+        &CACHE
+    }
 
-        let removed_count = diesel::delete(thread)
-            .filter(id.eq(thread_id))
-            .execute(conn);
+    fn cache_key(id: i32) -> CacheKey {
+        CacheKey::Thread(id)
+    }
+
+    fn id(&self) -> i32 {
+        self.id
+    }
 
-        if let Ok(removed_count) = removed_count {
-            debug_assert_eq!(removed_count, 1);
+    fn version(&self) -> Option<i32> {
+        Some(self.version)
+    }
+
+    fn room(&self, action: Action) -> Room {
+        match action {
+            Action::Create => Room::ThreadCreate,
+            _ => Room::Thread(self.id),
         }
+    }
 
-        removed_count
+    /// Drafts are only visible to those able to modify them.
+    fn is_public(&self, _conn: &Database) -> bool {
+        self.state != ThreadState::Draft
     }
 }
 
@@ -252,15 +554,11 @@ impl ToMarkdown for Thread {
     /// Convert the `Thread` object to valid markdown.
     /// The resulting string is intended for consumption by Reddit,
     /// but should be valid for any markdown flavor supporting tables.
-    fn to_markdown(&self, conn: &Database) -> Result<String, Box<dyn Error>> {
+    fn to_markdown(&self, ctx: &RenderContext) -> Result<String, RenderError> {
         let mut md = String::new();
 
         for &section_id in &self.sections_id {
-            writeln!(
-                &mut md,
-                "{}\n",
-                Section::find_id(conn, section_id)?.to_markdown(conn)?
-            )?;
+            writeln!(&mut md, "{}\n", ctx.section(section_id)?.to_markdown(ctx)?)?;
         }
 
         Ok(md)
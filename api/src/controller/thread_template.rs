@@ -0,0 +1,328 @@
+use super::{
+    section_kind,
+    Cache,
+    CacheKey,
+    EventColumns,
+    ExternalInsertThread,
+    MarkdownKind,
+    Page,
+    Repository,
+    SectionKindError,
+    Sort,
+    TimeFormat,
+    User,
+    THREAD_TEMPLATE_CACHE_SIZE,
+};
+use crate::{
+    schema::thread_template,
+    websocket::{Action, DataType, Room},
+    Database,
+};
+use derive_deref::Deref;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+};
+use macros::generate_structs;
+use once_cell::sync::Lazy;
+use rocket::FromForm;
+use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{io::Write, str::FromStr};
+
+/// Replaced with the name of the thread (or its launch)
+/// when a thread is created from a template.
+const NAME_PLACEHOLDER: &str = "{name}";
+
+/// A global cache, containing a mapping of IDs to their respective `ThreadTemplate`.
+static CACHE: Lazy<Cache<ThreadTemplate>> = Lazy::new(|| Cache::new(*THREAD_TEMPLATE_CACHE_SIZE));
+
+/// A section that is created in every thread created from a template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateSection {
+    pub name:    String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default = "default_kind")]
+    pub kind:    String,
+    #[serde(default)]
+    pub data:    Value,
+}
+
+fn default_kind() -> String {
+    MarkdownKind::NAME.into()
+}
+
+/// The ordered sections of a template,
+/// stored as JSON in the database.
+#[derive(
+    Debug, Clone, Default, PartialEq, Deref, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[serde(transparent)]
+#[sql_type = "Jsonb"]
+pub struct TemplateSections(pub Vec<TemplateSection>);
+
+impl TemplateSections {
+    /// Ensure every section is of a known kind, with valid data for that kind.
+    pub fn check(&self) -> Result<(), SectionKindError> {
+        for section in self.iter() {
+            section_kind(&section.kind)?.check(&section.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromSql<Jsonb, Pg> for TemplateSections {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for TemplateSections {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
+
+generate_structs! {
+    ThreadTemplate("thread_template") {
+        auto id: i32,
+        name: String,
+        thread_name_pattern: String = NAME_PLACEHOLDER,
+        display_name_pattern: String = NAME_PLACEHOLDER,
+        event_columns: EventColumns = EventColumns::default(),
+        time_format: TimeFormat = TimeFormat::default(),
+        sections: TemplateSections = TemplateSections::default(),
+        readonly subreddit: Option<String>,
+        readonly created_by_user_id: i32,
+        auto version: i32,
+    }
+}
+
+fn default_pattern() -> String {
+    NAME_PLACEHOLDER.into()
+}
+
+// The creator of a template is always the current user.
+// Use an `ExternalInsertThreadTemplate` wherever user input is expected.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalInsertThreadTemplate {
+    pub name: String,
+    #[serde(default = "default_pattern")]
+    pub thread_name_pattern: String,
+    #[serde(default = "default_pattern")]
+    pub display_name_pattern: String,
+    #[serde(default)]
+    pub event_columns: EventColumns,
+    #[serde(default)]
+    pub time_format: TimeFormat,
+    #[serde(default)]
+    pub sections: TemplateSections,
+    pub subreddit: Option<String>,
+}
+
+/// Criteria that all `ThreadTemplate`s returned by `ThreadTemplate::find_all` must match.
+/// Any criteria not provided are not checked.
+#[derive(Debug, Default, FromForm)]
+pub struct ThreadTemplateFilter {
+    pub subreddit:          Option<String>,
+    pub created_by_user_id: Option<i32>,
+}
+
+/// Fields that a list of `ThreadTemplate`s can be sorted by.
+#[derive(Debug, Clone, Copy)]
+pub enum ThreadTemplateSortKey {
+    Id,
+    Name,
+}
+
+impl FromStr for ThreadTemplateSortKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "id" => Ok(ThreadTemplateSortKey::Id),
+            "name" => Ok(ThreadTemplateSortKey::Name),
+            _ => Err("unknown sort key"),
+        }
+    }
+}
+
+/// Substitute the name into a pattern.
+///
+/// Returns `None` if the pattern requires a name and none is available.
+fn fill_pattern(pattern: &str, name: Option<&str>) -> Option<String> {
+    match name {
+        Some(name) => Some(pattern.replace(NAME_PLACEHOLDER, name)),
+        None if pattern.contains(NAME_PLACEHOLDER) => None,
+        None => Some(pattern.into()),
+    }
+}
+
+impl ThreadTemplate {
+    /// Find a page of `ThreadTemplate`s in the database matching the provided filter.
+    /// Unless otherwise specified, the results are sorted by ID.
+    ///
+    /// Templates without a subreddit are only included if they are visible to the provided user.
+    ///
+    /// Does _not_ use cache (reading or writing),
+    /// so as to avoid storing values rarely accessed.
+    pub fn find_all(
+        conn: &Database,
+        filter: &ThreadTemplateFilter,
+        sort: Option<Sort<ThreadTemplateSortKey>>,
+        page: Page,
+        viewer: Option<&User>,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::thread_template::dsl::{
+            created_by_user_id,
+            id,
+            name,
+            subreddit,
+            thread_template,
+        };
+
+        let mut query = thread_template.into_boxed();
+
+        match viewer {
+            Some(user) if user.is_global_admin => {}
+            Some(user) => {
+                query = query.filter(subreddit.is_not_null().or(created_by_user_id.eq(user.id)))
+            }
+            None => query = query.filter(subreddit.is_not_null()),
+        }
+
+        if let Some(value) = &filter.subreddit {
+            query = query.filter(subreddit.eq(value));
+        }
+        if let Some(value) = filter.created_by_user_id {
+            query = query.filter(created_by_user_id.eq(value));
+        }
+
+        let sort = sort.unwrap_or_else(|| Sort::ascending(ThreadTemplateSortKey::Id));
+        query = match sort.key {
+            ThreadTemplateSortKey::Id => order_by!(query, id, sort),
+            ThreadTemplateSortKey::Name => order_by!(query, name, sort),
+        };
+
+        query
+            .then_order_by(id.asc())
+            .limit(page.limit)
+            .offset(page.offset)
+            .load(conn)
+    }
+
+    /// Create a `ThreadTemplate` given the data provided by a user,
+    /// along with the ID of the user.
+    ///
+    /// The inserted row is added to the global cache and returned.
+    pub fn create(
+        conn: &Database,
+        data: &ExternalInsertThreadTemplate,
+        user_id: i32,
+    ) -> QueryResult<Self> {
+        let insertable_template = InsertThreadTemplate {
+            name: data.name.clone(),
+            thread_name_pattern: data.thread_name_pattern.clone(),
+            display_name_pattern: data.display_name_pattern.clone(),
+            event_columns: data.event_columns.clone(),
+            time_format: data.time_format.clone(),
+            sections: data.sections.clone(),
+            subreddit: data.subreddit.clone(),
+            created_by_user_id: user_id,
+        };
+
+        <Self as Repository>::create(conn, &insertable_template)
+    }
+
+    /// Can the provided user (if any) see the template?
+    ///
+    /// As with presets, templates belonging to a subreddit are visible to everyone;
+    /// those without a subreddit are only visible to their creator and global admins.
+    pub fn is_visible_to(&self, user: Option<&User>) -> bool {
+        self.subreddit.is_some()
+            || user.map_or(false, |user| {
+                user.is_global_admin || user.id == self.created_by_user_id
+            })
+    }
+
+    /// Can the template be used to create a thread in the provided subreddit?
+    ///
+    /// Templates belonging to a subreddit can only be used for threads in that subreddit.
+    /// Templates without a subreddit can only be used by the user that created them.
+    pub fn is_available_to(&self, subreddit: Option<&str>, user_id: i32) -> bool {
+        match &self.subreddit {
+            Some(template_subreddit) => subreddit.map_or(false, |subreddit| {
+                subreddit.eq_ignore_ascii_case(template_subreddit)
+            }),
+            None => self.created_by_user_id == user_id,
+        }
+    }
+}
+
+impl ExternalInsertThread {
+    /// Fill in anything not provided by the user from a template.
+    ///
+    /// The name (if any) is substituted for `{name}` in the template's patterns.
+    /// A pattern requiring a name is not used if there is none.
+    pub fn apply_template(&mut self, template: &ThreadTemplate, name: Option<&str>) {
+        if self.thread_name.is_empty() {
+            if let Some(thread_name) = fill_pattern(&template.thread_name_pattern, name) {
+                self.thread_name = thread_name;
+            }
+        }
+        if self.display_name.is_empty() {
+            if let Some(display_name) = fill_pattern(&template.display_name_pattern, name) {
+                self.display_name = display_name;
+            }
+        }
+        if self.event_columns.is_none() {
+            self.event_columns = Some(template.event_columns.clone());
+        }
+        if self.time_format.is_none() {
+            self.time_format = Some(template.time_format.clone());
+        }
+    }
+}
+
+impl Repository for ThreadTemplate {
+    type Insert = InsertThreadTemplate;
+    type Update = UpdateThreadTemplate;
+
+    const DATA_TYPE: DataType = DataType::ThreadTemplate;
+
+    repository_primitives!(thread_template, versioned);
+
+    fn cache() -> &'static Cache<Self> {
+        &CACHE
+    }
+
+    fn cache_key(id: i32) -> CacheKey {
+        CacheKey::ThreadTemplate(id)
+    }
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn version(&self) -> Option<i32> {
+        Some(self.version)
+    }
+
+    fn room(&self, _action: Action) -> Room {
+        Room::Template
+    }
+
+    /// Templates without a subreddit are private to their creator.
+    fn is_public(&self, _conn: &Database) -> bool {
+        self.subreddit.is_some()
+    }
+}
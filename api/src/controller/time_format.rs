@@ -0,0 +1,185 @@
+use super::format_t_minus;
+use chrono::{
+    format::{Item, StrftimeItems},
+    TimeZone,
+    Utc,
+};
+use chrono_tz::Tz;
+use derive_more::Display;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeSet, error::Error, io::Write};
+
+/// When to show the date of a timestamp, in addition to the time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShowDate {
+    Never,
+    Always,
+    /// Only when the thread's events occur on more than one day.
+    WhenSpanningDays,
+}
+
+/// How a thread's timestamps are displayed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(default, deny_unknown_fields)]
+#[sql_type = "Jsonb"]
+pub struct TimeFormat {
+    /// A strftime pattern for the time.
+    pub pattern:      String,
+    /// A strftime pattern for the date, shown before the time.
+    pub date_pattern: String,
+    /// The name of an IANA time zone, or `None` for UTC.
+    pub timezone:     Option<String>,
+    pub show_date:    ShowDate,
+    /// Whether to follow the time with `T±H:MM:SS` relative to the thread's T-0.
+    pub show_t_minus: bool,
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        TimeFormat {
+            pattern:      "%H:%M".into(),
+            date_pattern: "%Y-%m-%d".into(),
+            timezone:     None,
+            show_date:    ShowDate::Never,
+            show_t_minus: false,
+        }
+    }
+}
+
+/// Why a `TimeFormat` was rejected.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum TimeFormatError {
+    #[display(fmt = r#"invalid pattern "{}""#, _0)]
+    InvalidPattern(String),
+    #[display(fmt = r#"unknown time zone "{}""#, _0)]
+    UnknownTimezone(String),
+}
+
+impl Error for TimeFormatError {}
+
+/// Is the strftime pattern valid?
+fn is_valid_pattern(pattern: &str) -> bool {
+    StrftimeItems::new(pattern).all(|item| item != Item::Error)
+}
+
+impl TimeFormat {
+    /// Ensure the patterns are valid and the time zone is known.
+    pub fn check(&self) -> Result<(), TimeFormatError> {
+        for pattern in &[&self.pattern, &self.date_pattern] {
+            if !is_valid_pattern(pattern) {
+                return Err(TimeFormatError::InvalidPattern((*pattern).clone()));
+            }
+        }
+
+        self.tz().map(|_| ())
+    }
+
+    /// The time zone to display timestamps in.
+    fn tz(&self) -> Result<Tz, TimeFormatError> {
+        match &self.timezone {
+            Some(name) => name
+                .parse()
+                .map_err(|_| TimeFormatError::UnknownTimezone(name.clone())),
+            None => Ok(Tz::UTC),
+        }
+    }
+
+    /// Create a renderer for a thread with the provided T-0,
+    /// whose events occur at the provided timestamps.
+    ///
+    /// The timestamps are only used to determine if the events span multiple days.
+    pub fn renderer(
+        &self,
+        t0: Option<i64>,
+        timestamps: impl IntoIterator<Item = i64>,
+    ) -> TimeRenderer {
+        // The format is checked when it is stored,
+        // so this only falls back to UTC if a time zone is later removed.
+        let tz = self.tz().unwrap_or(Tz::UTC);
+
+        let show_date = match self.show_date {
+            ShowDate::Never => false,
+            ShowDate::Always => true,
+            ShowDate::WhenSpanningDays => {
+                timestamps
+                    .into_iter()
+                    .filter_map(|timestamp| tz.timestamp_opt(timestamp, 0).single())
+                    .map(|datetime| datetime.date().naive_local())
+                    .collect::<BTreeSet<_>>()
+                    .len()
+                    > 1
+            }
+        };
+
+        TimeRenderer {
+            format: self.clone(),
+            tz,
+            t0,
+            show_date,
+        }
+    }
+}
+
+/// Displays timestamps according to a `TimeFormat`,
+/// in the context of a particular thread.
+#[derive(Debug, Clone)]
+pub struct TimeRenderer {
+    format:    TimeFormat,
+    tz:        Tz,
+    t0:        Option<i64>,
+    show_date: bool,
+}
+
+impl TimeRenderer {
+    /// Display a UNIX timestamp.
+    ///
+    /// Returns `None` if the timestamp is out of range.
+    pub fn render(&self, timestamp: i64) -> Option<String> {
+        let datetime = Utc
+            .timestamp_opt(timestamp, 0)
+            .single()?
+            .with_timezone(&self.tz);
+
+        let mut rendered = if self.show_date {
+            format!(
+                "{} {}",
+                datetime.format(&self.format.date_pattern),
+                datetime.format(&self.format.pattern)
+            )
+        } else {
+            datetime.format(&self.format.pattern).to_string()
+        };
+
+        if let (true, Some(t0)) = (self.format.show_t_minus, self.t0) {
+            rendered = format!(
+                "{} ({})",
+                rendered,
+                format_t_minus(timestamp.checked_sub(t0)?)
+            );
+        }
+
+        Some(rendered)
+    }
+}
+
+impl FromSql<Jsonb, Pg> for TimeFormat {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for TimeFormat {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
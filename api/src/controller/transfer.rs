@@ -0,0 +1,186 @@
+use super::{value_to_text, ColumnError, Event, EventColumns, InsertEvent, PostAt};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+/// The name of the CSV column indicating whether an event is posted.
+const POSTED_HEADER: &str = "posted";
+
+/// An `Event` as imported into (or exported from) a thread.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransferredEvent {
+    pub cols:    Value,
+    #[serde(default)]
+    pub posted:  bool,
+    #[serde(default)]
+    pub post_at: Option<PostAt>,
+}
+
+/// Why a set of events could not be imported or exported.
+#[derive(Debug, Display)]
+pub enum TransferError {
+    #[display(fmt = "invalid CSV: {}", _0)]
+    Csv(csv::Error),
+    #[display(fmt = "invalid JSON: {}", _0)]
+    Json(serde_json::Error),
+    #[display(fmt = r#"unknown column "{}""#, _0)]
+    UnknownColumn(String),
+    #[display(fmt = r#"invalid value "{}" for "posted" in row {}"#, value, row)]
+    InvalidPosted { row: usize, value: String },
+    #[display(fmt = "row {}: {}", row, error)]
+    Row { row: usize, error: ColumnError },
+}
+
+impl Error for TransferError {}
+
+impl From<csv::Error> for TransferError {
+    fn from(err: csv::Error) -> Self {
+        TransferError::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for TransferError {
+    fn from(err: serde_json::Error) -> Self {
+        TransferError::Json(err)
+    }
+}
+
+impl From<&Event> for TransferredEvent {
+    fn from(event: &Event) -> Self {
+        TransferredEvent {
+            cols:    event.cols.clone(),
+            posted:  event.posted,
+            post_at: event.post_at,
+        }
+    }
+}
+
+/// Where a CSV column is stored in a `TransferredEvent`.
+#[derive(Debug, Clone, Copy)]
+enum CsvField {
+    Column(usize),
+    Posted,
+}
+
+/// Parse events from CSV.
+///
+/// The first row is a header naming each column,
+/// which must be in the thread's schema (or be `posted`).
+/// Columns may be in any order, and may be omitted.
+/// Each field is interpreted according to the type of its column.
+pub fn events_from_csv(
+    columns: &EventColumns,
+    input: &str,
+) -> Result<Vec<TransferredEvent>, TransferError> {
+    let mut reader = csv::Reader::from_reader(input.as_bytes());
+
+    let fields = reader
+        .headers()?
+        .iter()
+        .map(
+            |header| match columns.iter().position(|column| column.name == header) {
+                Some(index) => Ok(CsvField::Column(index)),
+                None if header == POSTED_HEADER => Ok(CsvField::Posted),
+                None => Err(TransferError::UnknownColumn(header.into())),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut events = vec![];
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        let mut event = TransferredEvent {
+            cols:    Value::Array(vec![Value::Null; columns.len()]),
+            posted:  false,
+            post_at: None,
+        };
+
+        for (field, text) in fields.iter().zip(record.iter()) {
+            match *field {
+                CsvField::Column(index) => {
+                    event.cols[index] = columns[index].kind.parse_text(text);
+                }
+                CsvField::Posted => {
+                    event.posted = match text {
+                        "" | "false" => false,
+                        "true" => true,
+                        _ => {
+                            return Err(TransferError::InvalidPosted {
+                                row:   row + 1,
+                                value: text.into(),
+                            })
+                        }
+                    }
+                }
+            }
+        }
+
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// Parse events from a JSON array, each element being a `TransferredEvent`.
+pub fn events_from_json(input: &str) -> Result<Vec<TransferredEvent>, TransferError> {
+    Ok(serde_json::from_str(input)?)
+}
+
+/// Validate each imported event against the thread's schema,
+/// with the same rules as when creating a single event.
+pub fn prepare_import(
+    columns: &EventColumns,
+    thread_id: i32,
+    events: Vec<TransferredEvent>,
+) -> Result<Vec<InsertEvent>, TransferError> {
+    events
+        .into_iter()
+        .enumerate()
+        .map(|(row, event)| {
+            Ok(InsertEvent {
+                posted:       event.posted,
+                in_thread_id: thread_id,
+                cols:         columns.validate(&event.cols).map_err(|error| {
+                    TransferError::Row {
+                        row: row + 1,
+                        error,
+                    }
+                })?,
+                post_at:      event.post_at,
+            })
+        })
+        .collect()
+}
+
+/// Write events as CSV, with a header of the column names followed by `posted`.
+///
+/// Values are written such that the result can be imported without any changes.
+pub fn events_to_csv(columns: &EventColumns, events: &[Event]) -> Result<String, TransferError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    writer.write_record(
+        columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .chain(Some(POSTED_HEADER)),
+    )?;
+
+    for event in events {
+        let values = event.cols.as_array().map(Vec::as_slice).unwrap_or_default();
+
+        writer.write_record(
+            (0..columns.len())
+                .map(|i| values.get(i).map(value_to_text).unwrap_or_default())
+                .chain(Some(event.posted.to_string())),
+        )?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| TransferError::Csv(err.into_error().into()))?;
+
+    // The CSV writer only ever writes the (valid UTF-8) strings provided to it.
+    Ok(String::from_utf8(bytes).expect("CSV is valid UTF-8"))
+}
@@ -1,44 +1,44 @@
 #![allow(non_snake_case)]
 
-use super::{Claim, Thread, USER_CACHE_SIZE};
+use super::{
+    Cache,
+    CacheKey,
+    Claim,
+    Page,
+    PresetEvent,
+    Repository,
+    Sort,
+    Thread,
+    ThreadTemplate,
+    USER_CACHE_SIZE,
+};
 use crate::{
     encryption::{decrypt, encrypt},
     endpoint::oauth::REDDIT,
     schema::user,
-    websocket::{Action, DataType, Message, Room, Update},
+    websocket::{Action, DataType, Room},
     DataDB,
     Database,
 };
-use lru_cache::LruCache;
 use macros::generate_structs;
 use once_cell::sync::Lazy;
-use parking_lot::Mutex;
 use rocket::{
     http::Status,
     request::{self, FromRequest, Request},
+    FromForm,
     Outcome,
 };
 use rocket_contrib::databases::diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
 use std::{
     convert::TryFrom,
+    str::FromStr,
     time::{Duration, UNIX_EPOCH},
 };
 #[cfg(debug)]
-use {rocket_contrib::json::Json, serde::Deserialize, serde_json::json};
-
-/// A global cache, containing a mapping of IDs to their respective `Event`.
-///
-/// The cache is protected by a `Mutex`,
-/// ensuring there is only ever at most one writer at a time.
-/// Note that even when reading,
-/// there must be a lock on mutability,
-/// as the `LruCache` must be able to update itself.
-///
-/// To read from the cache,
-/// you'll want to call `CACHE.lock()` before performing normal operations.
-/// ```
-static CACHE: Lazy<Mutex<LruCache<i32, User>>> =
-    Lazy::new(|| Mutex::new(LruCache::new(USER_CACHE_SIZE)));
+use {rocket_contrib::json::Json, serde::Deserialize};
+
+/// A global cache, containing a mapping of IDs to their respective `User`.
+static CACHE: Lazy<Cache<User>> = Lazy::new(|| Cache::new(*USER_CACHE_SIZE));
 
 generate_structs! {
     User("user") {
@@ -150,6 +150,32 @@ impl Into<InsertUser> for Json<ExternalInsertUser> {
     }
 }
 
+/// Criteria that all `User`s returned by `User::find_all` must match.
+/// Any criteria not provided are not checked.
+#[derive(Debug, Default, FromForm)]
+pub struct UserFilter {
+    pub reddit_username: Option<String>,
+}
+
+/// Fields that a list of `User`s can be sorted by.
+#[derive(Debug, Clone, Copy)]
+pub enum UserSortKey {
+    Id,
+    RedditUsername,
+}
+
+impl FromStr for UserSortKey {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "id" => Ok(UserSortKey::Id),
+            "reddit_username" => Ok(UserSortKey::RedditUsername),
+            _ => Err("unknown sort key"),
+        }
+    }
+}
+
 impl User {
     /// Check if the user is a moderator of a given subreddit.
     ///
@@ -169,10 +195,16 @@ impl User {
     pub fn is_host_for(&self, subreddit: Option<&str>) -> bool {
         let subreddit = subreddit.unwrap_or_default().to_lowercase();
 
-        match subreddit.as_ref() {
-            "spacex" => self.spacex__is_host,
-            _ => false,
+        self.hosted_subreddits().contains(&subreddit.as_ref())
+    }
+
+    /// All subreddits the user is a host of, in lowercase.
+    pub fn hosted_subreddits(&self) -> Vec<&'static str> {
+        let mut subreddits = vec![];
+        if self.spacex__is_host {
+            subreddits.push("spacex");
         }
+        subreddits
     }
 
     /// Is the provided user able to modify data (including sections and events)
@@ -213,6 +245,26 @@ impl User {
         thread.created_by_user_id == self.id
     }
 
+    /// Is the provided user able to modify (or delete) the indicated preset?
+    ///
+    /// Global admins can modify any preset,
+    /// and subreddit hosts can modify any preset belonging to their subreddit.
+    /// Otherwise, only the user that created the preset can modify it.
+    pub fn can_modify_preset(&self, preset: &PresetEvent) -> bool {
+        self.is_global_admin
+            || self.is_host_for(preset.subreddit.as_ref().map(String::as_str))
+            || preset.created_by_user_id == self.id
+    }
+
+    /// Is the provided user able to modify (or delete) the indicated template?
+    ///
+    /// The same rules apply as for presets.
+    pub fn can_modify_template(&self, template: &ThreadTemplate) -> bool {
+        self.is_global_admin
+            || self.is_host_for(template.subreddit.as_ref().map(String::as_str))
+            || template.created_by_user_id == self.id
+    }
+
     /// When performing any request to Reddit,
     /// we need to send an access token to authenticate ourselves.
     /// These tokens must be refreshed every hour (currently; that's subject to change).
@@ -253,98 +305,71 @@ impl User {
         }
     }
 
-    /// Find all `User`s in the database.
+    /// Find a page of `User`s in the database matching the provided filter.
+    /// Unless otherwise specified, the results are sorted by ID.
     ///
     /// Does _not_ use cache (reading or writing),
     /// so as to avoid storing values rarely accessed.
-    pub fn find_all(conn: &Database) -> QueryResult<Vec<Self>> {
-        use crate::schema::user::dsl::user;
-        user.load(conn)
-    }
+    pub fn find_all(
+        conn: &Database,
+        filter: &UserFilter,
+        sort: Option<Sort<UserSortKey>>,
+        page: Page,
+    ) -> QueryResult<Vec<Self>> {
+        use crate::schema::user::dsl::{id, reddit_username, user};
 
-    /// Find a specific `User` given its ID.
-    ///
-    /// Internally uses a cache to limit database accesses.
-    pub fn find_id(conn: &Database, user_id: i32) -> QueryResult<Self> {
-        use crate::schema::user::dsl::user;
+        let mut query = user.into_boxed();
 
-        let mut cache = CACHE.lock();
-        if cache.contains_key(&user_id) {
-            Ok(cache.get_mut(&user_id).unwrap().clone())
-        } else {
-            let result: Self = user.find(user_id).first(conn)?;
-            cache.insert(user_id, result.clone());
-            Ok(result)
+        if let Some(value) = &filter.reddit_username {
+            query = query.filter(reddit_username.eq(value));
         }
-    }
 
-    /// Create a `User` given the data.
-    ///
-    /// The inserted row is added to the global cache and returned.
-    pub fn create(conn: &Database, data: &InsertUser) -> QueryResult<Self> {
-        use crate::schema::user::dsl::user;
-
-        let result: Self = diesel::insert_into(user).values(data).get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::User,
-            action:    Action::Create,
-            data_type: DataType::User,
-            data:      &result,
-        }
-        .send();
+        let sort = sort.unwrap_or_else(|| Sort::ascending(UserSortKey::Id));
+        query = match sort.key {
+            UserSortKey::Id => order_by!(query, id, sort),
+            UserSortKey::RedditUsername => order_by!(query, reddit_username, sort),
+        };
 
-        Ok(result)
+        query
+            .then_order_by(id.asc())
+            .limit(page.limit)
+            .offset(page.offset)
+            .load(conn)
     }
+}
 
-    /// Update a `User` given an ID and the data to update.
-    ///
-    /// The entry is updated in the database, added to cache, and returned.
-    pub fn update(conn: &Database, user_id: i32, data: &UpdateUser) -> QueryResult<Self> {
-        use crate::schema::user::dsl::{id, user};
-
-        let result: Self = diesel::update(user)
-            .filter(id.eq(user_id))
-            .set(data)
-            .get_result(conn)?;
-        CACHE.lock().insert(result.id, result.clone());
-
-        let _ = Message {
-            room:      Room::User,
-            action:    Action::Update,
-            data_type: DataType::User,
-            data:      &Update::new(user_id, data),
-        }
-        .send();
+impl Repository for User {
+    type Insert = InsertUser;
+    type Update = UpdateUser;
+
+    const DATA_TYPE: DataType = DataType::User;
+
+    repository_primitives!(user);
 
-        Ok(result)
+    fn cache() -> &'static Cache<Self> {
+        &CACHE
     }
 
-    /// Delete a `User` given its ID.
-    ///
-    /// Removes the entry from cache and returns the number of rows deleted (should be `1`).
-    #[cfg(debug)]
-    pub fn delete(conn: &Database, user_id: i32) -> QueryResult<usize> {
-        use crate::schema::user::dsl::{id, user};
-
-        CACHE.lock().remove(&user_id);
-
-        let _ = Message {
-            room:      Room::User,
-            action:    Action::Delete,
-            data_type: DataType::User,
-            data:      &json!({ "id": user_id }),
-        }
-        .send();
+    fn cache_key(id: i32) -> CacheKey {
+        CacheKey::User(id)
+    }
 
-        let removed_count = diesel::delete(user).filter(id.eq(user_id)).execute(conn);
+    fn id(&self) -> i32 {
+        self.id
+    }
 
-        if let Ok(removed_count) = removed_count {
-            debug_assert_eq!(removed_count, 1);
-        }
+    fn room(&self, _action: Action) -> Room {
+        Room::User
+    }
 
-        removed_count
+    /// Users can only be deleted in debug builds.
+    #[cfg(release)]
+    fn before_delete(&self, _conn: &Database) -> QueryResult<()> {
+        Err(
+            rocket_contrib::databases::diesel::result::Error::QueryBuilderError(
+                "users cannot be deleted".into(),
+            ),
+        )
     }
 }
 
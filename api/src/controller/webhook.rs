@@ -0,0 +1,427 @@
+use super::{Event, RenderContext, Repository, Section, Thread, ToMarkdown, UpdateThread};
+use crate::Database;
+use derive_deref::Deref;
+use derive_more::Display;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use reqwest::{header::RETRY_AFTER, Response, StatusCode, Url};
+use rocket_contrib::databases::diesel::QueryResult;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    cmp,
+    error::Error,
+    io::Write,
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread,
+    time::Duration,
+};
+
+/// How long to wait before each retry of a failed delivery.
+/// A message is attempted once more than there are delays.
+pub const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(25),
+];
+
+/// The longest a service may ask (via `Retry-After`) for a retry to be delayed.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The most messages that may be waiting to be delivered.
+/// Any further messages are dropped (and logged) until the queue has room.
+const QUEUE_SIZE: usize = 1_000;
+
+/// The client used to deliver all messages.
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
+/// Messages waiting to be delivered,
+/// all of which are sent in order by a single background thread.
+static QUEUE: Lazy<Mutex<SyncSender<Delivery>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::sync_channel::<Delivery>(QUEUE_SIZE);
+
+    thread::Builder::new()
+        .name("webhook".into())
+        .spawn(move || {
+            for delivery in receiver {
+                if let Err(err) = deliver(&CLIENT, &delivery.url, &delivery.payload, &RETRY_DELAYS)
+                {
+                    eprintln!(
+                        "error sending {} webhook for thread {}: {}",
+                        delivery.service, delivery.thread_id, err
+                    );
+                }
+            }
+        })
+        .expect("could not spawn webhook thread");
+
+    Mutex::new(sender)
+});
+
+/// A message waiting to be delivered.
+struct Delivery {
+    service:   WebhookService,
+    url:       String,
+    payload:   Value,
+    thread_id: i32,
+}
+
+/// A service accepting messages via an incoming webhook.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookService {
+    #[display(fmt = "Discord")]
+    Discord,
+    #[display(fmt = "Slack")]
+    Slack,
+}
+
+impl WebhookService {
+    /// The longest message accepted by the service, in characters.
+    fn max_len(self) -> usize {
+        match self {
+            WebhookService::Discord => 2_000,
+            WebhookService::Slack => 4_000,
+        }
+    }
+
+    /// Embolden the text, using the service's own markup.
+    fn bold(self, text: &str) -> String {
+        match self {
+            WebhookService::Discord => format!("**{}**", text),
+            WebhookService::Slack => format!("*{}*", text),
+        }
+    }
+
+    /// Is the URL one the service provides for an incoming webhook?
+    ///
+    /// No other URLs are accepted,
+    /// so that the server cannot be made to send requests to arbitrary (or internal) hosts.
+    fn provides(self, url: &Url) -> bool {
+        let (hosts, path): (&[&str], _) = match self {
+            WebhookService::Discord => (
+                &[
+                    "discord.com",
+                    "discordapp.com",
+                    "ptb.discord.com",
+                    "canary.discord.com",
+                ],
+                "/api/webhooks/",
+            ),
+            WebhookService::Slack => (&["hooks.slack.com"], "/services/"),
+        };
+
+        url.scheme() == "https"
+            && url.port().is_none()
+            && url.username().is_empty()
+            && url.password().is_none()
+            && url.host_str().map_or(false, |host| hosts.contains(&host))
+            && url.path().starts_with(path)
+    }
+
+    /// The body of the request sending the message.
+    fn payload(self, message: String) -> Value {
+        match self {
+            WebhookService::Discord => json!({ "content": message }),
+            WebhookService::Slack => json!({ "text": message }),
+        }
+    }
+}
+
+const fn truthy() -> bool {
+    true
+}
+
+/// A single incoming webhook, and what is sent to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub service:     WebhookService,
+    /// The URL provided by the service.
+    pub url:         String,
+    /// Whether each event is sent as it is posted.
+    #[serde(default = "truthy")]
+    pub events:      bool,
+    /// The sections sent whenever they are changed.
+    #[serde(default)]
+    pub sections_id: Vec<i32>,
+}
+
+/// All webhooks of a thread,
+/// stored as JSON in the database.
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Deref, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[serde(transparent)]
+#[sql_type = "Jsonb"]
+pub struct Webhooks(pub Vec<Webhook>);
+
+/// Why the webhooks of a thread were rejected.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum WebhookConfigError {
+    #[display(fmt = "webhook {} does not have a URL provided by its service", _0)]
+    InvalidUrl(usize),
+    #[display(fmt = "section {} is not in the thread", _0)]
+    UnknownSection(i32),
+}
+
+impl Error for WebhookConfigError {}
+
+/// Why a message could not be delivered.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum WebhookError {
+    #[display(fmt = "{}", _0)]
+    Request(String),
+    #[display(fmt = "the service responded with status {}", _0)]
+    Status(u16),
+}
+
+impl Error for WebhookError {}
+
+/// Is the URL that of a local stand-in for a service?
+/// These are only accepted when testing.
+#[cfg(test)]
+fn is_stand_in(url: &Url) -> bool {
+    url.scheme() == "http" && url.host_str() == Some("127.0.0.1")
+}
+
+#[cfg(not(test))]
+fn is_stand_in(_url: &Url) -> bool {
+    false
+}
+
+impl Webhooks {
+    /// Ensure every webhook has a URL provided by its service,
+    /// and only refers to sections of the thread.
+    pub fn check(&self, sections_id: &[i32]) -> Result<(), WebhookConfigError> {
+        for (i, webhook) in self.iter().enumerate() {
+            let is_valid = Url::parse(&webhook.url).map_or(false, |url| {
+                webhook.service.provides(&url) || is_stand_in(&url)
+            });
+            if !is_valid {
+                return Err(WebhookConfigError::InvalidUrl(i));
+            }
+            if let Some(&id) = webhook
+                .sections_id
+                .iter()
+                .find(|id| !sections_id.contains(id))
+            {
+                return Err(WebhookConfigError::UnknownSection(id));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromSql<Jsonb, Pg> for Webhooks {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for Webhooks {
+    fn to_sql<W: Write>(&self, out: &mut Output<'_, W, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <Value as ToSql<Jsonb, Pg>>::to_sql(&value, out)
+    }
+}
+
+/// Something that happened in a thread, to be sent to its webhooks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// An event was posted,
+    /// with the name and formatted value of each column that has a value.
+    EventPosted(Vec<(String, String)>),
+    /// A section was changed, with its rendered markdown.
+    SectionChanged { section_id: i32, markdown: String },
+}
+
+impl Notification {
+    /// Should the notification be sent to the webhook?
+    fn is_for(&self, webhook: &Webhook) -> bool {
+        match self {
+            Notification::EventPosted(_) => webhook.events,
+            Notification::SectionChanged { section_id, .. } => {
+                webhook.sections_id.contains(section_id)
+            }
+        }
+    }
+
+    /// The message sent to the service, headed by the name of the thread.
+    ///
+    /// Messages longer than the service accepts are truncated.
+    pub fn message(&self, service: WebhookService, thread_name: &str) -> String {
+        let body = match self {
+            Notification::EventPosted(values) => values
+                .iter()
+                .map(|(name, value)| format!("{} {}", service.bold(&format!("{}:", name)), value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Notification::SectionChanged { markdown, .. } => markdown.trim_end().to_owned(),
+        };
+        let message = format!("{}\n{}", service.bold(thread_name), body);
+
+        if message.chars().count() <= service.max_len() {
+            return message;
+        }
+
+        let mut truncated: String = message.chars().take(service.max_len() - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Is the failure one that may succeed if retried?
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// How long the service asked for a retry to be delayed, if it did.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+
+    Some(cmp::min(Duration::from_secs(seconds), MAX_RETRY_AFTER))
+}
+
+/// Send the payload to the URL,
+/// retrying after each delay for as long as the failure is transient
+/// (a server error, rate limit, or failure to connect).
+///
+/// The thread is blocked for the duration.
+pub fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    payload: &Value,
+    delays: &[Duration],
+) -> Result<(), WebhookError> {
+    let mut delays = delays.iter();
+
+    loop {
+        let (error, requested_delay) = match client.post(url).json(payload).send() {
+            Ok(ref response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let error = WebhookError::Status(response.status().as_u16());
+                if !is_transient(response.status()) {
+                    return Err(error);
+                }
+                (error, retry_after(&response))
+            }
+            Err(err) => (WebhookError::Request(err.to_string()), None),
+        };
+
+        match delays.next() {
+            Some(&delay) => thread::sleep(cmp::max(delay, requested_delay.unwrap_or_default())),
+            None => return Err(error),
+        }
+    }
+}
+
+impl Thread {
+    /// Replace the webhooks of the thread with the provided ID.
+    ///
+    /// As webhook URLs are secret,
+    /// WebSocket clients are informed of the new version of the thread, but not the webhooks.
+    pub fn set_webhooks(conn: &Database, id: i32, webhooks: Webhooks) -> QueryResult<Self> {
+        let thread = Thread::update_row(
+            conn,
+            id,
+            None,
+            &UpdateThread {
+                webhooks: Some(webhooks),
+                ..UpdateThread::default()
+            },
+        )?;
+        Thread::updated(conn, &thread, &UpdateThread::default());
+
+        Ok(thread)
+    }
+
+    /// Send the notification to every webhook of the thread it is for.
+    ///
+    /// Messages are queued, to be delivered in order in the background.
+    /// Failures are retried after each of `RETRY_DELAYS`, then logged.
+    /// If the queue is full, the message is dropped (and logged) without blocking.
+    pub fn notify_webhooks(&self, notification: &Notification) {
+        for webhook in self
+            .webhooks
+            .iter()
+            .filter(|webhook| notification.is_for(webhook))
+        {
+            let service = webhook.service;
+            let delivery = Delivery {
+                service,
+                url: webhook.url.clone(),
+                payload: service.payload(notification.message(service, &self.display_name)),
+                thread_id: self.id,
+            };
+
+            let reason = match QUEUE.lock().try_send(delivery) {
+                Ok(()) => continue,
+                Err(TrySendError::Full(_)) => "the queue is full",
+                Err(TrySendError::Disconnected(_)) => "the webhook thread has stopped",
+            };
+            eprintln!(
+                "error sending {} webhook for thread {}: {}",
+                service, self.id, reason
+            );
+        }
+    }
+}
+
+impl Event {
+    /// Send the event to the webhooks of its thread, as it has been posted.
+    pub fn notify_posted(&self, conn: &Database) -> Result<(), Box<dyn Error>> {
+        let thread = Thread::find_id(conn, self.in_thread_id)?;
+        if !thread.webhooks.iter().any(|webhook| webhook.events) {
+            return Ok(());
+        }
+
+        let ctx = RenderContext::load(conn, &thread)?;
+        let values = thread
+            .event_columns
+            .iter()
+            .map(|column| column.name.clone())
+            .zip(thread.event_columns.format(&self.cols, ctx.time())?)
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+
+        thread.notify_webhooks(&Notification::EventPosted(values));
+        Ok(())
+    }
+}
+
+impl Section {
+    /// Send the section to the webhooks of its thread it is designated for,
+    /// as it has been changed.
+    pub fn notify_changed(&self, conn: &Database) -> Result<(), Box<dyn Error>> {
+        let thread = Thread::find_id(conn, self.in_thread_id)?;
+        if !thread
+            .webhooks
+            .iter()
+            .any(|webhook| webhook.sections_id.contains(&self.id))
+        {
+            return Ok(());
+        }
+
+        let ctx = RenderContext::load(conn, &thread)?;
+        thread.notify_webhooks(&Notification::SectionChanged {
+            section_id: self.id,
+            markdown:   self.to_markdown(&ctx)?,
+        });
+        Ok(())
+    }
+}
@@ -1,13 +1,26 @@
 use crate::{
-    controller::{Event, InsertEvent, Thread, UpdateEvent, User},
-    endpoint::helpers::RocketResult,
+    controller::{
+        BatchError,
+        BatchResult,
+        Event,
+        EventFilter,
+        EventOperation,
+        EventSortKey,
+        InsertEvent,
+        Repository,
+        Thread,
+        UpdateEvent,
+        User,
+    },
+    endpoint::helpers::{ETagged, IfMatch, RocketResult},
     DataDB,
 };
 use rocket::{delete, http::Status, patch, post, response::status::Created};
 use rocket_contrib::json::Json;
+use serde::Deserialize;
 
-generic_all!(Event);
-generic_get!(Event);
+generic_all!(Event, EventFilter, EventSortKey, private);
+generic_get!(Event, versioned, in_thread);
 
 /// Create an `Event`.
 #[post("/", data = "<data>")]
@@ -22,27 +35,21 @@ pub fn post(
 
     let thread = Thread::find_id(&conn, data.in_thread_id).expect("thread not found");
 
-    // Ensure the provided columns are of the expected types and length.
-    if !data.cols.is_array()
-        || thread.event_column_headers.len() != data.cols.as_array().unwrap().len()
-        || !data
-            .cols
-            .as_array()
-            .unwrap()
-            .iter()
-            .zip(0..)
-            .all(|(val, i)| match thread.space__utc_col_index {
-                Some(n) if i == n => val.is_number(),
-                _ => val.is_string(),
-            })
-    {
-        return Err(Status::UnprocessableEntity);
-    }
+    // Ensure the provided columns conform to the thread's schema.
+    let mut data = data.into_inner();
+    data.cols = match thread.event_columns.validate(&data.cols) {
+        Ok(cols) => cols,
+        Err(_) => return Err(Status::UnprocessableEntity),
+    };
 
     let ret_val = created!(Event::create(&conn, &data));
-    thread
+
+    // The thread now includes the event, so must be fetched again.
+    Thread::find_id(&conn, thread.id)
+        .expect("thread not found")
         .update_on_reddit(&conn)
         .expect("error updating on Reddit");
+
     ret_val
 }
 
@@ -62,35 +69,47 @@ pub enum UpdateEventDiscriminant {
 
 /// Discriminate between the two types,
 /// calling the `patch_full_event` method as necessary.
+///
+/// If the `If-Match` header is present,
+/// the update is only performed if the event's version matches.
+/// A partial update is always applied to the version it was read from,
+/// so concurrent partial updates cannot overwrite one another.
 #[patch("/<id>", data = "<data>")]
 pub fn patch(
     conn: DataDB,
     user: User,
+    if_match: IfMatch,
     id: i32,
     data: Json<UpdateEventDiscriminant>,
-) -> RocketResult<Json<Event>> {
+) -> RocketResult<ETagged<Json<Event>>> {
     use UpdateEventDiscriminant::{FullEvent, PartialEvent};
 
     match data.into_inner() {
-        FullEvent(data) => patch_full_event(conn, user, id, data),
+        FullEvent(data) => patch_full_event(conn, user, if_match, id, data),
         PartialEvent(data) => {
-            let mut event = match Event::find_id(&conn, id) {
+            let event = match Event::find_id(&conn, id) {
                 Ok(event) => event,
                 Err(_) => return Err(Status::NotFound),
             };
 
-            let event_fields = &mut event.cols;
-
-            for (key, value) in data {
-                event_fields[key] = value;
+            if !if_match.matches(event.version) {
+                return Err(Status::PreconditionFailed);
             }
 
+            let cols = Thread::find_id(&conn, event.in_thread_id)
+                .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?
+                .event_columns
+                .apply_partial(&event.cols, &data)
+                .map_err(|_| Status::UnprocessableEntity)?;
+
+            // The full set of columns is validated when updating the event.
             patch_full_event(
                 conn,
                 user,
+                IfMatch(Some(event.version)),
                 id,
                 UpdateEvent {
-                    cols: Some(event.cols),
+                    cols: Some(cols),
                     ..UpdateEvent::default()
                 },
             )
@@ -99,12 +118,16 @@ pub fn patch(
 }
 
 /// Update the `Event` on Reddit and in the database.
+///
+/// If the columns are being updated,
+/// they must conform to the thread's schema.
 pub fn patch_full_event(
     conn: DataDB,
     user: User,
+    if_match: IfMatch,
     id: i32,
-    data: UpdateEvent,
-) -> RocketResult<Json<Event>> {
+    mut data: UpdateEvent,
+) -> RocketResult<ETagged<Json<Event>>> {
     let event = match Event::find_id(&conn, id) {
         Ok(event) => event,
         Err(_) => return Err(Status::NotFound),
@@ -114,14 +137,26 @@ pub fn patch_full_event(
         return Err(Status::Unauthorized);
     }
 
-    let ret_val = json_result!(Event::update(&conn, id, &data));
+    if !if_match.matches(event.version) {
+        return Err(Status::PreconditionFailed);
+    }
 
-    Thread::find_id(&conn, event.in_thread_id)
-        .expect("thread not found")
+    let thread = Thread::find_id(&conn, event.in_thread_id).expect("thread not found");
+
+    if let Some(cols) = &data.cols {
+        data.cols = match thread.event_columns.validate(cols) {
+            Ok(cols) => Some(cols),
+            Err(_) => return Err(Status::UnprocessableEntity),
+        };
+    }
+
+    let ret_val = versioned_result!(Event::update_if_version(&conn, id, if_match.0, &data))?;
+
+    thread
         .update_on_reddit(&conn)
         .expect("error updating on Reddit");
 
-    ret_val
+    Ok(ret_val)
 }
 
 /// Delete an `Event` as well as any references to its ID.
@@ -145,3 +180,39 @@ pub fn delete(conn: DataDB, user: User, id: i32) -> RocketResult<Status> {
 
     ret_val
 }
+
+/// A number of operations to apply to the events of a single thread.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventBatch {
+    pub in_thread_id: i32,
+    pub operations:   Vec<EventOperation>,
+}
+
+/// Apply a number of operations to the `Event`s of a `Thread` at once.
+///
+/// Either all operations are applied or none are.
+/// Regardless of the number of operations,
+/// the thread is only updated on Reddit once.
+#[post("/batch", data = "<data>")]
+pub fn batch(conn: DataDB, user: User, data: Json<EventBatch>) -> RocketResult<Json<BatchResult>> {
+    if !user.can_modify_thread(&conn, data.in_thread_id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let result = match Event::apply_batch(&conn, data.in_thread_id, &data.operations) {
+        Ok(result) => result,
+        Err(BatchError::VersionMismatch(_)) => return Err(Status::PreconditionFailed),
+        Err(BatchError::NotInThread(_)) | Err(BatchError::Column { .. }) => {
+            return Err(Status::UnprocessableEntity)
+        }
+        Err(BatchError::Database(e)) => return Err(crate::endpoint::helpers::error_mapper(&e)),
+    };
+
+    Thread::find_id(&conn, data.in_thread_id)
+        .expect("thread not found")
+        .update_on_reddit(&conn)
+        .expect("error updating on Reddit");
+
+    Ok(Json(result))
+}
@@ -1,21 +1,67 @@
-/// Generate an endpoint for fetching all of the provided type.
+/// Generate an endpoint for fetching a page of the provided type.
+///
+/// Along with the standard `limit`, `offset`, and `sort` query parameters,
+/// any fields of the type's filter (such as `ThreadFilter`) can be provided.
+/// An unknown sort key results in a `400 BAD REQUEST`.
 ///
 /// This macro should suffice for all types.
+/// For types with private values, pass `private`;
+/// the type's `find_all` then additionally receives the requesting user (if any).
 #[macro_export]
 macro_rules! generic_all {
-    ($x:ident) => {
-        #[rocket::get("/")]
+    ($x:ident, $filter:ident, $sort_key:ident, private) => {
+        #[rocket::get("/?<limit>&<offset>&<sort>&<filter..>")]
+        pub fn all(
+            conn: crate::DataDB,
+            user: Option<crate::controller::User>,
+            limit: Option<i64>,
+            offset: Option<i64>,
+            sort: Option<String>,
+            filter: rocket::request::LenientForm<$filter>,
+        ) -> crate::endpoint::helpers::RocketResult<crate::endpoint::helpers::Paginated<$x>> {
+            let page = crate::controller::Page::new(limit, offset);
+            let sort = sort
+                .map(|sort| sort.parse::<crate::controller::Sort<$sort_key>>())
+                .transpose()
+                .map_err(|_| rocket::http::Status::BadRequest)?;
+
+            $x::find_all(&conn, &filter, sort, page, user.as_ref())
+                .map(|values| crate::endpoint::helpers::Paginated(values, page))
+                .map_err(|e| crate::endpoint::helpers::error_mapper(&e))
+        }
+    };
+
+    ($x:ident, $filter:ident, $sort_key:ident) => {
+        #[rocket::get("/?<limit>&<offset>&<sort>&<filter..>")]
         pub fn all(
             conn: crate::DataDB,
-        ) -> crate::endpoint::helpers::RocketResult<rocket_contrib::json::Json<Vec<$x>>> {
-            json_result!($x::find_all(&conn))
+            limit: Option<i64>,
+            offset: Option<i64>,
+            sort: Option<String>,
+            filter: rocket::request::LenientForm<$filter>,
+        ) -> crate::endpoint::helpers::RocketResult<crate::endpoint::helpers::Paginated<$x>> {
+            let page = crate::controller::Page::new(limit, offset);
+            let sort = sort
+                .map(|sort| sort.parse::<crate::controller::Sort<$sort_key>>())
+                .transpose()
+                .map_err(|_| rocket::http::Status::BadRequest)?;
+
+            $x::find_all(&conn, &filter, sort, page)
+                .map(|values| crate::endpoint::helpers::Paginated(values, page))
+                .map_err(|e| crate::endpoint::helpers::error_mapper(&e))
         }
     };
 }
 
 /// Generate an endpoint for fetching a specific instance of the provided type.
 ///
-/// This macro should suffice for all types.
+/// This macro should suffice for all types implementing `Repository`.
+/// For types with a `version` field,
+/// pass `versioned` to additionally send the version in the `ETag` header.
+/// For types with private values, additionally pass `private`;
+/// values not visible to the requesting user (per `is_visible_to`) are then not found.
+/// For types belonging to a thread, pass `in_thread` instead;
+/// values are then not found unless their thread is visible to the requesting user.
 #[macro_export]
 macro_rules! generic_get {
     ($x:ident) => {
@@ -24,7 +70,67 @@ macro_rules! generic_get {
             conn: crate::DataDB,
             id: i32,
         ) -> crate::endpoint::helpers::RocketResult<rocket_contrib::json::Json<$x>> {
-            json_result!($x::find_id(&conn, id))
+            json_result!(<$x as crate::controller::Repository>::find_id(&conn, id))
+        }
+    };
+
+    ($x:ident, versioned) => {
+        #[rocket::get("/<id>")]
+        pub fn get(
+            conn: crate::DataDB,
+            id: i32,
+        ) -> crate::endpoint::helpers::RocketResult<
+            crate::endpoint::helpers::ETagged<rocket_contrib::json::Json<$x>>,
+        > {
+            etag_result!(<$x as crate::controller::Repository>::find_id(&conn, id))
+        }
+    };
+
+    ($x:ident, versioned, private) => {
+        #[rocket::get("/<id>")]
+        pub fn get(
+            conn: crate::DataDB,
+            user: Option<crate::controller::User>,
+            id: i32,
+        ) -> crate::endpoint::helpers::RocketResult<
+            crate::endpoint::helpers::ETagged<rocket_contrib::json::Json<$x>>,
+        > {
+            etag_result!(
+                <$x as crate::controller::Repository>::find_id(&conn, id).and_then(|value| {
+                    if value.is_visible_to(user.as_ref()) {
+                        Ok(value)
+                    } else {
+                        Err(diesel::result::Error::NotFound)
+                    }
+                })
+            )
+        }
+    };
+
+    ($x:ident, versioned, in_thread) => {
+        #[rocket::get("/<id>")]
+        pub fn get(
+            conn: crate::DataDB,
+            user: Option<crate::controller::User>,
+            id: i32,
+        ) -> crate::endpoint::helpers::RocketResult<
+            crate::endpoint::helpers::ETagged<rocket_contrib::json::Json<$x>>,
+        > {
+            etag_result!(
+                <$x as crate::controller::Repository>::find_id(&conn, id).and_then(|value| {
+                    let thread =
+                        <crate::controller::Thread as crate::controller::Repository>::find_id(
+                            &conn,
+                            value.in_thread_id,
+                        )?;
+
+                    if thread.is_visible_to(user.as_ref()) {
+                        Ok(value)
+                    } else {
+                        Err(diesel::result::Error::NotFound)
+                    }
+                })
+            )
         }
     };
 }
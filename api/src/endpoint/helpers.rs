@@ -1,5 +1,12 @@
-use rocket::http::Status;
-use rocket_contrib::databases::diesel::result::Error;
+use crate::controller::Page;
+use rocket::{
+    http::Status,
+    request::{self, FromRequest, Request},
+    response::{self, Responder, Response},
+    Outcome,
+};
+use rocket_contrib::{databases::diesel::result::Error, json::Json};
+use serde::Serialize;
 
 pub type RocketResult<T> = Result<T, Status>;
 
@@ -10,6 +17,131 @@ pub fn error_mapper(err: &Error) -> Status {
     }
 }
 
+/// Map the error of a versioned update.
+///
+/// The caller must have already verified that the row exists,
+/// so a row not being found indicates the version did not match.
+pub fn precondition_mapper(err: &Error) -> Status {
+    match err {
+        Error::NotFound => Status::PreconditionFailed,
+        _ => Status::InternalServerError,
+    }
+}
+
+/// A response whose body is a versioned value.
+/// The version is sent to the client in the `ETag` header,
+/// which can later be provided in the `If-Match` header of a request.
+#[derive(Debug)]
+pub struct ETagged<R>(pub i32, pub R);
+
+impl<'r, R: Responder<'r>> Responder<'r> for ETagged<R> {
+    fn respond_to(self, request: &Request<'_>) -> response::Result<'r> {
+        Response::build_from(self.1.respond_to(request)?)
+            .raw_header("ETag", format!(r#""{}""#, self.0))
+            .ok()
+    }
+}
+
+/// A page of results.
+/// Links to the adjacent pages are sent to the client in the `Link` header,
+/// preserving any other query parameters (such as filters) of the request.
+#[derive(Debug)]
+pub struct Paginated<T>(pub Vec<T>, pub Page);
+
+impl<T> Paginated<T> {
+    /// Format a link to the page at the provided offset.
+    fn link(request: &Request<'_>, page: Page, offset: i64, rel: &str) -> String {
+        let mut params: Vec<_> = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| {
+                !param.is_empty() && !param.starts_with("limit=") && !param.starts_with("offset=")
+            })
+            .map(ToOwned::to_owned)
+            .collect();
+        params.push(format!("limit={}", page.limit));
+        params.push(format!("offset={}", offset));
+
+        format!(
+            r#"<{}?{}>; rel="{}""#,
+            request.uri().path(),
+            params.join("&"),
+            rel
+        )
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r> for Paginated<T> {
+    fn respond_to(self, request: &Request<'_>) -> response::Result<'r> {
+        let Self(values, page) = self;
+        let mut links = vec![];
+
+        if page.offset > 0 {
+            links.push(Self::link(request, page, 0, "first"));
+            links.push(Self::link(
+                request,
+                page,
+                (page.offset - page.limit).max(0),
+                "prev",
+            ));
+        }
+
+        // If the page is full, there may be more results.
+        if values.len() as i64 == page.limit {
+            links.push(Self::link(request, page, page.offset + page.limit, "next"));
+        }
+
+        let mut response = Response::build_from(Json(values).respond_to(request)?);
+        if !links.is_empty() {
+            response.raw_header("Link", links.join(", "));
+        }
+        response.ok()
+    }
+}
+
+/// The version a client expects a value to be at,
+/// as provided in the `If-Match` header.
+///
+/// If the header is not present (or is `*`),
+/// any version is acceptable.
+#[derive(Debug, Clone, Copy)]
+pub struct IfMatch(pub Option<i32>);
+
+impl IfMatch {
+    /// Does the provided version satisfy the precondition?
+    pub fn matches(self, version: i32) -> bool {
+        self.0.map_or(true, |expected| expected == version)
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfMatch {
+    type Error = &'a str;
+
+    /// Parse the `If-Match` header, if present.
+    /// Both strong (`"3"`) and weak (`W/"3"`) entity tags are accepted.
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let header = match request.headers().get_one("If-Match") {
+            Some(header) => header.trim(),
+            None => return Outcome::Success(IfMatch(None)),
+        };
+
+        if header == "*" {
+            return Outcome::Success(IfMatch(None));
+        }
+
+        let tag = header.trim_start_matches("W/").trim_matches('"');
+        match tag.parse() {
+            Ok(version) => Outcome::Success(IfMatch(Some(version))),
+            Err(_) => Outcome::Failure((
+                Status::BadRequest,
+                r#""If-Match" header must contain a single entity tag"#,
+            )),
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! json_result {
     ($x:expr) => {
@@ -18,6 +150,26 @@ macro_rules! json_result {
     };
 }
 
+#[macro_export]
+macro_rules! etag_result {
+    ($x:expr) => {
+        $x.map(|value| {
+            crate::endpoint::helpers::ETagged(value.version, rocket_contrib::json::Json(value))
+        })
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))
+    };
+}
+
+#[macro_export]
+macro_rules! versioned_result {
+    ($x:expr) => {
+        $x.map(|value| {
+            crate::endpoint::helpers::ETagged(value.version, rocket_contrib::json::Json(value))
+        })
+        .map_err(|e| crate::endpoint::helpers::precondition_mapper(&e))
+    };
+}
+
 #[macro_export]
 macro_rules! no_content {
     ($x:expr) => {
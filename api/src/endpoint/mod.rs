@@ -6,6 +6,8 @@ mod generics;
 pub mod event;
 pub mod meta;
 pub mod oauth;
+pub mod preset;
 pub mod section;
+pub mod template;
 pub mod thread;
 pub mod user;
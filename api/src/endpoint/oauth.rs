@@ -1,7 +1,7 @@
 #[cfg(test)]
 use crate::guid;
 use crate::{
-    controller::{Claim, InsertUser, User},
+    controller::{Claim, InsertUser, Repository, User},
     encryption::encrypt,
     DataDB,
 };
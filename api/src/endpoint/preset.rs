@@ -0,0 +1,162 @@
+use crate::{
+    controller::{
+        Event,
+        ExternalInsertPresetEvent,
+        InsertEvent,
+        PresetEvent,
+        PresetEventFilter,
+        PresetEventSortKey,
+        Repository,
+        Thread,
+        UpdatePresetEvent,
+        User,
+    },
+    endpoint::helpers::{ETagged, IfMatch, RocketResult},
+    DataDB,
+};
+use chrono::Utc;
+use rocket::{delete, http::Status, patch, post, response::status::Created};
+use rocket_contrib::json::Json;
+use serde::Deserialize;
+
+generic_all!(PresetEvent, PresetEventFilter, PresetEventSortKey, private);
+generic_get!(PresetEvent, versioned, private);
+
+/// Create a `PresetEvent`.
+///
+/// Only hosts of a subreddit (and global admins) can create presets belonging to it.
+#[post("/", data = "<data>")]
+pub fn post(
+    conn: DataDB,
+    user: User,
+    data: Json<ExternalInsertPresetEvent>,
+) -> RocketResult<Created<Json<PresetEvent>>> {
+    if let Some(subreddit) = &data.subreddit {
+        if !user.is_global_admin && !user.is_host_for(Some(subreddit)) {
+            return Err(Status::Unauthorized);
+        }
+    }
+
+    if !data.cols.is_array() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    created!(PresetEvent::create(&conn, &data, user.id))
+}
+
+/// Update a `PresetEvent`.
+///
+/// If the `If-Match` header is present,
+/// the update is only performed if the preset's version matches.
+#[patch("/<id>", data = "<data>")]
+pub fn patch(
+    conn: DataDB,
+    user: User,
+    if_match: IfMatch,
+    id: i32,
+    data: Json<UpdatePresetEvent>,
+) -> RocketResult<ETagged<Json<PresetEvent>>> {
+    let preset = match PresetEvent::find_id(&conn, id) {
+        Ok(preset) if preset.is_visible_to(Some(&user)) => preset,
+        _ => return Err(Status::NotFound),
+    };
+
+    if !user.can_modify_preset(&preset) {
+        return Err(Status::Unauthorized);
+    }
+
+    if !if_match.matches(preset.version) {
+        return Err(Status::PreconditionFailed);
+    }
+
+    if data.cols.as_ref().map_or(false, |cols| !cols.is_array()) {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    versioned_result!(PresetEvent::update_if_version(&conn, id, if_match.0, &data))
+}
+
+/// Delete a `PresetEvent`.
+/// Events previously created from the preset are unaffected.
+#[delete("/<id>")]
+pub fn delete(conn: DataDB, user: User, id: i32) -> RocketResult<Status> {
+    let preset = match PresetEvent::find_id(&conn, id) {
+        Ok(preset) if preset.is_visible_to(Some(&user)) => preset,
+        _ => return Err(Status::NotFound),
+    };
+
+    if !user.can_modify_preset(&preset) {
+        return Err(Status::Unauthorized);
+    }
+
+    no_content!(PresetEvent::delete(&conn, id))
+}
+
+/// The thread to create an `Event` in from a `PresetEvent`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstantiatePreset {
+    pub in_thread_id: i32,
+    #[serde(default)]
+    pub posted:       bool,
+}
+
+/// Create an `Event` from a `PresetEvent`,
+/// substituting any placeholders with their current values.
+///
+/// The resulting columns must conform to the thread's schema.
+#[post("/<id>/instantiate", data = "<data>")]
+pub fn instantiate(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    data: Json<InstantiatePreset>,
+) -> RocketResult<Created<Json<Event>>> {
+    let preset = match PresetEvent::find_id(&conn, id) {
+        Ok(preset) if preset.is_visible_to(Some(&user)) => preset,
+        _ => return Err(Status::NotFound),
+    };
+
+    if !user.can_modify_thread(&conn, data.in_thread_id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let thread = Thread::find_id(&conn, data.in_thread_id).expect("thread not found");
+
+    if !user.is_global_admin && !preset.is_available_to(&thread, user.id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let cols = match preset
+        .instantiate(&thread, Utc::now().timestamp())
+        .map(|cols| thread.event_columns.validate(&cols))
+    {
+        Ok(Ok(cols)) => cols,
+        _ => return Err(Status::UnprocessableEntity),
+    };
+
+    let ret_val = Event::create(
+        &conn,
+        &InsertEvent {
+            posted: data.posted,
+            in_thread_id: data.in_thread_id,
+            cols,
+            post_at: None,
+        },
+    )
+    .map(|event| {
+        Created(
+            rocket::uri!(crate::endpoint::event::get: event.id).to_string(),
+            Some(Json(event)),
+        )
+    })
+    .map_err(|e| crate::endpoint::helpers::error_mapper(&e));
+
+    // The thread now includes the event, so must be fetched again.
+    Thread::find_id(&conn, thread.id)
+        .expect("thread not found")
+        .update_on_reddit(&conn)
+        .expect("error updating on Reddit");
+
+    ret_val
+}
@@ -1,18 +1,30 @@
 use crate::{
     controller::{
+        section_kind,
+        section_kinds,
         ExternalLockSection,
         InsertSection,
         LockSection,
+        MarkdownKind,
+        Repository,
         Section,
+        SectionFilter,
+        SectionKindInfo,
+        SectionSortKey,
+        StatsChange,
+        StatsError,
+        StatsKind,
+        StatsTable,
         Thread,
         UpdateSection,
         User,
     },
-    endpoint::helpers::RocketResult,
+    endpoint::helpers::{ETagged, IfMatch, RocketResult},
     DataDB,
 };
-use rocket::{delete, http::Status, patch, post, response::status::Created};
+use rocket::{delete, get, http::Status, patch, post, response::status::Created};
 use rocket_contrib::json::Json;
+use serde_json::json;
 use std::{
     convert::TryFrom,
     time::{SystemTime, UNIX_EPOCH},
@@ -24,8 +36,8 @@ use std::{
 /// although this is an implementation detail and should not be relied upon.
 const LOCK_DURATION_SECONDS: i64 = 10 * 60;
 
-generic_all!(Section);
-generic_get!(Section);
+generic_all!(Section, SectionFilter, SectionSortKey, private);
+generic_get!(Section, versioned, in_thread);
 
 /// Create a `Section`.
 #[post("/", data = "<data>")]
@@ -38,6 +50,14 @@ pub fn post(
         return Err(Status::Unauthorized);
     }
 
+    // Ensure the data is valid for the kind of section.
+    if section_kind(&data.kind)
+        .and_then(|kind| kind.check(&data.data))
+        .is_err()
+    {
+        return Err(Status::UnprocessableEntity);
+    }
+
     let ret_val = created!(Section::create(&conn, &data));
 
     Thread::find_id(&conn, data.in_thread_id)
@@ -62,18 +82,22 @@ pub enum UpdateSectionDiscriminant {
 
 /// Discriminate between the two types,
 /// calling the appropriate method as necessary.
+///
+/// If the `If-Match` header is present,
+/// the update is only performed if the section's version matches.
 #[patch("/<id>", data = "<data>")]
 pub fn patch(
     conn: DataDB,
     user: User,
+    if_match: IfMatch,
     id: i32,
     data: Json<UpdateSectionDiscriminant>,
-) -> RocketResult<Json<Section>> {
+) -> RocketResult<ETagged<Json<Section>>> {
     use UpdateSectionDiscriminant::{LockSection, UpdateSection};
 
     match data.into_inner() {
-        LockSection(data) => set_lock(conn, user, id, data),
-        UpdateSection(data) => update_fields(conn, user, id, data),
+        LockSection(data) => set_lock(conn, user, if_match, id, data),
+        UpdateSection(data) => update_fields(conn, user, if_match, id, data),
     }
 }
 
@@ -82,9 +106,10 @@ pub fn patch(
 fn set_lock(
     conn: DataDB,
     user: User,
+    if_match: IfMatch,
     id: i32,
     data: ExternalLockSection,
-) -> RocketResult<Json<Section>> {
+) -> RocketResult<ETagged<Json<Section>>> {
     let section = match Section::find_id(&conn, id) {
         Ok(section) => section,
         Err(_) => return Err(Status::NotFound),
@@ -95,6 +120,10 @@ fn set_lock(
         return Err(Status::Unauthorized);
     }
 
+    if !if_match.matches(section.version) {
+        return Err(Status::PreconditionFailed);
+    }
+
     let current_unix_timestamp = i64::try_from(
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -114,9 +143,10 @@ fn set_lock(
             && data.lock_held_by_user_id == Some(user.id))
         || (section.lock_assigned_at_utc + LOCK_DURATION_SECONDS <= current_unix_timestamp)
     {
-        json_result!(Section::set_lock(
+        versioned_result!(Section::set_lock(
             &conn,
             id,
+            if_match.0,
             &LockSection {
                 lock_held_by_user_id: data.lock_held_by_user_id,
                 lock_assigned_at_utc: current_unix_timestamp,
@@ -133,9 +163,10 @@ fn set_lock(
 fn update_fields(
     conn: DataDB,
     user: User,
+    if_match: IfMatch,
     id: i32,
     data: UpdateSection,
-) -> RocketResult<Json<Section>> {
+) -> RocketResult<ETagged<Json<Section>>> {
     let section = match Section::find_id(&conn, id) {
         Ok(section) => section,
         Err(_) => return Err(Status::NotFound),
@@ -145,14 +176,133 @@ fn update_fields(
         return Err(Status::Unauthorized);
     }
 
-    let ret_val = json_result!(Section::update(&conn, id, &data));
+    if !if_match.matches(section.version) {
+        return Err(Status::PreconditionFailed);
+    }
+
+    // Ensure the data remains valid for the (possibly new) kind of section.
+    if data.kind.is_some() || data.data.is_some() {
+        let kind = data.kind.as_ref().unwrap_or(&section.kind);
+        let kind_data = data.data.as_ref().unwrap_or(&section.data);
+
+        if section_kind(kind)
+            .and_then(|kind| kind.check(kind_data))
+            .is_err()
+        {
+            return Err(Status::UnprocessableEntity);
+        }
+    }
+
+    let ret_val = versioned_result!(Section::update_if_version(&conn, id, if_match.0, &data))?;
 
     Thread::find_id(&conn, section.in_thread_id)
         .expect("thread not found")
         .update_on_reddit(&conn)
         .expect("error updating on Reddit");
 
-    ret_val
+    Ok(ret_val)
+}
+
+/// Update individual rows of a `Section`'s stats table, in order.
+/// Each change is a key and its new value, with `null` removing the row.
+///
+/// If the `If-Match` header is present,
+/// the update is only performed if the section's version matches.
+#[patch("/<id>/stats", data = "<data>")]
+pub fn patch_stats(
+    conn: DataDB,
+    user: User,
+    if_match: IfMatch,
+    id: i32,
+    data: Json<Vec<StatsChange>>,
+) -> RocketResult<ETagged<Json<Section>>> {
+    change_stats(conn, user, if_match, id, |_, section| {
+        let mut stats = section.stats().ok_or(StatsError::NotStats)?;
+        stats.apply(&data)?;
+        Ok(stats)
+    })
+}
+
+/// Fill a `Section`'s stats table from the launch data of its `Thread`,
+/// overwriting the value of any rows already present.
+/// A markdown section becomes a stats section, keeping its content.
+#[post("/<id>/stats/fill")]
+pub fn fill_stats(conn: DataDB, user: User, id: i32) -> RocketResult<ETagged<Json<Section>>> {
+    change_stats(conn, user, IfMatch(None), id, |thread, section| {
+        let stats = match section.kind.as_str() {
+            MarkdownKind::NAME => None,
+            StatsKind::NAME => section.stats(),
+            _ => return Err(StatsError::NotStats),
+        };
+        let launch = thread
+            .launch_sync
+            .launch
+            .as_ref()
+            .ok_or(StatsError::NoLaunch)?;
+
+        let mut stats = stats.unwrap_or_default();
+        for row in launch.stats().rows {
+            stats.set(&row.key, row.value);
+        }
+
+        Ok(stats)
+    })
+}
+
+/// Change the stats table of a `Section`, given a function returning the new table.
+///
+/// The section's thread is updated on Reddit afterwards.
+fn change_stats(
+    conn: DataDB,
+    user: User,
+    if_match: IfMatch,
+    id: i32,
+    change: impl FnOnce(&Thread, &Section) -> Result<StatsTable, StatsError>,
+) -> RocketResult<ETagged<Json<Section>>> {
+    let section = match Section::find_id(&conn, id) {
+        Ok(section) => section,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    if !user.can_modify_thread(&conn, section.in_thread_id) {
+        return Err(Status::Unauthorized);
+    }
+
+    if !if_match.matches(section.version) {
+        return Err(Status::PreconditionFailed);
+    }
+
+    let thread = match Thread::find_id(&conn, section.in_thread_id) {
+        Ok(thread) => thread,
+        Err(_) => return Err(Status::NotFound),
+    };
+    let stats = match change(&thread, &section) {
+        Ok(stats) => stats,
+        Err(_) => return Err(Status::UnprocessableEntity),
+    };
+
+    let ret_val = versioned_result!(Section::update_if_version(
+        &conn,
+        id,
+        if_match.0,
+        &UpdateSection {
+            kind: Some(StatsKind::NAME.into()),
+            data: Some(json!(stats)),
+            ..UpdateSection::default()
+        }
+    ))?;
+
+    thread
+        .update_on_reddit(&conn)
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    Ok(ret_val)
+}
+
+/// List every kind of `Section`, with a description and an example of its `data`.
+#[get("/kinds")]
+pub fn kinds() -> Json<Vec<SectionKindInfo>> {
+    Json(section_kinds())
 }
 
 /// Delete a `Section` and any references to its ID.
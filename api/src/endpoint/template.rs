@@ -0,0 +1,118 @@
+use crate::{
+    controller::{
+        EventColumns,
+        ExternalInsertThreadTemplate,
+        Repository,
+        TemplateSections,
+        ThreadTemplate,
+        ThreadTemplateFilter,
+        ThreadTemplateSortKey,
+        TimeFormat,
+        UpdateThreadTemplate,
+        User,
+    },
+    endpoint::helpers::{ETagged, IfMatch, RocketResult},
+    DataDB,
+};
+use rocket::{delete, http::Status, patch, post, response::status::Created};
+use rocket_contrib::json::Json;
+
+generic_all!(
+    ThreadTemplate,
+    ThreadTemplateFilter,
+    ThreadTemplateSortKey,
+    private
+);
+generic_get!(ThreadTemplate, versioned, private);
+
+/// Are the event columns, time format, and sections of a template (where provided) valid?
+fn is_valid(
+    event_columns: Option<&EventColumns>,
+    time_format: Option<&TimeFormat>,
+    sections: Option<&TemplateSections>,
+) -> bool {
+    event_columns.map_or(true, |event_columns| event_columns.check().is_ok())
+        && time_format.map_or(true, |time_format| time_format.check().is_ok())
+        && sections.map_or(true, |sections| sections.check().is_ok())
+}
+
+/// Create a `ThreadTemplate`.
+///
+/// Only hosts of a subreddit (and global admins) can create templates belonging to it.
+#[post("/", data = "<data>")]
+pub fn post(
+    conn: DataDB,
+    user: User,
+    data: Json<ExternalInsertThreadTemplate>,
+) -> RocketResult<Created<Json<ThreadTemplate>>> {
+    if let Some(subreddit) = &data.subreddit {
+        if !user.is_global_admin && !user.is_host_for(Some(subreddit)) {
+            return Err(Status::Unauthorized);
+        }
+    }
+
+    if !is_valid(
+        Some(&data.event_columns),
+        Some(&data.time_format),
+        Some(&data.sections),
+    ) {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    created!(ThreadTemplate::create(&conn, &data, user.id))
+}
+
+/// Update a `ThreadTemplate`.
+/// Threads previously created from the template are unaffected.
+///
+/// If the `If-Match` header is present,
+/// the update is only performed if the template's version matches.
+#[patch("/<id>", data = "<data>")]
+pub fn patch(
+    conn: DataDB,
+    user: User,
+    if_match: IfMatch,
+    id: i32,
+    data: Json<UpdateThreadTemplate>,
+) -> RocketResult<ETagged<Json<ThreadTemplate>>> {
+    let template = match ThreadTemplate::find_id(&conn, id) {
+        Ok(template) if template.is_visible_to(Some(&user)) => template,
+        _ => return Err(Status::NotFound),
+    };
+
+    if !user.can_modify_template(&template) {
+        return Err(Status::Unauthorized);
+    }
+
+    if !if_match.matches(template.version) {
+        return Err(Status::PreconditionFailed);
+    }
+
+    if !is_valid(
+        data.event_columns.as_ref(),
+        data.time_format.as_ref(),
+        data.sections.as_ref(),
+    ) {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    versioned_result!(ThreadTemplate::update_if_version(
+        &conn, id, if_match.0, &data
+    ))
+}
+
+/// Delete a `ThreadTemplate`.
+/// Threads previously created from the template are unaffected.
+#[delete("/<id>")]
+pub fn delete(conn: DataDB, user: User, id: i32) -> RocketResult<Status> {
+    let template = match ThreadTemplate::find_id(&conn, id) {
+        Ok(template) if template.is_visible_to(Some(&user)) => template,
+        _ => return Err(Status::NotFound),
+    };
+
+    if !user.can_modify_template(&template) {
+        return Err(Status::Unauthorized);
+    }
+
+    no_content!(ThreadTemplate::delete(&conn, id))
+}
@@ -1,63 +1,285 @@
 use crate::{
-    controller::{ExternalInsertThread, Thread, UpdateThread, User},
-    endpoint::helpers::RocketResult,
+    controller::{
+        events_from_csv,
+        events_from_json,
+        events_to_csv,
+        prepare_import,
+        Clock,
+        ClockError,
+        ClockStatus,
+        CloneRequest,
+        Event,
+        ExternalInsertThread,
+        ImportError,
+        LaunchError,
+        Preview,
+        PreviewError,
+        PreviewRequest,
+        RenderFormat,
+        Repository,
+        ScrubError,
+        ScrubRequest,
+        Thread,
+        ThreadBundle,
+        ThreadFilter,
+        ThreadSortKey,
+        ThreadState,
+        ThreadTemplate,
+        TransferredEvent,
+        TransitionError,
+        TransitionRequest,
+        UpdateThread,
+        User,
+        Webhooks,
+    },
+    endpoint::helpers::{ETagged, IfMatch, RocketResult},
     DataDB,
+    Services,
+};
+use chrono::Utc;
+use rocket::{
+    delete,
+    get,
+    http::{ContentType, Status},
+    patch,
+    post,
+    put,
+    response::{
+        content::Content,
+        status::{Created, Custom},
+    },
+    Data,
+    State,
 };
-use rocket::{delete, get, http::Status, patch, post, response::status::Created};
 use rocket_contrib::json::{Json, JsonValue};
-use std::collections::BTreeSet;
+use serde::Deserialize;
+use std::{collections::BTreeSet, io::Read};
+
+/// The maximum size of an import, in bytes.
+const IMPORT_LIMIT: u64 = 1024 * 1024;
 
-generic_all!(Thread);
-generic_get!(Thread);
+generic_all!(Thread, ThreadFilter, ThreadSortKey, private);
+generic_get!(Thread, versioned, private);
+
+/// Find a `Thread` that is visible to the provided user (if any).
+/// Drafts that are not visible to the user are not found.
+fn find_visible(conn: &DataDB, id: i32, user: Option<&User>) -> RocketResult<Thread> {
+    match Thread::find_id(conn, id) {
+        Ok(thread) if thread.is_visible_to(user) => Ok(thread),
+        Ok(_) => Err(Status::NotFound),
+        Err(e) => Err(crate::endpoint::helpers::error_mapper(&e)),
+    }
+}
 
 /// Get the `Thread` along with its `Section`s, `Event`s, author, and section locks.
 #[get("/<id>/full")]
-pub fn get_full(conn: DataDB, id: i32) -> RocketResult<JsonValue> {
+pub fn get_full(conn: DataDB, user: Option<User>, id: i32) -> RocketResult<JsonValue> {
+    find_visible(&conn, id, user.as_ref())?;
+
     Ok(Thread::find_id_with_foreign_keys(&conn, id)
         .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?
         .into())
 }
 
 /// Create a `Thread`.
+///
+/// If a template is provided, anything not otherwise provided is taken from it,
+/// and the thread is created along with the template's sections.
 #[post("/", data = "<data>")]
 pub fn post(
     conn: DataDB,
     user: User,
+    services: State<'_, Services>,
     data: Json<ExternalInsertThread>,
 ) -> RocketResult<Created<Json<Thread>>> {
-    let user_id = user.id;
-    let subreddit = &data.subreddit;
-    let mut post_id = None;
+    let mut data = data.into_inner();
 
-    if let Some(subreddit) = subreddit {
-        let mut user: reddit::User<'_> = user.into();
-        post_id = Some(
-            user.submit_self_post(subreddit, &data.thread_name, None)
-                .expect("error posting to Reddit"),
-        );
-        User::update_access_token_if_necessary(&conn, user_id, &mut user)
-            .expect("could not update access token");
+    // Ensure the schema of the thread's events and the format of its timestamps are valid.
+    // Those from a template were checked when it was saved.
+    if data
+        .event_columns
+        .as_ref()
+        .map_or(false, |event_columns| event_columns.check().is_err())
+        || data
+            .time_format
+            .as_ref()
+            .map_or(false, |time_format| time_format.check().is_err())
+    {
+        return Err(Status::UnprocessableEntity);
     }
 
-    created!(Thread::create(&conn, &data, user_id, post_id))
+    let state = data.state().ok_or(Status::UnprocessableEntity)?;
+
+    let template = match data.template_id {
+        Some(template_id) => {
+            let template = ThreadTemplate::find_id(&conn, template_id)
+                .map_err(|_| Status::UnprocessableEntity)?;
+            let subreddit = data.subreddit.as_ref().map(String::as_str);
+            if !template.is_available_to(subreddit, user.id) {
+                return Err(Status::Unauthorized);
+            }
+            Some(template)
+        }
+        None => None,
+    };
+
+    // Fill in anything not provided from the launch data, if available.
+    let launch = match (&data.spacex__api_id, &services.launch_provider) {
+        (Some(api_id), Some(provider)) => Some(provider.launch(api_id).map_err(launch_mapper)?),
+        _ => None,
+    };
+    if let Some(template) = &template {
+        let name = if data.display_name.is_empty() {
+            launch.as_ref().map(|launch| launch.name.clone())
+        } else {
+            Some(data.display_name.clone())
+        };
+        data.apply_template(template, name.as_ref().map(String::as_str));
+    }
+    if let Some(launch) = &launch {
+        data.prefill(launch);
+    }
+    if data.thread_name.is_empty() || data.display_name.is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    // Drafts and scheduled threads are posted later, by changing their state.
+    // Threads posted to Reddit now are created as drafts and then published,
+    // so that they are submitted complete with their content.
+    let publish = match state {
+        ThreadState::Posted | ThreadState::Live => data.subreddit.is_some(),
+        _ => false,
+    };
+    let initial_state = if publish { ThreadState::Draft } else { state };
+
+    // The thread and any sections from the launch data or template are created atomically.
+    let thread = match (launch, &template) {
+        (Some(launch), _) => Thread::create_with_launch(
+            &conn,
+            &data.insertable(user.id, initial_state),
+            template
+                .as_ref()
+                .map_or(&[][..], |template| &template.sections[..]),
+            launch,
+            Utc::now().timestamp(),
+        ),
+        (None, Some(template)) => Thread::create_with_contents(
+            &conn,
+            &data.insertable(user.id, initial_state),
+            &template.sections,
+            &[],
+        ),
+        (None, None) => Thread::create(&conn, &data, user.id, initial_state),
+    }
+    .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    let thread = if publish {
+        publish_new(&conn, &services, thread, state)?
+    } else {
+        thread
+    };
+
+    Ok(Created(
+        rocket::uri!(get: thread.id).to_string(),
+        Some(Json(thread)),
+    ))
+}
+
+/// Create a copy of a `Thread`, owned by the current user.
+///
+/// The new thread is only posted to Reddit if a subreddit is provided.
+#[post("/<id>/clone", data = "<data>")]
+pub fn clone(
+    conn: DataDB,
+    services: State<'_, Services>,
+    user: User,
+    id: i32,
+    data: Json<CloneRequest>,
+) -> RocketResult<Created<Json<Thread>>> {
+    if data.thread_name.is_empty()
+        || data
+            .display_name
+            .as_ref()
+            .map_or(false, |display_name| display_name.is_empty())
+    {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    find_visible(&conn, id, Some(&user))?;
+
+    let thread = Thread::duplicate(&conn, id, &data, user.id)
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    let thread = if data.subreddit.is_some() {
+        publish_new(&conn, &services, thread, ThreadState::Posted)?
+    } else {
+        thread
+    };
+
+    Ok(Created(
+        rocket::uri!(get: thread.id).to_string(),
+        Some(Json(thread)),
+    ))
+}
+
+/// Post a newly created draft to Reddit, complete with its content,
+/// and move it to the provided state (`posted` or `live`).
+///
+/// Should it not be posted, the draft is removed,
+/// as the request to create it has failed.
+fn publish_new(
+    conn: &DataDB,
+    services: &Services,
+    thread: Thread,
+    to: ThreadState,
+) -> RocketResult<Thread> {
+    let now = Utc::now().timestamp();
+
+    let thread = match Thread::publish(conn, &*services.publisher, thread.id, None, now) {
+        Ok(thread) => thread,
+        Err(e) => {
+            let _ = Thread::delete_with_contents(conn, thread.id);
+            return Err(transition_mapper(e));
+        }
+    };
+
+    if to == ThreadState::Posted {
+        return Ok(thread);
+    }
+
+    let request = TransitionRequest {
+        state:        to,
+        scheduled_at: None,
+    };
+    Thread::transition(conn, &*services.publisher, thread.id, &request, now)
+        .map_err(transition_mapper)
 }
 
 /// Update a `Thread`.
+///
+/// If the `If-Match` header is present,
+/// the update is only performed if the thread's version matches.
 #[patch("/<id>", data = "<data>")]
 pub fn patch(
     conn: DataDB,
     user: User,
+    if_match: IfMatch,
     id: i32,
     data: Json<UpdateThread>,
-) -> RocketResult<Json<Thread>> {
+) -> RocketResult<ETagged<Json<Thread>>> {
     if !user.can_modify_thread(&conn, id) {
         return Err(Status::Unauthorized);
     }
 
+    let current_thread =
+        Thread::find_id(&conn, id).map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    if !if_match.matches(current_thread.version) {
+        return Err(Status::PreconditionFailed);
+    }
+
     // Restrict changing `.sections_id` to reordering, not adding or removing.
     if data.sections_id.is_some() {
-        let current_thread = Thread::find_id(&conn, id).unwrap();
-
         let current_sections: BTreeSet<_> = current_thread.sections_id.iter().collect();
         let proposed_sections: BTreeSet<_> = data.sections_id.as_ref().unwrap().iter().collect();
 
@@ -68,8 +290,6 @@ pub fn patch(
 
     // Restrict changing `.events_id` to reordering, not adding or removing.
     if data.events_id.is_some() {
-        let current_thread = Thread::find_id(&conn, id).unwrap();
-
         let current_events: BTreeSet<_> = current_thread.events_id.iter().collect();
         let proposed_events: BTreeSet<_> = data.events_id.as_ref().unwrap().iter().collect();
 
@@ -78,12 +298,45 @@ pub fn patch(
         }
     }
 
-    Thread::find_id(&conn, id)
-        .unwrap()
+    // The clock can only be changed by holding and resuming,
+    // the launch data only by refreshing it,
+    // and the webhooks only on their own (so as to not broadcast them).
+    if data.clock.is_some() || data.launch_sync.is_some() || data.webhooks.is_some() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    if let Some(time_format) = &data.time_format {
+        if time_format.check().is_err() {
+            return Err(Status::UnprocessableEntity);
+        }
+    }
+
+    // Ensure the new schema is valid, and that all existing events conform to it.
+    if let Some(event_columns) = &data.event_columns {
+        if event_columns.check().is_err() {
+            return Err(Status::UnprocessableEntity);
+        }
+
+        let events = Event::find_ids(&conn, &current_thread.events_id)
+            .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+        if events
+            .values
+            .iter()
+            .any(|event| event_columns.validate(&event.cols).is_err())
+        {
+            return Err(Status::UnprocessableEntity);
+        }
+    }
+
+    let thread = Thread::update_if_version(&conn, id, if_match.0, &data)
+        .map_err(|e| crate::endpoint::helpers::precondition_mapper(&e))?;
+
+    // The post on Reddit reflects the thread as updated.
+    thread
         .update_on_reddit(&conn)
-        .unwrap();
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
 
-    json_result!(Thread::update(&conn, id, &data))
+    Ok(ETagged(thread.version, Json(thread)))
 }
 
 /// Approve a `Thread` on Reddit.
@@ -168,3 +421,469 @@ pub fn delete(conn: DataDB, user: User, id: i32) -> RocketResult<Status> {
         Err(Status::Unauthorized)
     }
 }
+
+/// Import a number of `Event`s into a `Thread`,
+/// either as CSV (`text/csv`) or as a JSON array.
+///
+/// Every row is validated against the thread's schema before anything is inserted,
+/// and all events are inserted in a single transaction.
+#[post("/<id>/events/import", data = "<data>")]
+pub fn import_events(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    content_type: Option<&ContentType>,
+    data: Data,
+) -> RocketResult<Custom<Json<Vec<Event>>>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let thread =
+        Thread::find_id(&conn, id).map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    // Read one byte past the limit, so that an oversized import is rejected
+    // rather than silently truncated.
+    let mut input = String::new();
+    data.open()
+        .take(IMPORT_LIMIT + 1)
+        .read_to_string(&mut input)
+        .map_err(|_| Status::BadRequest)?;
+    if input.len() as u64 > IMPORT_LIMIT {
+        return Err(Status::PayloadTooLarge);
+    }
+
+    let events = if content_type.map_or(false, ContentType::is_csv) {
+        events_from_csv(&thread.event_columns, &input)
+    } else {
+        events_from_json(&input)
+    };
+
+    let data = match events.and_then(|events| prepare_import(&thread.event_columns, id, events)) {
+        Ok(data) => data,
+        Err(_) => return Err(Status::UnprocessableEntity),
+    };
+
+    let events = Event::create_many(&conn, id, &data)
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    if !events.is_empty() {
+        Thread::find_id(&conn, id)
+            .and_then(|thread| thread.update_on_reddit(&conn))
+            .expect("error updating on Reddit");
+    }
+
+    Ok(Custom(Status::Created, Json(events)))
+}
+
+/// Export all `Event`s of a `Thread` in order,
+/// either as CSV (`?format=csv`) or as a JSON array (the default).
+///
+/// The result can be imported without any changes.
+#[get("/<id>/events/export?<format>")]
+pub fn export_events(
+    conn: DataDB,
+    user: Option<User>,
+    id: i32,
+    format: Option<String>,
+) -> RocketResult<Content<String>> {
+    let thread = find_visible(&conn, id, user.as_ref())?;
+    let events = Event::find_ids(&conn, &thread.events_id)
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?
+        .values;
+
+    match format.as_ref().map(String::as_str) {
+        None | Some("json") => {
+            let events: Vec<_> = events.iter().map(TransferredEvent::from).collect();
+            serde_json::to_string(&events)
+                .map(|body| Content(ContentType::JSON, body))
+                .map_err(|_| Status::InternalServerError)
+        }
+        Some("csv") => events_to_csv(&thread.event_columns, &events)
+            .map(|body| Content(ContentType::CSV, body))
+            .map_err(|_| Status::InternalServerError),
+        Some(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Export a `Thread` as a self-contained record, independent of Reddit.
+///
+/// By default, this is a JSON bundle of the thread along with its sections, events,
+/// authors, and the history of its countdown, which can be restored with `import`.
+/// With `?format=markdown` or `?format=html`,
+/// the thread is instead rendered as a standalone document.
+#[get("/<id>/export?<format>")]
+pub fn export(
+    conn: DataDB,
+    user: Option<User>,
+    id: i32,
+    format: Option<String>,
+) -> RocketResult<Content<String>> {
+    let thread = find_visible(&conn, id, user.as_ref())?;
+
+    match format.as_ref().map(String::as_str) {
+        None | Some("json") => {
+            let bundle = Thread::export(&conn, id, Utc::now().timestamp())
+                .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+            serde_json::to_string(&bundle)
+                .map(|body| Content(ContentType::JSON, body))
+                .map_err(|_| Status::InternalServerError)
+        }
+        Some(format) => {
+            let format: RenderFormat = format.parse().map_err(|_| Status::BadRequest)?;
+            thread
+                .export_document(&conn, format)
+                .map(|body| Content(format.content_type(), body))
+                .map_err(|_| Status::InternalServerError)
+        }
+    }
+}
+
+/// Render a `Thread`, including all its sections,
+/// either as markdown (`?format=markdown`, the default) or as sanitized HTML (`?format=html`).
+///
+/// The markdown is exactly that posted to Reddit,
+/// while the HTML is suitable for embedding in a website.
+#[get("/<id>/render?<format>")]
+pub fn render(
+    conn: DataDB,
+    user: Option<User>,
+    id: i32,
+    format: Option<String>,
+) -> RocketResult<Content<String>> {
+    let format = match format {
+        Some(format) => format.parse().map_err(|_| Status::BadRequest)?,
+        None => RenderFormat::Markdown,
+    };
+
+    find_visible(&conn, id, user.as_ref())?;
+
+    Thread::render(&conn, id, format)
+        .map(|body| Content(format.content_type(), body))
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// Restore an exported bundle into a new `Thread`, owned by the current user.
+///
+/// The new thread is not posted to Reddit.
+#[post("/import", data = "<data>")]
+pub fn import(
+    conn: DataDB,
+    user: User,
+    data: Json<ThreadBundle>,
+) -> RocketResult<Created<Json<Thread>>> {
+    match Thread::import(&conn, &data, user.id) {
+        Ok(thread) => Ok(Created(
+            rocket::uri!(get: thread.id).to_string(),
+            Some(Json(thread)),
+        )),
+        Err(ImportError::Version(_)) | Err(ImportError::Invalid(_)) => {
+            Err(Status::UnprocessableEntity)
+        }
+        Err(ImportError::Database(e)) => Err(crate::endpoint::helpers::error_mapper(&e)),
+    }
+}
+
+/// Get the state of a `Thread`'s countdown, including the current T-time.
+#[get("/<id>/clock")]
+pub fn clock(conn: DataDB, user: Option<User>, id: i32) -> RocketResult<Json<ClockStatus>> {
+    let thread = find_visible(&conn, id, user.as_ref())?;
+
+    Ok(Json(
+        thread
+            .clock
+            .status(thread.space__t0, Utc::now().timestamp()),
+    ))
+}
+
+/// How to resume a `Thread`'s countdown.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResumeClock {
+    /// The T-time to resume from, if the countdown was recycled.
+    #[serde(default)]
+    pub recycled_to: Option<i64>,
+}
+
+/// Hold a `Thread`'s countdown at the current T-time.
+#[post("/<id>/clock/hold")]
+pub fn hold(conn: DataDB, user: User, id: i32) -> RocketResult<Json<ClockStatus>> {
+    change_clock(conn, user, id, |clock, t0, now| {
+        clock.hold(t0, now).map(|()| t0)
+    })
+}
+
+/// Resume a `Thread`'s countdown,
+/// either from where it was held or from the T-time it was recycled to.
+///
+/// T-0 is moved accordingly.
+#[post("/<id>/clock/resume", data = "<data>")]
+pub fn resume(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    data: Json<ResumeClock>,
+) -> RocketResult<Json<ClockStatus>> {
+    change_clock(conn, user, id, |clock, t0, now| {
+        clock.resume(t0, now, data.recycled_to).map(Some)
+    })
+}
+
+/// Change the clock of a `Thread`, given a function returning the new T-0.
+///
+/// Clients in the thread's room are informed of the change,
+/// and the thread is updated on Reddit if T-0 changes.
+fn change_clock(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    change: impl FnOnce(&mut Clock, Option<i64>, i64) -> Result<Option<i64>, ClockError>,
+) -> RocketResult<Json<ClockStatus>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let thread =
+        Thread::find_id(&conn, id).map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+    let now = Utc::now().timestamp();
+
+    let mut clock = thread.clock.clone();
+    let t0 = match change(&mut clock, thread.space__t0, now) {
+        Ok(t0) => t0,
+        Err(_) => return Err(Status::UnprocessableEntity),
+    };
+
+    let thread = Thread::update_if_version(
+        &conn,
+        id,
+        Some(thread.version),
+        &UpdateThread {
+            clock: Some(clock),
+            space__t0: if t0 == thread.space__t0 {
+                None
+            } else {
+                Some(t0)
+            },
+            ..UpdateThread::default()
+        },
+    )
+    .map_err(|e| crate::endpoint::helpers::precondition_mapper(&e))?;
+
+    thread
+        .update_on_reddit(&conn)
+        .expect("error updating on Reddit");
+
+    Ok(Json(thread.clock.status(thread.space__t0, now)))
+}
+
+/// Scrub the current attempt of a `Thread`, optionally moving it to a new T-0.
+///
+/// The thread is updated on Reddit once all changes have been made.
+#[post("/<id>/scrub", data = "<data>")]
+pub fn scrub(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    data: Json<ScrubRequest>,
+) -> RocketResult<Json<Thread>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let thread = match Thread::scrub(&conn, id, user.id, &data, Utc::now().timestamp()) {
+        Ok(thread) => thread,
+        Err(ScrubError::Clock(_)) => return Err(Status::UnprocessableEntity),
+        Err(ScrubError::Archive(_)) => return Err(Status::InternalServerError),
+        Err(ScrubError::Database(e)) => return Err(crate::endpoint::helpers::error_mapper(&e)),
+    };
+
+    thread
+        .update_on_reddit(&conn)
+        .expect("error updating on Reddit");
+
+    Ok(Json(thread))
+}
+
+/// Move a `Thread` to another state.
+///
+/// Moving a thread to `posted` submits it to Reddit if it has a subreddit.
+#[post("/<id>/state", data = "<data>")]
+pub fn transition(
+    conn: DataDB,
+    services: State<'_, Services>,
+    user: User,
+    id: i32,
+    data: Json<TransitionRequest>,
+) -> RocketResult<Json<Thread>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    Thread::transition(
+        &conn,
+        &*services.publisher,
+        id,
+        &data,
+        Utc::now().timestamp(),
+    )
+    .map(Json)
+    .map_err(transition_mapper)
+}
+
+/// Where to publish a `Thread`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PublishRequest {
+    pub subreddit: Option<String>,
+}
+
+/// Post a draft or scheduled `Thread` to Reddit, complete with its content.
+///
+/// The subreddit may be chosen at this point, replacing any set previously.
+#[post("/<id>/publish", data = "<data>")]
+pub fn publish(
+    conn: DataDB,
+    services: State<'_, Services>,
+    user: User,
+    id: i32,
+    data: Json<PublishRequest>,
+) -> RocketResult<Json<Thread>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    Thread::publish(
+        &conn,
+        &*services.publisher,
+        id,
+        data.into_inner().subreddit,
+        Utc::now().timestamp(),
+    )
+    .map(Json)
+    .map_err(transition_mapper)
+}
+
+/// Preview a `Thread` as it would be posted to Reddit were the provided changes made,
+/// along with a diff against the thread as currently saved.
+///
+/// Nothing is saved, broadcast, or sent to Reddit.
+#[post("/<id>/preview", data = "<data>")]
+pub fn preview(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    data: Json<PreviewRequest>,
+) -> RocketResult<Json<Preview>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    Thread::preview(&conn, id, &data)
+        .map(Json)
+        .map_err(preview_mapper)
+}
+
+/// Refresh a `Thread`'s launch data, applying any changes not conflicting with manual edits.
+///
+/// The thread is updated on Reddit if anything changed.
+#[post("/<id>/launch/refresh")]
+pub fn refresh_launch(
+    conn: DataDB,
+    user: User,
+    services: State<'_, Services>,
+    id: i32,
+) -> RocketResult<Json<Thread>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let provider = services.launch_provider.as_ref().ok_or(Status::NotFound)?;
+    let (thread, changed) = Thread::refresh_launch(&conn, id, &**provider, Utc::now().timestamp())
+        .map_err(launch_mapper)?;
+
+    if changed {
+        thread
+            .update_on_reddit(&conn)
+            .expect("error updating on Reddit");
+    }
+
+    Ok(Json(thread))
+}
+
+/// Get the webhooks of a `Thread`.
+///
+/// Webhook URLs are secret, so are only available to those who can modify the thread.
+#[get("/<id>/webhooks")]
+pub fn webhooks(conn: DataDB, user: User, id: i32) -> RocketResult<Json<Webhooks>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let thread =
+        Thread::find_id(&conn, id).map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    Ok(Json(thread.webhooks))
+}
+
+/// Replace the webhooks of a `Thread`.
+///
+/// Each webhook may only be sent sections of the thread.
+#[put("/<id>/webhooks", data = "<data>")]
+pub fn set_webhooks(
+    conn: DataDB,
+    user: User,
+    id: i32,
+    data: Json<Webhooks>,
+) -> RocketResult<Json<Webhooks>> {
+    if !user.can_modify_thread(&conn, id) {
+        return Err(Status::Unauthorized);
+    }
+
+    let thread =
+        Thread::find_id(&conn, id).map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+    if data.check(&thread.sections_id).is_err() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let thread = Thread::set_webhooks(&conn, id, data.into_inner())
+        .map_err(|e| crate::endpoint::helpers::error_mapper(&e))?;
+
+    Ok(Json(thread.webhooks))
+}
+
+/// Convert an error changing the state of a thread to the appropriate status.
+fn transition_mapper(err: TransitionError) -> Status {
+    match err {
+        TransitionError::Invalid { .. }
+        | TransitionError::NoSchedule
+        | TransitionError::AlreadyPosted
+        | TransitionError::NoSubreddit => Status::UnprocessableEntity,
+        TransitionError::Render(_) => Status::InternalServerError,
+        TransitionError::Reddit(_) => Status::BadGateway,
+        TransitionError::Conflict => Status::Conflict,
+        TransitionError::Database(e) => crate::endpoint::helpers::error_mapper(&e),
+    }
+}
+
+/// Convert an error previewing a thread to the appropriate status.
+fn preview_mapper(err: PreviewError) -> Status {
+    match err {
+        PreviewError::UnknownSection(_)
+        | PreviewError::UnknownEvent(_)
+        | PreviewError::MissingName
+        | PreviewError::MissingCols
+        | PreviewError::Section(_)
+        | PreviewError::Event(_) => Status::UnprocessableEntity,
+        PreviewError::Render(_) => Status::InternalServerError,
+        PreviewError::Database(e) => crate::endpoint::helpers::error_mapper(&e),
+    }
+}
+
+/// Convert an error looking up a launch to the appropriate status.
+fn launch_mapper(err: LaunchError) -> Status {
+    match err {
+        LaunchError::NoLaunch | LaunchError::NotFound(_) => Status::UnprocessableEntity,
+        LaunchError::Request(_) | LaunchError::Invalid(_) => Status::BadGateway,
+        LaunchError::Database(e) => crate::endpoint::helpers::error_mapper(&e),
+    }
+}
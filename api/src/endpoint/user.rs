@@ -1,11 +1,11 @@
 #![allow(non_snake_case)]
 
-use crate::controller::User;
+use crate::controller::{User, UserFilter, UserSortKey};
 
 #[cfg(debug)]
 use {
     crate::{
-        controller::{Claim, ExternalInsertUser, ExternalUpdateUser},
+        controller::{Claim, ExternalInsertUser, ExternalUpdateUser, Repository},
         endpoint::helpers::RocketResult,
         DataDB,
     },
@@ -15,7 +15,7 @@ use {
     std::convert::From,
 };
 
-generic_all!(User);
+generic_all!(User, UserFilter, UserSortKey);
 generic_get!(User);
 
 /// Create a `User`.
@@ -0,0 +1,66 @@
+//! Periodically refresh the launch data of `Thread`s.
+
+use crate::{
+    controller::{LaunchProvider, Thread},
+    Database,
+    Pool,
+};
+use chrono::Utc;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// How frequently to refresh launch data.
+const INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Refresh the launch data of all threads whose launch may still change,
+/// then update any changed threads on Reddit.
+fn run_once(conn: &Database, provider: &dyn LaunchProvider) {
+    let now = Utc::now().timestamp();
+    let threads = match Thread::find_refreshable(conn, now) {
+        Ok(threads) => threads,
+        Err(err) => {
+            eprintln!("error finding threads to refresh: {}", err);
+            return;
+        }
+    };
+
+    for thread in threads {
+        let result = Thread::refresh_launch(conn, thread.id, provider, now)
+            .map_err(|err| err.to_string())
+            .and_then(|(thread, changed)| {
+                if changed {
+                    thread.update_on_reddit(conn).map_err(|err| err.to_string())
+                } else {
+                    Ok(())
+                }
+            });
+
+        if let Err(err) = result {
+            eprintln!("error refreshing launch of thread {}: {}", thread.id, err);
+        }
+    }
+}
+
+/// Refresh launch data indefinitely,
+/// taking a connection from the pool for each run.
+///
+/// A failure (including a panic or being unable to connect) is logged,
+/// and the threads are reconsidered on the next run.
+pub fn spawn(pool: Pool, provider: Arc<dyn LaunchProvider>) {
+    loop {
+        match pool.get() {
+            Ok(conn) => {
+                if panic::catch_unwind(AssertUnwindSafe(|| run_once(&conn, &*provider))).is_err() {
+                    eprintln!("launch refresh panicked; retrying");
+                }
+            }
+            Err(err) => eprintln!("launch refresh could not connect to the database: {}", err),
+        }
+
+        thread::sleep(INTERVAL);
+    }
+}
@@ -10,22 +10,41 @@ mod controller;
 mod encryption;
 mod endpoint;
 mod fairing;
+mod launch_refresh;
+mod scheduler;
 mod schema;
 mod telemetry;
 #[cfg(test)]
 mod tests;
 mod websocket;
 
+use controller::{
+    set_invalidation_channel,
+    FixtureProvider,
+    LaunchProvider,
+    PgNotify,
+    Publisher,
+    RedditPublisher,
+    SpaceXApi,
+    SPACEX_API_URL,
+};
 use dotenv::dotenv;
-use endpoint::{event, meta, oauth, section, thread, user};
+use endpoint::{event, meta, oauth, preset, section, template, thread, user};
 use fairing::FeatureFilter;
 use once_cell::sync::Lazy;
 use rocket::{routes, Rocket};
 use rocket_conditional_attach::ConditionalAttach;
-use rocket_contrib::{database, helmet::SpaceHelmet};
+use rocket_contrib::{
+    database,
+    databases::{
+        database_config,
+        diesel::r2d2::{self, ConnectionManager},
+    },
+    helmet::SpaceHelmet,
+};
 use rocket_cors::CorsOptions;
 use rocket_telemetry::Telemetry;
-use std::{error::Error, net::SocketAddr};
+use std::{error::Error, net::SocketAddr, sync::Arc};
 
 /// Single point to change if we need to alter the DBMS.
 /// Note that there may be database-specific features that also need changing.
@@ -33,6 +52,13 @@ pub type Database = diesel::PgConnection;
 #[database("data")]
 pub struct DataDB(Database);
 
+/// Connections for work done outside of requests,
+/// each of which is only held for as long as it is needed.
+pub type Pool = r2d2::Pool<ConnectionManager<Database>>;
+
+/// How many connections work outside of requests may hold at once.
+const BACKGROUND_POOL_SIZE: u32 = 2;
+
 /// Returns a globally unique identifier.
 /// Specifically, v4, which is not based on any input factors.
 #[macro_export]
@@ -83,6 +109,38 @@ static CLARGS: Lazy<clap::ArgMatches<'_>> = Lazy::new(|| {
                 .short("t")
                 .long("telemetry"),
         )
+        .arg(
+            Arg::with_name("cache invalidation")
+                .help("Channel used to keep caches coherent across multiple instances")
+                .short("c")
+                .long("cache-invalidation")
+                .value_name("CHANNEL")
+                .possible_values(&["none", "postgres"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("launch provider")
+                .help("Source of launch data for threads with a `spacex__api_id`")
+                .short("l")
+                .long("launch-provider")
+                .value_name("PROVIDER")
+                .possible_values(&["none", "spacex", "fixture"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("launch fixture")
+                .help("JSON file of launches, used by the fixture launch provider")
+                .long("launch-fixture")
+                .value_name("PATH")
+                .required_if("launch provider", "fixture"),
+        )
+        .arg(
+            Arg::with_name("SpaceX API")
+                .help("Base URL of the SpaceX API, used by the spacex launch provider")
+                .long("spacex-api")
+                .value_name("URL")
+                .default_value(SPACEX_API_URL),
+        )
         .get_matches()
 });
 
@@ -93,11 +151,37 @@ static WS_HOST: Lazy<SocketAddr> = Lazy::new(|| {
     clap::value_t!(CLARGS.value_of("WebSocket host"), SocketAddr).unwrap_or_else(|e| e.exit())
 });
 static TELEMETRY: Lazy<bool> = Lazy::new(|| CLARGS.is_present("telemetry"));
+static PG_CACHE_INVALIDATION: Lazy<bool> =
+    Lazy::new(|| CLARGS.value_of("cache invalidation") == Some("postgres"));
 
-/// Creates a server,
+/// The external services relied on by the server,
+/// which are managed as state so that each server (such as one in a test) can have its own.
+pub struct Services {
+    /// Where launches are looked up by a thread's `spacex__api_id`.
+    /// If there is none, launch data is never used.
+    pub launch_provider: Option<Arc<dyn LaunchProvider>>,
+    /// Where threads are submitted once posted, being Reddit by default.
+    pub publisher:       Arc<dyn Publisher>,
+}
+
+impl Default for Services {
+    fn default() -> Self {
+        Services {
+            launch_provider: None,
+            publisher:       Arc::new(RedditPublisher),
+        }
+    }
+}
+
+/// Creates a server without any external services.
+pub fn server() -> Rocket {
+    server_with(Services::default())
+}
+
+/// Creates a server using the provided services,
 /// attaching middleware for security and database access.
 /// Routes are then mounted (some conditionally).
-pub fn server() -> Rocket {
+pub fn server_with(services: Services) -> Rocket {
     let _ = dotenv();
 
     #[cfg(debug)]
@@ -114,6 +198,7 @@ pub fn server() -> Rocket {
         .attach(FeatureFilter::default())
         .attach_if(*TELEMETRY, Telemetry::default())
         .manage(CorsOptions::default().to_cors().unwrap())
+        .manage(services)
         .mount("/", rocket_cors::catch_all_options_routes())
         .mount("/meta", routes![meta::meta])
         .mount("/oauth", routes![oauth::oauth, oauth::callback])
@@ -136,6 +221,22 @@ pub fn server() -> Rocket {
                 thread::sticky,
                 thread::unsticky,
                 thread::delete,
+                thread::import_events,
+                thread::export_events,
+                thread::export,
+                thread::import,
+                thread::render,
+                thread::clock,
+                thread::hold,
+                thread::resume,
+                thread::scrub,
+                thread::refresh_launch,
+                thread::clone,
+                thread::transition,
+                thread::publish,
+                thread::preview,
+                thread::webhooks,
+                thread::set_webhooks,
             ],
         )
         .mount(
@@ -146,6 +247,9 @@ pub fn server() -> Rocket {
                 section::post,
                 section::patch,
                 section::delete,
+                section::patch_stats,
+                section::fill_stats,
+                section::kinds,
             ],
         )
         .mount(
@@ -156,6 +260,28 @@ pub fn server() -> Rocket {
                 event::post,
                 event::patch,
                 event::delete,
+                event::batch,
+            ],
+        )
+        .mount(
+            "/v1/preset",
+            routes![
+                preset::all,
+                preset::get,
+                preset::post,
+                preset::patch,
+                preset::delete,
+                preset::instantiate,
+            ],
+        )
+        .mount(
+            "/v1/template",
+            routes![
+                template::all,
+                template::get,
+                template::post,
+                template::patch,
+                template::delete,
             ],
         )
 }
@@ -174,7 +300,50 @@ fn main() -> Result<(), Box<dyn Error>> {
             .spawn(telemetry::spawn)?;
     }
 
-    server().launch();
+    let launch_provider: Option<Arc<dyn LaunchProvider>> = match CLARGS.value_of("launch provider")
+    {
+        Some("spacex") => Some(Arc::new(SpaceXApi::new(
+            CLARGS.value_of("SpaceX API").unwrap_or(SPACEX_API_URL),
+        ))),
+        Some("fixture") => Some(Arc::new(FixtureProvider::from_file(
+            CLARGS.value_of("launch fixture").unwrap_or_default(),
+        )?)),
+        _ => None,
+    };
+
+    let publisher: Arc<dyn Publisher> = Arc::new(RedditPublisher);
+
+    let server = server_with(Services {
+        launch_provider: launch_provider.clone(),
+        publisher:       Arc::clone(&publisher),
+    });
+    let url = database_config("data", server.config())
+        .map_err(|err| format!("{:?}", err))?
+        .url
+        .to_owned();
+    let pool = Pool::builder()
+        .max_size(BACKGROUND_POOL_SIZE)
+        .build(ConnectionManager::new(url.as_str()))?;
+
+    if *PG_CACHE_INVALIDATION {
+        set_invalidation_channel(PgNotify);
+        thread::Builder::new()
+            .name("cache_invalidation".into())
+            .spawn(move || PgNotify::listen(&url))?;
+    }
+
+    if let Some(provider) = launch_provider {
+        let pool = pool.clone();
+        thread::Builder::new()
+            .name("launch_refresh".into())
+            .spawn(move || launch_refresh::spawn(pool, provider))?;
+    }
+
+    thread::Builder::new()
+        .name("scheduler".into())
+        .spawn(move || scheduler::spawn(pool, publisher))?;
+
+    server.launch();
 
     Ok(())
 }
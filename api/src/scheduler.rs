@@ -0,0 +1,86 @@
+//! Automatically post `Event`s and scheduled `Thread`s once they are due.
+
+use crate::{
+    controller::{Event, Publisher, Repository, Thread, ThreadState, TransitionRequest},
+    Database,
+    Pool,
+};
+use chrono::Utc;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// How frequently to check for due events and threads.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Post all due events, then update the affected threads on Reddit.
+fn run_once(conn: &Database) {
+    let thread_ids = match Event::post_due(conn, Utc::now().timestamp()) {
+        Ok(thread_ids) => thread_ids,
+        Err(err) => {
+            eprintln!("error posting scheduled events: {}", err);
+            return;
+        }
+    };
+
+    for thread_id in thread_ids {
+        let result =
+            Thread::find_id(conn, thread_id).and_then(|thread| thread.update_on_reddit(conn));
+
+        if let Err(err) = result {
+            eprintln!("error updating thread {} on Reddit: {}", thread_id, err);
+        }
+    }
+}
+
+/// Post all scheduled threads that are due.
+/// A thread that fails to post is logged, and retried on the next run.
+fn post_due_threads(conn: &Database, publisher: &dyn Publisher) {
+    let now = Utc::now().timestamp();
+    let thread_ids = match Thread::find_scheduled_due(conn, now) {
+        Ok(thread_ids) => thread_ids,
+        Err(err) => {
+            eprintln!("error finding scheduled threads: {}", err);
+            return;
+        }
+    };
+
+    let request = TransitionRequest {
+        state:        ThreadState::Posted,
+        scheduled_at: None,
+    };
+    for thread_id in thread_ids {
+        if let Err(err) = Thread::transition(conn, publisher, thread_id, &request, now) {
+            eprintln!("error posting scheduled thread {}: {}", thread_id, err);
+        }
+    }
+}
+
+/// Check for due events and threads indefinitely,
+/// taking a connection from the pool for each run.
+/// Scheduled threads are submitted to the provided publisher.
+///
+/// A failure (including a panic or being unable to connect) is logged,
+/// and the events and threads are reconsidered on the next run.
+pub fn spawn(pool: Pool, publisher: Arc<dyn Publisher>) {
+    loop {
+        match pool.get() {
+            Ok(conn) => {
+                if panic::catch_unwind(AssertUnwindSafe(|| run_once(&conn))).is_err() {
+                    eprintln!("scheduler panicked; retrying");
+                }
+                if panic::catch_unwind(AssertUnwindSafe(|| post_due_threads(&conn, &*publisher)))
+                    .is_err()
+                {
+                    eprintln!("scheduler panicked while posting threads; retrying");
+                }
+            }
+            Err(err) => eprintln!("scheduler could not connect to the database: {}", err),
+        }
+
+        thread::sleep(INTERVAL);
+    }
+}
@@ -4,18 +4,33 @@ table! {
         posted -> Bool,
         in_thread_id -> Int4,
         cols -> Jsonb,
+        post_at -> Nullable<Jsonb>,
+        version -> Int4,
+    }
+}
+
+table! {
+    preset_event (id) {
+        id -> Int4,
+        name -> Varchar,
+        cols -> Jsonb,
+        subreddit -> Nullable<Varchar>,
+        created_by_user_id -> Int4,
+        version -> Int4,
     }
 }
 
 table! {
     section (id) {
         id -> Int4,
-        is_events_section -> Bool,
         name -> Varchar,
         content -> Text,
         lock_held_by_user_id -> Nullable<Int4>,
         in_thread_id -> Int4,
         lock_assigned_at_utc -> Int8,
+        version -> Int4,
+        kind -> Varchar,
+        data -> Jsonb,
     }
 }
 
@@ -32,9 +47,30 @@ table! {
         created_by_user_id -> Int4,
         sections_id -> Array<Int4>,
         events_id -> Array<Int4>,
-        event_column_headers -> Array<Text>,
-        space__utc_col_index -> Nullable<Int2>,
         is_live -> Bool,
+        version -> Int4,
+        event_columns -> Jsonb,
+        time_format -> Jsonb,
+        clock -> Jsonb,
+        launch_sync -> Jsonb,
+        state -> Varchar,
+        scheduled_at -> Nullable<Int8>,
+        webhooks -> Jsonb,
+    }
+}
+
+table! {
+    thread_template (id) {
+        id -> Int4,
+        name -> Varchar,
+        thread_name_pattern -> Varchar,
+        display_name_pattern -> Varchar,
+        event_columns -> Jsonb,
+        time_format -> Jsonb,
+        sections -> Jsonb,
+        subreddit -> Nullable<Varchar>,
+        created_by_user_id -> Int4,
+        version -> Int4,
     }
 }
 
@@ -53,12 +89,16 @@ table! {
     }
 }
 
+joinable!(preset_event -> user (created_by_user_id));
 joinable!(section -> user (lock_held_by_user_id));
 joinable!(thread -> user (created_by_user_id));
+joinable!(thread_template -> user (created_by_user_id));
 
 allow_tables_to_appear_in_same_query!(
     event,
+    preset_event,
     section,
     thread,
+    thread_template,
     user,
 );
@@ -0,0 +1,32 @@
+use super::{append_log, sleep, IncludesTimestamp};
+use crate::controller::{
+    take_render_stats,
+    Event,
+    PresetEvent,
+    Repository,
+    Section,
+    Thread,
+    ThreadTemplate,
+    User,
+};
+
+pub async fn log() {
+    loop {
+        sleep(60).await;
+
+        for (name, (hits, misses)) in &[
+            ("event", Event::cache().take_stats()),
+            ("preset_event", PresetEvent::cache().take_stats()),
+            ("render", take_render_stats()),
+            ("section", Section::cache().take_stats()),
+            ("thread", Thread::cache().take_stats()),
+            ("thread_template", ThreadTemplate::cache().take_stats()),
+            ("user", User::cache().take_stats()),
+        ] {
+            append_log(
+                IncludesTimestamp(false),
+                format!("Cache {}: {} hits, {} misses", name, hits, misses),
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod caches;
 mod requests;
 mod ws_clients;
 pub mod ws_message;
@@ -68,6 +69,7 @@ pub fn spawn() {
     }
 
     tokio::run(compat!(async {
+        tokio::spawn(compat!(caches::log()));
         tokio::spawn(compat!(requests::log()));
         tokio::spawn(compat!(ws_clients::log()));
     }));
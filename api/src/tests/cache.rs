@@ -0,0 +1,154 @@
+use crate::{
+    controller::{
+        cache_size,
+        set_invalidation_channel,
+        InsertSection,
+        PgNotify,
+        Repository,
+        Section,
+        UpdateSection,
+    },
+    server,
+    tests::helpers::*,
+    DataDB,
+};
+use diesel::sql_types::Text;
+use fallible_iterator::FallibleIterator;
+use rocket_contrib::databases::{database_config, diesel::RunQueryDsl};
+use serde_json::Value as Json;
+use std::{
+    env,
+    sync::Once,
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+/// How long to wait for a notification to be delivered.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+static LISTENER: Once = Once::new();
+
+fn create_section(conn: &DataDB, thread_id: i32) -> Section {
+    Section::create(
+        conn,
+        &InsertSection {
+            name:         "Overview".into(),
+            content:      String::new(),
+            in_thread_id: thread_id,
+            kind:         "markdown".into(),
+            data:         Json::Null,
+        },
+    )
+    .unwrap()
+}
+
+/// Send an invalidation as though it came from another instance.
+fn notify_from_other_instance(conn: &DataDB, key: &str) {
+    diesel::sql_query("SELECT pg_notify('cache_invalidation', $1)")
+        .bind::<Text, _>(format!("other {}", key))
+        .execute(&**conn)
+        .unwrap();
+}
+
+/// Wait for the condition to hold, panicking if it does not do so in time.
+fn wait_for(description: &str, mut condition: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(
+            start.elapsed() < TIMEOUT,
+            "timed out waiting for {}",
+            description
+        );
+        sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn sizes() {
+    env::remove_var("TEST_CACHE_SIZE");
+    assert_eq!(cache_size("TEST_CACHE_SIZE", 5), 5);
+
+    env::set_var("TEST_CACHE_SIZE", "12");
+    assert_eq!(cache_size("TEST_CACHE_SIZE", 5), 12);
+
+    // invalid sizes are ignored
+    env::set_var("TEST_CACHE_SIZE", "many");
+    assert_eq!(cache_size("TEST_CACHE_SIZE", 5), 5);
+    env::set_var("TEST_CACHE_SIZE", "-1");
+    assert_eq!(cache_size("TEST_CACHE_SIZE", 5), 5);
+
+    env::remove_var("TEST_CACHE_SIZE");
+}
+
+#[test]
+fn pg_notify() {
+    let mut client = Client::new();
+    let server = server();
+    let conn = DataDB::get_one(&server).unwrap();
+    let url = database_config("data", server.config())
+        .unwrap()
+        .url
+        .to_owned();
+
+    // setup
+    set_invalidation_channel(PgNotify);
+    let listener_url = url.clone();
+    LISTENER.call_once(|| {
+        spawn(move || PgNotify::listen(&listener_url));
+    });
+    let observer = postgres::Connection::connect(url.as_str(), postgres::TlsMode::None).unwrap();
+    observer.execute("LISTEN cache_invalidation", &[]).unwrap();
+
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let section = create_section(&conn, thread_id);
+    let other_section = create_section(&conn, thread_id);
+
+    // test
+    // changes are published to other instances
+    Section::update(
+        &conn,
+        section.id,
+        &UpdateSection {
+            content: Some("Go for launch".into()),
+            ..UpdateSection::default()
+        },
+    )
+    .unwrap();
+    let key = format!("section:{}", section.id);
+    let notifications = observer.notifications();
+    let mut notifications = notifications.timeout_iter(TIMEOUT);
+    let payload = loop {
+        let notification = notifications
+            .next()
+            .unwrap()
+            .expect("timed out waiting for the invalidation to be published");
+        if notification.payload.ends_with(&format!(" {}", key)) {
+            break notification.payload;
+        }
+    };
+    assert!(!payload.starts_with("other "));
+
+    // once the listener is running, invalidations from other instances evict the value
+    wait_for("the listener to evict the section", || {
+        Section::cache().insert(section.id, section.clone());
+        notify_from_other_instance(&conn, &key);
+        sleep(Duration::from_millis(100));
+        Section::cache().get(section.id).is_none()
+    });
+
+    // only the value that changed is evicted
+    Section::cache().insert(section.id, section.clone());
+    Section::cache().insert(other_section.id, other_section.clone());
+    notify_from_other_instance(&conn, &key);
+    wait_for("the listener to evict the section", || {
+        Section::cache().get(section.id).is_none()
+    });
+    assert!(Section::cache().get(other_section.id).is_some());
+
+    // teardown
+    client
+        .with_base("/v1/thread")
+        .delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
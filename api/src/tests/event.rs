@@ -1,4 +1,10 @@
-use crate::{guid, tests::helpers::*};
+use crate::{
+    controller::{Event, RenderContext, Repository, Thread, ToMarkdown},
+    guid,
+    server,
+    tests::helpers::*,
+    DataDB,
+};
 use serde_json::{json, Value as Json};
 
 const BASE: &str = "/v1/event";
@@ -82,6 +88,8 @@ fn create() {
             "posted": false,
             "cols": event["cols"],
             "in_thread_id": event["in_thread_id"],
+            "post_at": null,
+            "version": 1,
         })
     );
 
@@ -91,6 +99,233 @@ fn create() {
     user::delete(&mut client, user_id);
 }
 
+#[test]
+fn create_scheduled() {
+    let mut client = Client::new();
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+
+    for post_at in &[json!({ "utc": 1_500_000_000 }), json!({ "t_minus": -60 })] {
+        let body = client
+            .with_base(BASE)
+            .post(
+                Some(&user_token),
+                json!({
+                    "cols": [1_500_000_000, guid!(), guid!()],
+                    "in_thread_id": thread_id,
+                    "post_at": post_at,
+                }),
+            )
+            .assert_created()
+            .get_body_object();
+        assert_eq!(body["posted"], json!(false));
+        assert_eq!(&body["post_at"], post_at);
+
+        client
+            .with_base(BASE)
+            .delete(Some(&user_token), &body["id"])
+            .assert_no_content();
+    }
+
+    // teardown
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn create_is_rendered() {
+    let mut client = Client::new();
+    let conn = DataDB::get_one(&server()).unwrap();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({ "name": "Live updates", "kind": "events", "in_thread_id": thread_id }),
+        )
+        .assert_created();
+
+    // test
+    // the thread as posted to Reddit includes the new event
+    let update = guid!();
+    let event = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "cols": [1_500_000_000, "", update],
+                "in_thread_id": thread_id,
+            }),
+        )
+        .assert_created()
+        .get_body_object();
+    let thread = Thread::find_id(&conn, thread_id).unwrap();
+    assert!(thread
+        .events_id
+        .contains(&(event["id"].as_i64().unwrap() as i32)));
+    let ctx = RenderContext::load(&conn, &thread).unwrap();
+    assert!(thread.to_markdown(&ctx).unwrap().contains(&update));
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &event["id"]);
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn post_due() {
+    let mut client = Client::new();
+    let conn = DataDB::get_one(&server()).unwrap();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let now = chrono::Utc::now().timestamp();
+    client
+        .with_base("/v1/thread")
+        .patch(
+            Some(&user_token),
+            thread_id,
+            json!({ "space__t0": now + 600 }),
+        )
+        .assert_ok();
+
+    // T-10:00 at the time of posting
+    let schedules = [
+        (json!({ "utc": now - 60 }), true),
+        (json!({ "utc": now + 60 }), false),
+        (json!({ "t_minus": -660 }), true),
+        (json!({ "t_minus": -540 }), false),
+    ];
+    let events_id: Vec<_> = schedules
+        .iter()
+        .map(|(post_at, _)| {
+            client
+                .with_base(BASE)
+                .post(
+                    Some(&user_token),
+                    json!({
+                        "cols": [1_500_000_000, guid!(), guid!()],
+                        "in_thread_id": thread_id,
+                        "post_at": post_at,
+                    }),
+                )
+                .assert_created()
+                .get_body_object()["id"]
+                .as_i64()
+                .unwrap() as i32
+        })
+        .collect();
+
+    // test
+    let threads_id = Event::post_due(&conn, now).unwrap();
+    assert!(threads_id.contains(&thread_id));
+
+    for (&event_id, (post_at, is_due)) in events_id.iter().zip(&schedules) {
+        let event = Event::load_id(&conn, event_id).unwrap();
+        assert_eq!(event.posted, *is_due, "event scheduled at {}", post_at);
+    }
+
+    // nothing is posted twice
+    let threads_id = Event::post_due(&conn, now).unwrap();
+    assert!(!threads_id.contains(&thread_id));
+
+    // teardown
+    for event_id in events_id {
+        client
+            .with_base(BASE)
+            .delete(Some(&user_token), event_id)
+            .assert_no_content();
+    }
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn create_validates_columns() {
+    let mut client = Client::new();
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = client
+        .with_base("/v1/thread")
+        .post(
+            Some(&user_token),
+            json!({
+                "thread_name": guid!(),
+                "display_name": guid!(),
+                "event_columns": [
+                    { "name": "UTC", "type": "utc", "required": true },
+                    { "name": "Countdown", "type": "t_minus" },
+                    {
+                        "name": "Status",
+                        "type": "enum",
+                        "options": ["go", "hold"],
+                        "default": "go",
+                    },
+                ],
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+
+    // values must be of the column's type
+    for cols in &[
+        json!(["foo"]),
+        json!([1_500_000_000, "soon"]),
+        json!([1_500_000_000, "T-0:10", "scrub"]),
+        json!([1_500_000_000, "T-0:10", "go", "extra"]),
+        json!([null, "T-0:10"]),
+    ] {
+        client
+            .with_base(BASE)
+            .post(
+                Some(&user_token),
+                json!({ "cols": cols, "in_thread_id": thread_id }),
+            )
+            .assert_unprocessable_entity();
+    }
+
+    // missing values take the column's default
+    let body = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "cols": [1_500_000_000], "in_thread_id": thread_id }),
+        )
+        .assert_created()
+        .get_body_object();
+    assert_eq!(body["cols"], json!([1_500_000_000, null, "go"]));
+
+    // partial updates are validated in the same manner
+    client
+        .with_base(BASE)
+        .patch(Some(&user_token), &body["id"], json!([[3, "foo"]]))
+        .assert_unprocessable_entity();
+    client
+        .with_base(BASE)
+        .patch(Some(&user_token), &body["id"], json!([[2, "scrub"]]))
+        .assert_unprocessable_entity();
+    let updated = client
+        .with_base(BASE)
+        .patch(Some(&user_token), &body["id"], json!([[1, "T+0:05"]]))
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(updated["cols"], json!([1_500_000_000, "T+0:05", "go"]));
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &body["id"]);
+    thread::delete(&mut client, &user_token, thread_id as i32);
+    user::delete(&mut client, user_id);
+}
+
 #[test]
 fn update() {
     let mut client = Client::new();
@@ -128,6 +363,49 @@ fn update() {
     user::delete(&mut client, user_id);
 }
 
+#[test]
+fn update_if_match() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let created_value = create_event(&mut client, &user_token, thread_id);
+    let version = created_value["version"].as_i64().unwrap();
+
+    // test
+    // the current version is accepted, and the new version is returned
+    let response = client
+        .with_base(BASE)
+        .patch_if_match(
+            &user_token,
+            &created_value["id"],
+            version,
+            json!({ "posted": true }),
+        )
+        .assert_ok();
+    assert_eq!(response.get_etag(), format!(r#""{}""#, version + 1));
+    assert_eq!(response.get_body_object()["version"], version + 1);
+
+    // a stale version is rejected
+    client
+        .with_base(BASE)
+        .patch_if_match(
+            &user_token,
+            &created_value["id"],
+            version,
+            json!([[2, guid!()]]),
+        )
+        .assert_precondition_failed();
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &created_value["id"]);
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
 #[test]
 fn delete() {
     let mut client = Client::new();
@@ -145,3 +423,70 @@ fn delete() {
     thread::delete(&mut client, &user_token, thread_id);
     user::delete(&mut client, user_id);
 }
+
+#[test]
+fn batch() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let first = create_event(&mut client, &user_token, thread_id);
+    let second = create_event(&mut client, &user_token, thread_id);
+    let third = create_event(&mut client, &user_token, thread_id);
+
+    // test
+    let body = client
+        .with_base(BASE)
+        .post_to(
+            "batch",
+            Some(&user_token),
+            json!({
+                "in_thread_id": thread_id,
+                "operations": [
+                    { "op": "partial_patch", "id": first["id"], "cols": [[2, "Updated"]] },
+                    { "op": "patch", "id": second["id"], "version": 1, "data": { "posted": true } },
+                    { "op": "delete", "id": third["id"] },
+                    { "op": "move", "id": second["id"], "to": 0 },
+                ],
+            }),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(body["updated"][0]["cols"][2], json!("Updated"));
+    assert_eq!(body["updated"][1]["posted"], json!(true));
+    assert_eq!(body["deleted"], json!([third["id"]]));
+    assert_eq!(body["events_id"], json!([second["id"], first["id"]]));
+
+    // a single failing operation prevents the entire batch from being applied
+    client
+        .with_base(BASE)
+        .post_to(
+            "batch",
+            Some(&user_token),
+            json!({
+                "in_thread_id": thread_id,
+                "operations": [
+                    { "op": "delete", "id": first["id"] },
+                    {
+                        "op": "patch",
+                        "id": second["id"],
+                        "version": 1,
+                        "data": { "posted": false },
+                    },
+                ],
+            }),
+        )
+        .assert_precondition_failed();
+    client.with_base(BASE).get(&first["id"]).assert_ok();
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &first["id"]);
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &second["id"]);
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
@@ -1,6 +1,6 @@
-use crate::server;
+use crate::{server, server_with, Services};
 use rocket::{
-    http::{Header, Status},
+    http::{ContentType, Header, Status},
     local::{Client as RocketClient, LocalResponse as RocketResponse},
 };
 use serde_json::Value;
@@ -18,6 +18,13 @@ impl<'a> Client<'a> {
         }
     }
 
+    pub fn with_services(services: Services) -> Self {
+        Client {
+            base:   "",
+            client: RocketClient::new(server_with(services)).expect("invalid rocket instance"),
+        }
+    }
+
     pub fn with_base(&mut self, base: &'a str) -> &Self {
         self.base = base;
         self
@@ -31,24 +38,66 @@ impl<'a> Client<'a> {
         self.get("")
     }
 
+    pub fn get_all_with_query(&self, query: impl AsRef<str>) -> Response<'_> {
+        self.get(format!("?{}", query.as_ref()))
+    }
+
     pub fn get(&self, id: impl ToString) -> Response<'_> {
         Response(self.client.get(self.url_for(id)).dispatch())
     }
 
+    pub fn get_as(&self, token: &str, id: impl ToString) -> Response<'_> {
+        Response(
+            self.client
+                .get(self.url_for(id))
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
+                .dispatch(),
+        )
+    }
+
     pub fn post(&self, token: Option<&str>, body: impl ToString) -> Response<'_> {
+        self.post_to("", token, body)
+    }
+
+    pub fn post_to(
+        &self,
+        path: impl ToString,
+        token: Option<&str>,
+        body: impl ToString,
+    ) -> Response<'_> {
+        let path = path.to_string();
+        let url = if path.is_empty() {
+            self.base.to_string()
+        } else {
+            self.url_for(path)
+        };
+
         Response(match token {
             Some(token) => self
                 .client
-                .post(self.base)
+                .post(url)
                 .body(body.to_string())
                 .header(Header::new("Authorization", format!("Bearer {}", token)))
                 .dispatch(),
-            None => self
-                .client
-                .post(self.base)
+            None => self.client.post(url).body(body.to_string()).dispatch(),
+        })
+    }
+
+    pub fn post_to_as(
+        &self,
+        path: impl ToString,
+        token: &str,
+        content_type: ContentType,
+        body: impl ToString,
+    ) -> Response<'_> {
+        Response(
+            self.client
+                .post(self.url_for(path))
                 .body(body.to_string())
+                .header(content_type)
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
                 .dispatch(),
-        })
+        )
     }
 
     pub fn patch(
@@ -72,6 +121,36 @@ impl<'a> Client<'a> {
         })
     }
 
+    pub fn put(&self, token: &str, id: impl ToString, body: impl ToString) -> Response<'_> {
+        Response(
+            self.client
+                .put(self.url_for(id))
+                .body(body.to_string())
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
+                .dispatch(),
+        )
+    }
+
+    pub fn patch_if_match(
+        &self,
+        token: &str,
+        id: impl ToString,
+        version: impl ToString,
+        body: impl ToString,
+    ) -> Response<'_> {
+        Response(
+            self.client
+                .patch(self.url_for(id))
+                .body(body.to_string())
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
+                .header(Header::new(
+                    "If-Match",
+                    format!(r#""{}""#, version.to_string()),
+                ))
+                .dispatch(),
+        )
+    }
+
     pub fn delete(&self, token: Option<&str>, id: impl ToString) -> Response<'_> {
         Response(match token {
             Some(token) => self
@@ -111,6 +190,49 @@ impl Response<'_> {
         self
     }
 
+    pub fn assert_bad_request(self) -> Self {
+        assert_eq!(self.status(), Status::BadRequest);
+        self
+    }
+
+    pub fn assert_unauthorized(self) -> Self {
+        assert_eq!(self.status(), Status::Unauthorized);
+        self
+    }
+
+    pub fn assert_not_found(self) -> Self {
+        assert_eq!(self.status(), Status::NotFound);
+        self
+    }
+
+    pub fn assert_precondition_failed(self) -> Self {
+        assert_eq!(self.status(), Status::PreconditionFailed);
+        self
+    }
+
+    pub fn assert_payload_too_large(self) -> Self {
+        assert_eq!(self.status(), Status::PayloadTooLarge);
+        self
+    }
+
+    pub fn assert_unprocessable_entity(self) -> Self {
+        assert_eq!(self.status(), Status::UnprocessableEntity);
+        self
+    }
+
+    pub fn assert_bad_gateway(self) -> Self {
+        assert_eq!(self.status(), Status::BadGateway);
+        self
+    }
+
+    pub fn get_etag(&self) -> String {
+        self.0.headers().get_one("ETag").unwrap().into()
+    }
+
+    pub fn get_link(&self) -> Option<String> {
+        self.0.headers().get_one("Link").map(Into::into)
+    }
+
     pub fn get_redirect_uri(self) -> String {
         self.0.headers().get_one("Location").unwrap().into()
     }
@@ -127,6 +249,10 @@ impl Response<'_> {
         body
     }
 
+    pub fn get_body_string(mut self) -> String {
+        self.0.body_string().unwrap()
+    }
+
     fn body(&mut self) -> Value {
         self.0
             .body_string()
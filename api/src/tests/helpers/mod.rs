@@ -1,6 +1,8 @@
 mod client;
 
+pub mod publisher;
 pub mod thread;
 pub mod user;
+pub mod webhook;
 
 pub use client::Client;
@@ -0,0 +1,66 @@
+use crate::{
+    controller::{Publisher, TransitionError},
+    Database,
+};
+use parking_lot::Mutex;
+
+/// A post submitted to a `RecordingPublisher`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submission {
+    pub user_id:   i32,
+    pub subreddit: String,
+    pub title:     String,
+    pub markdown:  String,
+}
+
+/// A stand-in for Reddit, recording every post submitted to it.
+/// Each post is given the ID `post<n>`, counting from zero.
+#[derive(Debug, Default)]
+pub struct RecordingPublisher {
+    submissions: Mutex<Vec<Submission>>,
+}
+
+impl RecordingPublisher {
+    /// The posts submitted so far, in order.
+    pub fn submissions(&self) -> Vec<Submission> {
+        self.submissions.lock().clone()
+    }
+}
+
+impl Publisher for RecordingPublisher {
+    fn submit(
+        &self,
+        _conn: &Database,
+        user_id: i32,
+        subreddit: &str,
+        title: &str,
+        markdown: &str,
+    ) -> Result<String, TransitionError> {
+        let mut submissions = self.submissions.lock();
+        submissions.push(Submission {
+            user_id,
+            subreddit: subreddit.into(),
+            title: title.into(),
+            markdown: markdown.into(),
+        });
+
+        Ok(format!("post{}", submissions.len() - 1))
+    }
+}
+
+/// A stand-in for Reddit rejecting every post submitted to it.
+#[derive(Debug, Default)]
+pub struct FailingPublisher;
+
+impl Publisher for FailingPublisher {
+    fn submit(
+        &self,
+        _conn: &Database,
+        _user_id: i32,
+        _subreddit: &str,
+        _title: &str,
+        _markdown: &str,
+    ) -> Result<String, TransitionError> {
+        Err(TransitionError::Reddit("rejected".into()))
+    }
+}
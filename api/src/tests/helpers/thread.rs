@@ -11,8 +11,11 @@ pub fn create(client: &mut Client<'_>, token: impl ToString) -> i32 {
             json!({
                 "thread_name": guid!(),
                 "display_name": guid!(),
-                "event_column_headers": ["UTC", "Countdown", "Update"],
-                "space__utc_col_index": 0,
+                "event_columns": [
+                    { "name": "UTC", "type": "utc", "required": true },
+                    { "name": "Countdown", "type": "text" },
+                    { "name": "Update", "type": "text", "default": "" },
+                ],
             }),
         )
         .assert_created()
@@ -0,0 +1,95 @@
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for a webhook to be delivered.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A local stand-in for Discord or Slack,
+/// recording the body of every request it receives.
+pub struct StandIn {
+    pub url: String,
+    bodies:  Arc<Mutex<Vec<Value>>>,
+}
+
+impl StandIn {
+    /// Respond to each request with the next of the provided statuses,
+    /// and with `204 No Content` once they are exhausted.
+    pub fn new(statuses: &[u16]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind stand-in");
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(vec![]));
+
+        let mut statuses = statuses.to_vec().into_iter();
+        let received = Arc::clone(&bodies);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let status = statuses.next().unwrap_or(204);
+                if let Some(body) = respond(stream, status) {
+                    received.lock().push(body);
+                }
+            }
+        });
+
+        StandIn { url, bodies }
+    }
+
+    /// Wait for at least the provided number of requests,
+    /// returning the body of every request received.
+    pub fn wait_for(&self, count: usize) -> Vec<Value> {
+        let start = Instant::now();
+
+        while self.bodies.lock().len() < count && start.elapsed() < TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        self.bodies.lock().clone()
+    }
+}
+
+/// Read a single request, responding with the status and closing the connection.
+fn respond(stream: TcpStream, status: u16) -> Option<Value> {
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+    let mut content_length = 0;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let mut parts = line.splitn(2, ':');
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} Stand-In\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    )
+    .ok()?;
+
+    serde_json::from_slice(&body).ok()
+}
@@ -1,12 +1,58 @@
 use crate::{
-    controller::{Event, Section, Thread, ToMarkdown},
+    controller::{
+        Clock,
+        ColumnError,
+        Event,
+        LaunchSync,
+        RenderContext,
+        RenderError,
+        Repository,
+        Section,
+        Thread,
+        ThreadState,
+        TimeFormat,
+        ToMarkdown,
+        Webhooks,
+    },
+    guid,
     server,
     tests::helpers::*,
     DataDB,
 };
-use serde_json::json;
+use serde_json::{json, Value as Json};
 use std::error::Error;
 
+/// A thread that only exists in memory, with the default columns.
+fn in_memory_thread() -> Thread {
+    Thread {
+        id: 0,
+        thread_name: "Launch".into(),
+        display_name: "Launch".into(),
+        post_id: None,
+        subreddit: None,
+        space__t0: None,
+        video_url: None,
+        spacex__api_id: None,
+        created_by_user_id: 0,
+        sections_id: vec![],
+        events_id: vec![],
+        is_live: false,
+        version: 1,
+        event_columns: serde_json::from_value(json!([
+            { "name": "UTC", "type": "utc", "required": true },
+            { "name": "Countdown", "type": "text" },
+            { "name": "Update", "type": "text", "default": "" },
+        ]))
+        .unwrap(),
+        time_format: TimeFormat::default(),
+        clock: Clock::default(),
+        launch_sync: LaunchSync::default(),
+        state: ThreadState::Draft,
+        scheduled_at: None,
+        webhooks: Webhooks::default(),
+    }
+}
+
 #[test]
 fn event_posted() -> Result<(), Box<dyn Error>> {
     // setup
@@ -31,7 +77,9 @@ fn event_posted() -> Result<(), Box<dyn Error>> {
         .as_i64()
         .unwrap() as i32;
 
-    let md = Event::find_id(&conn, event_id)?.to_markdown(&conn)?;
+    let thread = Thread::find_id(&conn, thread_id)?;
+    let ctx = RenderContext::load(&conn, &thread)?;
+    let md = Event::find_id(&conn, event_id)?.to_markdown(&ctx)?;
 
     assert_eq!("|01:11|T+0:00|foo|\n", md);
 
@@ -47,42 +95,156 @@ fn event_posted() -> Result<(), Box<dyn Error>> {
 }
 
 #[test]
-fn event_unposted() -> Result<(), Box<dyn Error>> {
-    let database = DataDB::get_one(&server()).unwrap();
+fn event_time_format() -> Result<(), Box<dyn Error>> {
+    // setup
+    let mut client = Client::new();
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = client
+        .with_base("/v1/thread")
+        .post(
+            Some(&user_token),
+            json!({
+                "thread_name": guid!(),
+                "display_name": guid!(),
+                "space__t0": 1_546_305_000,
+                "event_columns": [{ "name": "Time", "type": "utc" }],
+                "time_format": {
+                    "pattern": "%H:%M:%S %Z",
+                    "timezone": "America/New_York",
+                    "show_date": "always",
+                    "show_t_minus": true,
+                },
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap() as i32;
+    let conn = DataDB::get_one(&server()).unwrap();
+
+    // test
+    let event_id = client
+        .with_base("/v1/event")
+        .post(
+            Some(&user_token),
+            json!({
+                "posted": true,
+                "cols": [1_546_305_060],
+                "in_thread_id": thread_id,
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap() as i32;
+
+    let thread = Thread::find_id(&conn, thread_id)?;
+    let ctx = RenderContext::load(&conn, &thread)?;
+    let md = Event::find_id(&conn, event_id)?.to_markdown(&ctx)?;
+
+    assert_eq!("|2018-12-31 20:11:00 EST (T+0:01:00)|\n", md);
 
+    // teardown
+    client
+        .with_base("/v1/event")
+        .delete(Some(&user_token), event_id)
+        .assert_no_content();
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+
+    Ok(())
+}
+
+#[test]
+fn event_unposted() -> Result<(), Box<dyn Error>> {
     let event = Event {
         id:           0, // irrelevant
         posted:       false,
         cols:         json!([1_546_305_060, "T+0:00", "foo"]),
         in_thread_id: 0, // irrelevant
+        post_at:      None,
+        version:      1, // irrelevant
     };
 
-    let md = event.to_markdown(&database)?;
+    let ctx = RenderContext::new(in_memory_thread(), vec![], vec![event.clone()]);
+    let md = event.to_markdown(&ctx)?;
 
     assert_eq!("", md);
     Ok(())
 }
 
 #[test]
-fn section_not_events() -> Result<(), Box<dyn Error>> {
-    let database = DataDB::get_one(&server()).unwrap();
+fn event_malformed() {
+    let event = Event {
+        id:           3,
+        posted:       true,
+        cols:         json!(["not a timestamp", "T+0:00", "foo"]),
+        in_thread_id: 0, // irrelevant
+        post_at:      None,
+        version:      1, // irrelevant
+    };
+
+    let ctx = RenderContext::new(in_memory_thread(), vec![], vec![event.clone()]);
+
+    assert_eq!(
+        Err(RenderError::Event {
+            event_id: 3,
+            error:    ColumnError::InvalidValue("UTC".into()),
+        }),
+        event.to_markdown(&ctx)
+    );
+}
 
+#[test]
+fn section_not_events() -> Result<(), Box<dyn Error>> {
     let section = Section {
         id: 0, // irrelevant
-        is_events_section: false,
         name: "Introduction".into(),
         content: "foo\n\nbar".into(),
         lock_held_by_user_id: None,
         lock_assigned_at_utc: 0,
         in_thread_id: 0,
+        version: 1,
+        kind: "markdown".into(),
+        data: Json::Null,
     };
 
-    let md = section.to_markdown(&database)?;
+    let ctx = RenderContext::new(in_memory_thread(), vec![section.clone()], vec![]);
+    let md = section.to_markdown(&ctx)?;
 
     assert_eq!("# Introduction\nfoo\n\nbar", md);
     Ok(())
 }
 
+#[test]
+fn section_stats() -> Result<(), Box<dyn Error>> {
+    let section = Section {
+        id: 0, // irrelevant
+        name: "Stats".into(),
+        content: "\nWeather is 80% GO.".into(),
+        lock_held_by_user_id: None,
+        lock_assigned_at_utc: 0,
+        in_thread_id: 0,
+        version: 1,
+        kind: "stats".into(),
+        data: json!({
+            "rows": [
+                { "key": "Vehicle", "value": "Falcon 9" },
+                { "key": "Landing", "value": "OCISLY | ASDS" },
+            ],
+        }),
+    };
+
+    let ctx = RenderContext::new(in_memory_thread(), vec![section.clone()], vec![]);
+    let md = section.to_markdown(&ctx)?;
+
+    assert_eq!(
+        "# Stats\n|||\n|-|-|\n|Vehicle|Falcon 9|\n|Landing|OCISLY \\| ASDS|\n\nWeather is 80% GO.",
+        md
+    );
+    Ok(())
+}
+
 #[test]
 fn section_events() -> Result<(), Box<dyn Error>> {
     // setup
@@ -97,7 +259,7 @@ fn section_events() -> Result<(), Box<dyn Error>> {
                 "in_thread_id": thread_id,
                 "name": "Live Updates",
                 "content": "",
-                "is_events_section": true,
+                "kind": "events",
             }),
         )
         .assert_created()
@@ -137,7 +299,9 @@ fn section_events() -> Result<(), Box<dyn Error>> {
     let conn = crate::DataDB::get_one(&crate::server()).unwrap();
 
     // test
-    let md = Section::find_id(&conn, section_id)?.to_markdown(&conn)?;
+    let thread = Thread::find_id(&conn, thread_id)?;
+    let ctx = RenderContext::load(&conn, &thread)?;
+    let md = Section::find_id(&conn, section_id)?.to_markdown(&ctx)?;
 
     assert_eq!(
         "# Live Updates\n\
@@ -178,19 +342,17 @@ fn thread() -> Result<(), Box<dyn Error>> {
             "in_thread_id": thread_id,
             "name": "Introduction",
             "content": "Sed consectetur nunc molestie eros.",
-            "is_events_section": false,
         }),
         json!({
             "in_thread_id": thread_id,
             "name": "Live Updates",
             "content": "",
-            "is_events_section": true,
+            "kind": "events",
         }),
         json!({
             "in_thread_id": thread_id,
             "name": "Participate!",
             "content": "Fusce volutpat nisl a metus.",
-            "is_events_section": false,
         }),
     ]
     .iter()
@@ -237,7 +399,9 @@ fn thread() -> Result<(), Box<dyn Error>> {
     let conn = crate::DataDB::get_one(&crate::server()).unwrap();
 
     // test
-    let md = Thread::find_id(&conn, thread_id)?.to_markdown(&conn)?;
+    let thread = Thread::find_id(&conn, thread_id)?;
+    let ctx = RenderContext::load(&conn, &thread)?;
+    let md = thread.to_markdown(&ctx)?;
 
     assert_eq!(
         "# Introduction\n\
@@ -1,8 +1,13 @@
+mod cache;
 mod event;
 mod helpers;
 mod markdown;
 mod meta;
 mod oauth;
+mod preset;
+mod repository;
 mod section;
+mod template;
 mod thread;
 mod user;
+mod webhook;
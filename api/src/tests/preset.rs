@@ -0,0 +1,175 @@
+use crate::{guid, tests::helpers::*};
+use serde_json::{json, Value as Json};
+
+const BASE: &str = "/v1/preset";
+
+fn create_preset(client: &mut Client<'_>, token: &str, cols: Json) -> Json {
+    client
+        .with_base(BASE)
+        .post(Some(token), json!({ "name": guid!(), "cols": cols }))
+        .assert_created()
+        .get_body_object()
+}
+
+#[test]
+fn get_all() {
+    Client::new()
+        .with_base(BASE)
+        .get_all()
+        .assert_ok()
+        .get_body_array();
+}
+
+#[test]
+fn create() {
+    let mut client = Client::new();
+    let (user_id, user_token) = user::create(&mut client);
+
+    let mut body = create_preset(&mut client, &user_token, json!(["{utc}", "", "Liftoff!"]));
+    assert!(body["id"].is_number(), r#"body["id"] is number"#);
+    let id = body["id"].take();
+    let name = body["name"].take();
+    assert_eq!(
+        body,
+        json!({
+            "id": null,
+            "name": null,
+            "cols": ["{utc}", "", "Liftoff!"],
+            "subreddit": null,
+            "created_by_user_id": user_id,
+            "version": 1,
+        })
+    );
+    assert!(name.is_string(), r#"body["name"] is string"#);
+
+    // only hosts can create presets for a subreddit
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "name": guid!(), "cols": [], "subreddit": "spacex" }),
+        )
+        .assert_unauthorized();
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), id)
+        .assert_no_content();
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn personal_presets_are_private() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let id = create_preset(&mut client, &user_token, json!([]))["id"]
+        .as_i64()
+        .unwrap();
+    let query = format!("?created_by_user_id={}", user_id);
+
+    // test
+    // the creator can see the preset
+    client.with_base(BASE).get_as(&user_token, id).assert_ok();
+    let presets = client
+        .with_base(BASE)
+        .get_as(&user_token, &query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(presets[0]["id"], json!(id));
+
+    // nobody else can
+    client.with_base(BASE).get(id).assert_not_found();
+    client
+        .with_base(BASE)
+        .get_as(&other_user_token, id)
+        .assert_not_found();
+    client
+        .with_base(BASE)
+        .patch(Some(&other_user_token), id, json!({ "name": guid!() }))
+        .assert_not_found();
+    client
+        .with_base(BASE)
+        .delete(Some(&other_user_token), id)
+        .assert_not_found();
+    let presets = client
+        .with_base(BASE)
+        .get_as(&other_user_token, &query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(presets, json!([]));
+    let presets = client
+        .with_base(BASE)
+        .get(&query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(presets, json!([]));
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), id)
+        .assert_no_content();
+    user::delete(&mut client, other_user_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn instantiate() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let preset = create_preset(
+        &mut client,
+        &user_token,
+        json!(["{utc}", "", "Liftoff at {utc}!"]),
+    );
+    let t_minus_preset = create_preset(&mut client, &user_token, json!(["{utc}", "{t_minus}"]));
+
+    let instantiate_url = format!("{}/{}", BASE, preset["id"]);
+    let t_minus_instantiate_url = format!("{}/{}", BASE, t_minus_preset["id"]);
+
+    // test
+    // placeholders are substituted
+    let event = client
+        .with_base(&instantiate_url)
+        .post_to(
+            "instantiate",
+            Some(&user_token),
+            json!({ "in_thread_id": thread_id }),
+        )
+        .assert_created()
+        .get_body_object();
+    assert!(event["cols"][0].is_i64(), "UTC placeholder is a timestamp");
+    let message = event["cols"][2].as_str().unwrap();
+    assert!(message.starts_with("Liftoff at ") && message.ends_with('!'));
+    assert!(!message.contains("{utc}"));
+
+    // the thread has no T-0, so the placeholder cannot be substituted
+    client
+        .with_base(&t_minus_instantiate_url)
+        .post_to(
+            "instantiate",
+            Some(&user_token),
+            json!({ "in_thread_id": thread_id }),
+        )
+        .assert_unprocessable_entity();
+
+    // teardown
+    client
+        .with_base("/v1/event")
+        .delete(Some(&user_token), &event["id"]);
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &preset["id"]);
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &t_minus_preset["id"]);
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
@@ -0,0 +1,157 @@
+use crate::{
+    controller::{Cache, InsertSection, Repository, Section, Thread, UpdateSection},
+    server,
+    tests::helpers::*,
+    DataDB,
+};
+use rocket_contrib::databases::diesel::result::Error as DieselError;
+use serde_json::Value as Json;
+
+fn ids(sections: &[Section]) -> Vec<i32> {
+    sections.iter().map(|section| section.id).collect()
+}
+
+#[test]
+fn cache() {
+    let cache = Cache::new(2);
+
+    // test
+    assert_eq!(cache.get(1), None);
+    cache.insert(1, "one");
+    cache.insert(2, "two");
+    assert_eq!(cache.get(1), Some("one"));
+
+    // the least recently used value is evicted once full
+    cache.insert(3, "three");
+    assert_eq!(cache.get(2), None);
+    assert_eq!(cache.get(3), Some("three"));
+
+    // hits and misses are reset once taken
+    assert_eq!(cache.take_stats(), (2, 2));
+    assert_eq!(cache.take_stats(), (0, 0));
+
+    cache.remove(1);
+    assert_eq!(cache.get(1), None);
+    cache.clear();
+    assert_eq!(cache.get(3), None);
+    assert_eq!(cache.take_stats(), (0, 2));
+}
+
+#[test]
+fn lifecycle() {
+    let mut client = Client::new();
+    let conn = DataDB::get_one(&server()).unwrap();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+
+    // test
+    // created values are cached, and any hooks are run
+    let section = Section::create(
+        &conn,
+        &InsertSection {
+            name:         "Overview".into(),
+            content:      String::new(),
+            in_thread_id: thread_id,
+            kind:         "markdown".into(),
+            data:         Json::Null,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        Section::cache()
+            .get(section.id)
+            .map(|cached| cached.version),
+        Some(section.version)
+    );
+    assert!(Thread::find_id(&conn, thread_id)
+        .unwrap()
+        .sections_id
+        .contains(&section.id));
+
+    // updates are only made if the version matches, if provided
+    let changes = UpdateSection {
+        content: Some("Go for launch".into()),
+        ..UpdateSection::default()
+    };
+    match Section::update_if_version(&conn, section.id, Some(section.version + 1), &changes) {
+        Err(DieselError::NotFound) => {}
+        _ => panic!("expected the version to not match"),
+    }
+    let updated =
+        Section::update_if_version(&conn, section.id, Some(section.version), &changes).unwrap();
+    assert_eq!(updated.content, "Go for launch");
+    assert_eq!(updated.version, section.version + 1);
+    assert_eq!(
+        Section::cache()
+            .get(section.id)
+            .map(|cached| cached.version),
+        Some(updated.version)
+    );
+    assert_eq!(
+        Section::load_id(&conn, section.id).unwrap().content,
+        updated.content
+    );
+
+    // deleted values are removed from the cache and database
+    assert_eq!(Section::delete(&conn, section.id).unwrap(), 1);
+    assert!(Section::cache().get(section.id).is_none());
+    match Section::find_id(&conn, section.id) {
+        Err(DieselError::NotFound) => {}
+        _ => panic!("expected the section to be deleted"),
+    }
+
+    // teardown
+    client
+        .with_base("/v1/thread")
+        .delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn find_ids() {
+    let mut client = Client::new();
+    let conn = DataDB::get_one(&server()).unwrap();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let insert = |name: &str| {
+        Section::create(
+            &conn,
+            &InsertSection {
+                name:         name.into(),
+                content:      String::new(),
+                in_thread_id: thread_id,
+                kind:         "markdown".into(),
+                data:         Json::Null,
+            },
+        )
+        .unwrap()
+    };
+    let first = insert("First");
+    let second = insert("Second");
+
+    // test
+    // values are returned in the order requested, whether cached or not
+    Section::cache().remove(first.id);
+    let found = Section::find_ids(&conn, &[second.id, -1, first.id, second.id]).unwrap();
+    assert_eq!(ids(&found.values), vec![second.id, first.id, second.id]);
+    assert_eq!(found.missing, vec![-1]);
+
+    // values loaded from the database are cached
+    assert!(Section::cache().get(first.id).is_some());
+
+    // unless only peeked at
+    Section::cache().remove(first.id);
+    let found = Section::peek_ids(&conn, &[first.id, second.id]).unwrap();
+    assert_eq!(ids(&found.values), vec![first.id, second.id]);
+    assert!(Section::cache().get(first.id).is_none());
+
+    // teardown
+    client
+        .with_base("/v1/thread")
+        .delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
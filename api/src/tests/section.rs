@@ -75,12 +75,14 @@ fn create() {
         body,
         json!({
             "id": null,
-            "is_events_section": false,
             "name": section["name"],
             "content": section["content"],
             "lock_held_by_user_id": null,
             "lock_assigned_at_utc": null,
             "in_thread_id": section["in_thread_id"],
+            "version": 1,
+            "kind": "markdown",
+            "data": null,
         })
     );
 
@@ -134,3 +136,148 @@ fn delete() {
     thread::delete(&mut client, &user_token, thread_id);
     user::delete(&mut client, user_id);
 }
+
+#[test]
+fn stats() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let created_value = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "in_thread_id": thread_id,
+                "kind": "stats",
+                "data": { "rows": [{ "key": "Vehicle", "value": "Falcon 9" }] },
+            }),
+        )
+        .assert_created()
+        .get_body_object();
+    let stats_url = format!("{}/{}", BASE, created_value["id"]);
+
+    // test
+    let body = client
+        .with_base(&stats_url)
+        .patch(
+            Some(&user_token),
+            "stats",
+            json!([
+                { "key": "Booster", "value": "B1049.4" },
+                { "key": "Vehicle", "value": "Falcon Heavy" },
+            ]),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(
+        body["data"]["rows"],
+        json!([
+            { "key": "Vehicle", "value": "Falcon Heavy" },
+            { "key": "Booster", "value": "B1049.4" },
+        ])
+    );
+
+    let body = client
+        .with_base(&stats_url)
+        .patch(
+            Some(&user_token),
+            "stats",
+            json!([{ "key": "Vehicle", "value": null }]),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(
+        body["data"]["rows"],
+        json!([{ "key": "Booster", "value": "B1049.4" }])
+    );
+
+    // keys must be present
+    client
+        .with_base(&stats_url)
+        .patch(
+            Some(&user_token),
+            "stats",
+            json!([{ "key": "", "value": "foo" }]),
+        )
+        .assert_unprocessable_entity();
+
+    // the thread has no launch data
+    client
+        .with_base(&stats_url)
+        .post_to("stats/fill", Some(&user_token), "")
+        .assert_unprocessable_entity();
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &created_value["id"]);
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn kinds() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+
+    // test
+    let body = client
+        .with_base(BASE)
+        .get("kinds")
+        .assert_ok()
+        .get_body_array();
+    let names: Vec<_> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|kind| kind["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["markdown", "events", "stats", "links", "video"]);
+
+    // every example is valid
+    for kind in body.as_array().unwrap() {
+        let section = client
+            .with_base(BASE)
+            .post(
+                Some(&user_token),
+                json!({
+                    "in_thread_id": thread_id,
+                    "kind": kind["name"],
+                    "data": kind["example"],
+                }),
+            )
+            .assert_created()
+            .get_body_object();
+        client
+            .with_base(BASE)
+            .delete(Some(&user_token), &section["id"]);
+    }
+
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "in_thread_id": thread_id, "kind": "unknown" }),
+        )
+        .assert_unprocessable_entity();
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "in_thread_id": thread_id,
+                "kind": "links",
+                "data": { "links": [{ "title": "Press kit", "url": "javascript:alert(1)" }] },
+            }),
+        )
+        .assert_unprocessable_entity();
+
+    // teardown
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
@@ -0,0 +1,208 @@
+use crate::{guid, tests::helpers::*};
+use serde_json::{json, Value as Json};
+
+const BASE: &str = "/v1/template";
+
+fn create_template(client: &mut Client<'_>, token: &str) -> Json {
+    client
+        .with_base(BASE)
+        .post(
+            Some(token),
+            json!({
+                "name": guid!(),
+                "thread_name_pattern": "{name} Launch Thread",
+                "event_columns": [
+                    { "name": "UTC", "type": "utc" },
+                    { "name": "Update", "type": "text" },
+                ],
+                "sections": [
+                    { "name": "Overview", "content": "Welcome!" },
+                    { "name": "Live updates", "kind": "events" },
+                ],
+            }),
+        )
+        .assert_created()
+        .get_body_object()
+}
+
+#[test]
+fn get_all() {
+    Client::new()
+        .with_base(BASE)
+        .get_all()
+        .assert_ok()
+        .get_body_array();
+}
+
+#[test]
+fn create() {
+    let mut client = Client::new();
+    let (user_id, user_token) = user::create(&mut client);
+
+    let body = create_template(&mut client, &user_token);
+    assert_eq!(body["display_name_pattern"], json!("{name}"));
+    assert_eq!(body["sections"][0]["kind"], json!("markdown"));
+    assert_eq!(body["created_by_user_id"], json!(user_id));
+
+    // sections must be of a known kind, with valid data
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "name": guid!(), "sections": [{ "name": "Nope", "kind": "nope" }] }),
+        )
+        .assert_unprocessable_entity();
+    client
+        .with_base(BASE)
+        .patch(
+            Some(&user_token),
+            &body["id"],
+            json!({ "sections": [{ "name": "Links", "kind": "links", "data": null }] }),
+        )
+        .assert_unprocessable_entity();
+
+    // only hosts can create templates for a subreddit
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "name": guid!(), "subreddit": "spacex" }),
+        )
+        .assert_unauthorized();
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &body["id"])
+        .assert_no_content();
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn personal_templates_are_private() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let id = create_template(&mut client, &user_token)["id"]
+        .as_i64()
+        .unwrap();
+    let query = format!("?created_by_user_id={}", user_id);
+
+    // test
+    // the creator can see the template
+    client.with_base(BASE).get_as(&user_token, id).assert_ok();
+    let templates = client
+        .with_base(BASE)
+        .get_as(&user_token, &query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(templates[0]["id"], json!(id));
+
+    // nobody else can
+    client.with_base(BASE).get(id).assert_not_found();
+    client
+        .with_base(BASE)
+        .get_as(&other_user_token, id)
+        .assert_not_found();
+    client
+        .with_base(BASE)
+        .patch(Some(&other_user_token), id, json!({ "name": guid!() }))
+        .assert_not_found();
+    client
+        .with_base(BASE)
+        .delete(Some(&other_user_token), id)
+        .assert_not_found();
+    let templates = client
+        .with_base(BASE)
+        .get_as(&other_user_token, &query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(templates, json!([]));
+    let templates = client
+        .with_base(BASE)
+        .get(&query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(templates, json!([]));
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), id)
+        .assert_no_content();
+    user::delete(&mut client, other_user_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn create_thread() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let template = create_template(&mut client, &user_token);
+
+    // test
+    // the thread is created with the template's names, schema, and sections
+    let thread = client
+        .with_base("/v1/thread")
+        .post(
+            Some(&user_token),
+            json!({ "display_name": "Starlink 5", "template_id": template["id"] }),
+        )
+        .assert_created()
+        .get_body_object();
+    let thread_id = thread["id"].as_i64().unwrap() as i32;
+    assert_eq!(thread["thread_name"], json!("Starlink 5 Launch Thread"));
+    assert_eq!(thread["display_name"], json!("Starlink 5"));
+    assert_eq!(thread["event_columns"], template["event_columns"]);
+
+    let sections: Vec<_> = thread["sections_id"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| {
+            client
+                .with_base("/v1/section")
+                .get(id)
+                .assert_ok()
+                .get_body_object()
+        })
+        .collect();
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[0]["name"], json!("Overview"));
+    assert_eq!(sections[0]["content"], json!("Welcome!"));
+    assert_eq!(sections[1]["kind"], json!("events"));
+
+    // a name is required to fill in the patterns
+    client
+        .with_base("/v1/thread")
+        .post(Some(&user_token), json!({ "template_id": template["id"] }))
+        .assert_unprocessable_entity();
+
+    // personal templates can only be used by their creator
+    client
+        .with_base("/v1/thread")
+        .post(
+            Some(&other_user_token),
+            json!({ "display_name": "Starlink 5", "template_id": template["id"] }),
+        )
+        .assert_unauthorized();
+
+    // teardown
+    for section in &sections {
+        client
+            .with_base("/v1/section")
+            .delete(Some(&user_token), &section["id"]);
+    }
+    thread::delete(&mut client, &user_token, thread_id);
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &template["id"])
+        .assert_no_content();
+    user::delete(&mut client, user_id);
+    user::delete(&mut client, other_user_id);
+}
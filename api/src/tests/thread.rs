@@ -1,5 +1,17 @@
-use crate::{guid, tests::helpers::*};
+use crate::{
+    controller::{Clock, ClockError, FixtureProvider, Publisher, Repository, Thread, UpdateThread},
+    guid,
+    server,
+    tests::helpers::{
+        publisher::{FailingPublisher, RecordingPublisher, Submission},
+        *,
+    },
+    DataDB,
+    Services,
+};
+use rocket::http::ContentType;
 use serde_json::{json, Value as Json};
+use std::{error::Error, sync::Arc};
 
 const BASE: &str = "/v1/thread";
 
@@ -11,7 +23,7 @@ fn create_thread(client: &mut Client<'_>, token: &str) -> Json {
             json!({
                 "thread_name": guid!(),
                 "display_name": guid!(),
-                "event_column_headers": [],
+                "event_columns": [],
             }),
         )
         .assert_created()
@@ -27,6 +39,48 @@ fn get_all() {
         .get_body_array();
 }
 
+#[test]
+fn get_all_filtered() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let first = create_thread(&mut client, &user_token);
+    let second = create_thread(&mut client, &user_token);
+
+    // test
+    // only the user's threads are returned, newest first
+    let body = client
+        .with_base(BASE)
+        .get_all_with_query(format!("created_by_user_id={}&sort=-id", user_id))
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(body, json!([second, first]));
+
+    // the page is full, so there is a link to the next page
+    let response = client
+        .with_base(BASE)
+        .get_all_with_query(format!("created_by_user_id={}&limit=1", user_id))
+        .assert_ok();
+    assert!(response.get_link().unwrap().contains("offset=1"));
+    assert_eq!(response.get_body_array(), json!([first]));
+
+    // unknown sort keys are rejected
+    client
+        .with_base(BASE)
+        .get_all_with_query("sort=foo")
+        .assert_bad_request();
+
+    // teardown
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &first["id"]);
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &second["id"]);
+    user::delete(&mut client, user_id);
+}
+
 #[test]
 fn get_one() {
     let mut client = Client::new();
@@ -50,6 +104,53 @@ fn get_one() {
     user::delete(&mut client, user_id);
 }
 
+#[test]
+fn get_full() -> Result<(), Box<dyn Error>> {
+    let mut client = Client::new();
+    let conn = DataDB::get_one(&server()).unwrap();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+
+    // test
+    let body = client
+        .with_base(BASE)
+        .get(format!("{}/full", thread_id))
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(body["created_by_user"]["id"], user_id);
+    assert_eq!(body["sections"], json!([]));
+    assert_eq!(body["events"], json!([]));
+    assert_eq!(
+        body["missing"],
+        json!({ "sections_id": [], "events_id": [], "users_id": [] })
+    );
+
+    // children that cannot be found are reported, rather than causing an error
+    Thread::update(
+        &conn,
+        thread_id,
+        &UpdateThread {
+            events_id: Some(vec![i32::max_value()]),
+            ..UpdateThread::default()
+        },
+    )?;
+    let body = client
+        .with_base(BASE)
+        .get(format!("{}/full", thread_id))
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(body["events"], json!([]));
+    assert_eq!(body["missing"]["events_id"], json!([i32::max_value()]));
+
+    // teardown
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+
+    Ok(())
+}
+
 #[test]
 fn create() {
     let mut client = Client::new();
@@ -60,7 +161,7 @@ fn create() {
         "display_name": guid!(),
         "space__t0": rand::random::<i64>(),
         "video_url": guid!(),
-        "event_column_headers": [],
+        "event_columns": [],
     });
 
     let mut body = client
@@ -87,12 +188,29 @@ fn create() {
             "sections_id": [],
             "events_id": [],
             "is_live": false,
+            "version": 1,
+            "time_format": {
+                "pattern": "%H:%M",
+                "date_pattern": "%Y-%m-%d",
+                "timezone": null,
+                "show_date": "never",
+                "show_t_minus": false,
+            },
+            "clock": { "holds": [], "scrubbed_at": null, "scrubs": [] },
+            "launch_sync": {
+                "synced_at": null,
+                "launch": null,
+                "stats_section_id": null,
+                "conflicts": [],
+            },
+            "state": "posted",
+            "scheduled_at": null,
 
             // user-provided
             "thread_name": thread["thread_name"],
             "display_name": thread["display_name"],
             "video_url": thread["video_url"],
-            "event_column_headers": thread["event_column_headers"],
+            "event_columns": thread["event_columns"],
         })
     );
 
@@ -108,7 +226,7 @@ fn create_no_auth() {
     let thread = json!({
         "thread_name": guid!(),
         "display_name": guid!(),
-        "event_column_headers": [],
+        "event_columns": [],
     });
 
     client.with_base(BASE).post(None, &thread).assert_created();
@@ -154,3 +272,1264 @@ fn delete() {
         .assert_no_content();
     user::delete(&mut client, user_id);
 }
+
+#[test]
+fn import_export_events() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let thread_url = format!("{}/{}", BASE, thread_id);
+
+    // test
+    // JSON, with omitted columns taking their defaults
+    let json_events = client
+        .with_base(&thread_url)
+        .post_to_as(
+            "events/import",
+            &user_token,
+            ContentType::JSON,
+            json!([
+                { "cols": [1_500_000_000, "T-10"], "posted": true },
+                { "cols": [1_500_000_060, "T-9", "Terminal count"] },
+            ]),
+        )
+        .assert_created()
+        .get_body_array();
+    assert_eq!(json_events[0]["cols"], json!([1_500_000_000, "T-10", ""]));
+    assert_eq!(json_events[1]["posted"], json!(false));
+
+    // CSV, with columns in any order
+    let csv_events = client
+        .with_base(&thread_url)
+        .post_to_as(
+            "events/import",
+            &user_token,
+            ContentType::CSV,
+            "Update,UTC,posted\nLiftoff!,1500000120,true\n",
+        )
+        .assert_created()
+        .get_body_array();
+    assert_eq!(
+        csv_events[0]["cols"],
+        json!([1_500_000_120, null, "Liftoff!"])
+    );
+
+    // a single invalid row rejects the entire import
+    client
+        .with_base(&thread_url)
+        .post_to_as(
+            "events/import",
+            &user_token,
+            ContentType::CSV,
+            "UTC,Update\n1500000180,Fine\n,Missing UTC\n",
+        )
+        .assert_unprocessable_entity();
+
+    // an import over the limit is rejected rather than truncated
+    let oversized = format!(
+        "UTC,Update\n{}",
+        "1500000180,Fine\n".repeat(1024 * 1024 / 16 + 1)
+    );
+    client
+        .with_base(&thread_url)
+        .post_to_as("events/import", &user_token, ContentType::CSV, oversized)
+        .assert_payload_too_large();
+
+    let exported = client
+        .with_base(&thread_url)
+        .get("events/export")
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(exported.as_array().unwrap().len(), 3);
+    assert_eq!(
+        exported[2],
+        json!({ "cols": [1_500_000_120, null, "Liftoff!"], "posted": true, "post_at": null })
+    );
+
+    let csv = client
+        .with_base(&thread_url)
+        .get("events/export?format=csv")
+        .assert_ok()
+        .get_body_string();
+    assert_eq!(
+        csv,
+        "UTC,Countdown,Update,posted\n\
+         1500000000,T-10,,true\n\
+         1500000060,T-9,Terminal count,false\n\
+         1500000120,,Liftoff!,true\n"
+    );
+
+    // teardown
+    for event in json_events
+        .as_array()
+        .unwrap()
+        .iter()
+        .chain(csv_events.as_array().unwrap())
+    {
+        client
+            .with_base("/v1/event")
+            .delete(Some(&user_token), &event["id"]);
+    }
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn hold_and_resume() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let now = chrono::Utc::now().timestamp();
+    let thread_id = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "thread_name": guid!(),
+                "display_name": guid!(),
+                "space__t0": now + 3_600,
+                "event_columns": [],
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+
+    // test
+    let body = client
+        .with_base(&thread_url)
+        .get("clock")
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(body["state"], json!("counting"));
+    assert!(body["t_minus"].as_i64().unwrap() <= -3_590);
+
+    let body = client
+        .with_base(&thread_url)
+        .post_to("clock/hold", Some(&user_token), "")
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(body["state"], json!("holding"));
+    assert_eq!(body["holds"].as_array().unwrap().len(), 1);
+
+    // the countdown is already holding
+    client
+        .with_base(&thread_url)
+        .post_to("clock/hold", Some(&user_token), "")
+        .assert_unprocessable_entity();
+
+    // recycle to T-10:00
+    let body = client
+        .with_base(&thread_url)
+        .post_to(
+            "clock/resume",
+            Some(&user_token),
+            json!({ "recycled_to": -600 }),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(body["state"], json!("counting"));
+    assert_eq!(body["holds"][0]["recycled_to"], json!(-600));
+    let t0 = body["t0"].as_i64().unwrap();
+    assert!(t0 >= now + 600 && t0 <= now + 610);
+
+    // the countdown is not holding
+    client
+        .with_base(&thread_url)
+        .post_to("clock/resume", Some(&user_token), json!({}))
+        .assert_unprocessable_entity();
+
+    // the clock cannot be changed directly
+    client
+        .with_base(BASE)
+        .patch(Some(&user_token), thread_id, json!({ "clock": {} }))
+        .assert_unprocessable_entity();
+
+    // teardown
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn clock_out_of_range() {
+    let now = chrono::Utc::now().timestamp();
+    let mut clock = Clock::default();
+
+    // a T-0 too far from now to be represented has no T-time
+    assert_eq!(None, clock.t_minus(Some(i64::min_value()), now));
+    assert_eq!(
+        Err(ClockError::OutOfRange),
+        clock.hold(Some(i64::min_value()), now)
+    );
+
+    clock.hold(Some(now), now).unwrap();
+    assert_eq!(
+        Err(ClockError::OutOfRange),
+        clock.resume(Some(now), now, Some(i64::min_value()))
+    );
+}
+
+#[test]
+fn scrub() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let now = chrono::Utc::now().timestamp();
+    let thread_id = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "thread_name": guid!(),
+                "display_name": guid!(),
+                "space__t0": now + 600,
+                "event_columns": [{ "name": "Update", "type": "text" }],
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    let events = client
+        .with_base(&thread_url)
+        .post_to_as(
+            "events/import",
+            &user_token,
+            ContentType::JSON,
+            json!([
+                { "cols": ["Fueling complete"], "posted": true },
+                { "cols": ["Liftoff!"], "post_at": { "t_minus": 0 } },
+            ]),
+        )
+        .assert_created()
+        .get_body_array();
+
+    // test
+    let thread = client
+        .with_base(&thread_url)
+        .post_to(
+            "scrub",
+            Some(&user_token),
+            json!({
+                "reason": "Weather",
+                "new_t0": now + 86_400,
+                "archive_events": true,
+            }),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["space__t0"], json!(now + 86_400));
+    assert_eq!(thread["events_id"], json!([events[1]["id"]]));
+    assert_eq!(thread["clock"]["scrubbed_at"], json!(null));
+    assert_eq!(thread["clock"]["scrubs"][0]["reason"], json!("Weather"));
+    assert_eq!(
+        thread["clock"]["scrubs"][0]["previous_t0"],
+        json!(now + 600)
+    );
+
+    let section_id = &thread["clock"]["scrubs"][0]["archive_section_id"];
+    assert_eq!(thread["sections_id"], json!([section_id]));
+    let section = client
+        .with_base("/v1/section")
+        .get(section_id)
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(section["name"], json!("Previous attempt"));
+    assert!(section["content"]
+        .as_str()
+        .unwrap()
+        .contains("Fueling complete"));
+
+    // scrubbing without a new T-0 leaves the countdown scrubbed
+    let thread = client
+        .with_base(&thread_url)
+        .post_to("scrub", Some(&user_token), json!({ "reason": "Range" }))
+        .assert_ok()
+        .get_body_object();
+    assert!(thread["clock"]["scrubbed_at"].is_i64());
+    assert_eq!(thread["clock"]["scrubs"].as_array().unwrap().len(), 2);
+
+    // a new T-0 too far from the current one is rejected
+    client
+        .with_base(&thread_url)
+        .post_to(
+            "scrub",
+            Some(&user_token),
+            json!({ "reason": "Range", "new_t0": i64::min_value() }),
+        )
+        .assert_unprocessable_entity();
+
+    // teardown
+    client
+        .with_base("/v1/event")
+        .delete(Some(&user_token), &events[1]["id"]);
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), section_id);
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn launch_data() {
+    let provider = FixtureProvider::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/tests/fixtures/launches.json"
+    ))
+    .unwrap();
+    let mut client = Client::with_services(Services {
+        launch_provider: Some(Arc::new(provider)),
+    });
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+
+    // test
+    // the name, T-0, and a stats section are filled in from the launch
+    let thread = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "spacex__api_id": "83", "event_columns": [] }),
+        )
+        .assert_created()
+        .get_body_object();
+    let thread_id = thread["id"].as_i64().unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    assert_eq!(thread["display_name"], json!("Amos-17"));
+    assert_eq!(thread["space__t0"], json!(1_565_131_320));
+    assert_eq!(
+        thread["launch_sync"]["launch"]["vehicle"],
+        json!("Falcon 9")
+    );
+
+    let section_id = &thread["launch_sync"]["stats_section_id"];
+    assert_eq!(thread["sections_id"], json!([section_id]));
+    let section = client
+        .with_base("/v1/section")
+        .get(section_id)
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(
+        section["data"]["rows"][2],
+        json!({ "key": "Vehicle", "value": "Falcon 9" })
+    );
+
+    // unknown launches are rejected
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "spacex__api_id": "0", "event_columns": [] }),
+        )
+        .assert_unprocessable_entity();
+
+    // a manual edit is kept, and reported as a conflict
+    client
+        .with_base(BASE)
+        .patch(
+            Some(&user_token),
+            thread_id,
+            json!({ "space__t0": 1_565_140_000 }),
+        )
+        .assert_ok();
+    let thread = client
+        .with_base(&thread_url)
+        .post_to("launch/refresh", Some(&user_token), "")
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["space__t0"], json!(1_565_140_000));
+    assert_eq!(
+        thread["launch_sync"]["conflicts"][0]["field"],
+        json!("space__t0")
+    );
+    assert_eq!(
+        thread["launch_sync"]["conflicts"][0]["launch"],
+        json!(1_565_131_320)
+    );
+
+    // the launch data cannot be changed directly
+    client
+        .with_base(BASE)
+        .patch(Some(&user_token), thread_id, json!({ "launch_sync": {} }))
+        .assert_unprocessable_entity();
+
+    // teardown
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), section_id);
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn clone() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    let section = client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({ "name": "Overview", "content": "Welcome!", "in_thread_id": thread_id }),
+        )
+        .assert_created()
+        .get_body_object();
+    client
+        .with_base(&thread_url)
+        .post_to_as(
+            "events/import",
+            &user_token,
+            ContentType::JSON,
+            json!([
+                { "cols": [1_565_130_000, "", "Fueling complete"], "posted": true },
+                { "cols": [1_565_131_320, "", "Liftoff!"], "post_at": { "t_minus": 0 } },
+            ]),
+        )
+        .assert_created();
+
+    // test
+    // the sections are copied, but not the events
+    let clone = client
+        .with_base(&thread_url)
+        .post_to(
+            "clone",
+            Some(&other_user_token),
+            json!({ "thread_name": guid!() }),
+        )
+        .assert_created()
+        .get_body_object();
+    assert_eq!(clone["created_by_user_id"], json!(other_user_id));
+    assert_eq!(clone["post_id"], json!(null));
+    assert_eq!(clone["events_id"], json!([]));
+    let clone_section_id = &clone["sections_id"][0];
+    assert_ne!(clone_section_id, &section["id"]);
+    let clone_section = client
+        .with_base("/v1/section")
+        .get_as(&other_user_token, clone_section_id)
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(clone_section["content"], json!("Welcome!"));
+    assert_eq!(clone_section["in_thread_id"], clone["id"]);
+
+    // events are copied if requested, unposted
+    let clone_with_events = client
+        .with_base(&thread_url)
+        .post_to(
+            "clone",
+            Some(&other_user_token),
+            json!({ "thread_name": guid!(), "include_events": true }),
+        )
+        .assert_created()
+        .get_body_object();
+    let events: Vec<_> = clone_with_events["events_id"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| {
+            client
+                .with_base("/v1/event")
+                .get_as(&other_user_token, id)
+                .assert_ok()
+                .get_body_object()
+        })
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["posted"], json!(false));
+    assert_eq!(events[1]["post_at"], json!({ "t_minus": 0 }));
+
+    // a thread name is required
+    client
+        .with_base(&thread_url)
+        .post_to("clone", Some(&user_token), json!({ "thread_name": "" }))
+        .assert_unprocessable_entity();
+
+    // teardown
+    for thread in &[&clone, &clone_with_events] {
+        for id in thread["sections_id"].as_array().unwrap() {
+            client
+                .with_base("/v1/section")
+                .delete(Some(&other_user_token), id);
+        }
+        for id in thread["events_id"].as_array().unwrap() {
+            client
+                .with_base("/v1/event")
+                .delete(Some(&other_user_token), id);
+        }
+        client
+            .with_base(BASE)
+            .delete(Some(&other_user_token), &thread["id"]);
+    }
+    let thread = client.with_base(BASE).get(thread_id).get_body_object();
+    for id in thread["events_id"].as_array().unwrap() {
+        client.with_base("/v1/event").delete(Some(&user_token), id);
+    }
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), &section["id"]);
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+    user::delete(&mut client, other_user_id);
+}
+
+#[test]
+fn clone_to_subreddit() {
+    let publisher = Arc::new(RecordingPublisher::default());
+    let mut client = Client::with_services(Services {
+        publisher: Arc::clone(&publisher) as Arc<dyn Publisher>,
+        ..Services::default()
+    });
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    let section_id = client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({ "name": "Overview", "content": "Welcome!", "in_thread_id": thread_id }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .clone();
+    let thread_name = guid!();
+
+    // test
+    // the clone is posted complete with its content
+    let clone = client
+        .with_base(&thread_url)
+        .post_to(
+            "clone",
+            Some(&other_user_token),
+            json!({ "thread_name": thread_name, "subreddit": "spacex" }),
+        )
+        .assert_created()
+        .get_body_object();
+    assert_eq!(clone["state"], json!("posted"));
+    assert_eq!(clone["post_id"], json!("post0"));
+    let submissions = publisher.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].user_id, other_user_id);
+    assert_eq!(submissions[0].subreddit, "spacex");
+    assert_eq!(submissions[0].title, thread_name);
+    assert!(submissions[0].markdown.contains("Welcome!"));
+
+    // a clone Reddit rejects is not created
+    let mut failing_client = Client::with_services(Services {
+        publisher: Arc::new(FailingPublisher),
+        ..Services::default()
+    });
+    failing_client
+        .with_base(&thread_url)
+        .post_to(
+            "clone",
+            Some(&other_user_token),
+            json!({ "thread_name": guid!(), "subreddit": "spacex" }),
+        )
+        .assert_bad_gateway();
+    let threads = client
+        .with_base(BASE)
+        .get_as(
+            &other_user_token,
+            format!("?created_by_user_id={}", other_user_id),
+        )
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(threads.len(), 1);
+
+    // teardown
+    client
+        .with_base("/v1/section")
+        .delete(Some(&other_user_token), &clone["sections_id"][0]);
+    client
+        .with_base(BASE)
+        .delete(Some(&other_user_token), &clone["id"]);
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), &section_id);
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+    user::delete(&mut client, other_user_id);
+}
+
+#[test]
+fn export_and_import() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({ "name": "Overview", "content": "Welcome!", "in_thread_id": thread_id }),
+        )
+        .assert_created();
+    client
+        .with_base(&thread_url)
+        .post_to_as(
+            "events/import",
+            &user_token,
+            ContentType::JSON,
+            json!([{ "cols": [1_565_130_000, "", "Fueling complete"], "posted": true }]),
+        )
+        .assert_created();
+
+    // test
+    let bundle = client
+        .with_base(&thread_url)
+        .get("export")
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(bundle["version"], json!(1));
+    assert_eq!(bundle["thread"]["created_by_user_id"], json!(user_id));
+    assert_eq!(bundle["sections"][0]["content"], json!("Welcome!"));
+    assert_eq!(bundle["events"][0]["posted"], json!(true));
+    assert_eq!(bundle["authors"][0]["id"], json!(user_id));
+
+    let markdown = client
+        .with_base(&thread_url)
+        .get("export?format=markdown")
+        .assert_ok()
+        .get_body_string();
+    let title = format!("# {}", bundle["thread"]["thread_name"].as_str().unwrap());
+    assert!(markdown.starts_with(&title));
+    assert!(markdown.contains("Welcome!"));
+
+    // the bundle is restored into a new (unposted) thread
+    let restored = client
+        .with_base(BASE)
+        .post_to("import", Some(&other_user_token), &bundle)
+        .assert_created()
+        .get_body_object();
+    assert_ne!(restored["id"], json!(thread_id));
+    assert_eq!(restored["thread_name"], bundle["thread"]["thread_name"]);
+    assert_eq!(restored["created_by_user_id"], json!(other_user_id));
+    assert_eq!(restored["post_id"], json!(null));
+    assert_eq!(restored["state"], json!("draft"));
+    assert_eq!(restored["events_id"].as_array().unwrap().len(), 1);
+    let restored_section = client
+        .with_base("/v1/section")
+        .get_as(&other_user_token, &restored["sections_id"][0])
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(restored_section["content"], json!("Welcome!"));
+
+    // bundles of another version cannot be restored
+    let mut future_bundle = bundle.clone();
+    future_bundle["version"] = json!(2);
+    client
+        .with_base(BASE)
+        .post_to("import", Some(&other_user_token), &future_bundle)
+        .assert_unprocessable_entity();
+
+    // teardown
+    let thread = client.with_base(BASE).get(thread_id).get_body_object();
+    for (thread, token) in &[(&restored, &other_user_token), (&thread, &user_token)] {
+        for id in thread["sections_id"].as_array().unwrap() {
+            client.with_base("/v1/section").delete(Some(token), id);
+        }
+        for id in thread["events_id"].as_array().unwrap() {
+            client.with_base("/v1/event").delete(Some(token), id);
+        }
+        client.with_base(BASE).delete(Some(token), &thread["id"]);
+    }
+    user::delete(&mut client, user_id);
+    user::delete(&mut client, other_user_id);
+}
+
+#[test]
+fn render() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    let section_id = client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({
+                "name": "Overview",
+                "content": "**Welcome!**<script>alert(1)</script>",
+                "in_thread_id": thread_id,
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+
+    // test
+    let markdown = client
+        .with_base(&thread_url)
+        .get("render")
+        .assert_ok()
+        .get_body_string();
+    assert!(markdown.contains("**Welcome!**"));
+
+    // anything unsafe is removed from HTML
+    let html = client
+        .with_base(&thread_url)
+        .get("render?format=html")
+        .assert_ok()
+        .get_body_string();
+    assert!(html.contains("<h1>Overview</h1>"));
+    assert!(html.contains("<strong>Welcome!</strong>"));
+    assert!(!html.contains("<script>"));
+
+    // the rendered thread reflects any changes
+    client
+        .with_base("/v1/section")
+        .patch(
+            Some(&user_token),
+            section_id,
+            json!({ "content": "Liftoff!" }),
+        )
+        .assert_ok();
+    let html = client
+        .with_base(&thread_url)
+        .get("render?format=html")
+        .assert_ok()
+        .get_body_string();
+    assert!(html.contains("Liftoff!"));
+    assert!(!html.contains("Welcome!"));
+
+    client
+        .with_base(&thread_url)
+        .get("render?format=pdf")
+        .assert_bad_request();
+
+    // teardown
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), section_id);
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn preview() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    let mut sections_id = vec![];
+    for section in &[
+        json!({ "name": "Overview", "content": "Welcome!", "in_thread_id": thread_id }),
+        json!({ "name": "Live updates", "kind": "events", "in_thread_id": thread_id }),
+    ] {
+        sections_id.push(
+            client
+                .with_base("/v1/section")
+                .post(Some(&user_token), section)
+                .assert_created()
+                .get_body_object()["id"]
+                .clone(),
+        );
+    }
+
+    // test
+    let preview = client
+        .with_base(&thread_url)
+        .post_to(
+            "preview",
+            Some(&user_token),
+            json!({
+                "sections": [{ "id": sections_id[0], "content": "Go for launch" }],
+                "events": [{ "cols": [1_565_131_320, "", "Liftoff!"], "posted": true }],
+            }),
+        )
+        .assert_ok()
+        .get_body_object();
+    let markdown = preview["markdown"].as_str().unwrap();
+    assert!(markdown.contains("Go for launch"));
+    assert!(markdown.contains("|Liftoff!|"));
+    let diff = preview["saved_diff"].as_str().unwrap();
+    assert!(diff.contains("-Welcome!"));
+    assert!(diff.contains("+Go for launch"));
+    assert_eq!(preview["changed"], json!(true));
+
+    // nothing is saved
+    let section = client
+        .with_base("/v1/section")
+        .get(&sections_id[0])
+        .get_body_object();
+    assert_eq!(section["content"], json!("Welcome!"));
+    let thread = client.with_base(BASE).get(thread_id).get_body_object();
+    assert_eq!(thread["events_id"], json!([]));
+
+    // sections must be in the thread
+    client
+        .with_base(&thread_url)
+        .post_to(
+            "preview",
+            Some(&user_token),
+            json!({ "sections": [{ "id": -1, "content": "Nope" }] }),
+        )
+        .assert_unprocessable_entity();
+
+    client
+        .with_base(&thread_url)
+        .post_to("preview", Some(&other_user_token), json!({}))
+        .assert_unauthorized();
+
+    // teardown
+    for id in &sections_id {
+        client
+            .with_base("/v1/section")
+            .delete(Some(&user_token), id);
+    }
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+    user::delete(&mut client, other_user_id);
+}
+
+#[test]
+fn lifecycle() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let now = chrono::Utc::now().timestamp();
+    let thread = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "thread_name": guid!(), "display_name": guid!(), "draft": true }),
+        )
+        .assert_created()
+        .get_body_object();
+    let thread_id = thread["id"].as_i64().unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    assert_eq!(thread["state"], json!("draft"));
+
+    // test
+    // only permitted transitions can be made
+    client
+        .with_base(&thread_url)
+        .post_to("state", Some(&user_token), json!({ "state": "live" }))
+        .assert_unprocessable_entity();
+
+    // scheduling requires a time
+    client
+        .with_base(&thread_url)
+        .post_to("state", Some(&user_token), json!({ "state": "scheduled" }))
+        .assert_unprocessable_entity();
+    let thread = client
+        .with_base(&thread_url)
+        .post_to(
+            "state",
+            Some(&user_token),
+            json!({ "state": "scheduled", "scheduled_at": now + 3600 }),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["state"], json!("scheduled"));
+    assert_eq!(thread["scheduled_at"], json!(now + 3600));
+
+    // the schedule is cleared once posted
+    let thread = client
+        .with_base(&thread_url)
+        .post_to("state", Some(&user_token), json!({ "state": "posted" }))
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["state"], json!("posted"));
+    assert_eq!(thread["scheduled_at"], json!(null));
+
+    // `is_live` follows the state, and cannot be changed directly
+    let thread = client
+        .with_base(&thread_url)
+        .post_to("state", Some(&user_token), json!({ "state": "live" }))
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["is_live"], json!(true));
+    client
+        .with_base(BASE)
+        .patch(Some(&user_token), thread_id, json!({ "is_live": false }))
+        .assert_unprocessable_entity();
+
+    let live_threads = client
+        .with_base(BASE)
+        .get_all_with_query("state=live")
+        .assert_ok()
+        .get_body_array();
+    assert!(live_threads
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|thread| thread["state"] == json!("live")));
+
+    let thread = client
+        .with_base(&thread_url)
+        .post_to("state", Some(&user_token), json!({ "state": "concluded" }))
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["is_live"], json!(false));
+
+    // conflicting options are rejected on creation
+    client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "thread_name": guid!(),
+                "display_name": guid!(),
+                "draft": true,
+                "scheduled_at": now,
+            }),
+        )
+        .assert_unprocessable_entity();
+
+    // teardown
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn publish() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "thread_name": guid!(), "display_name": guid!(), "draft": true }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+
+    // test
+    // a subreddit is required to publish
+    client
+        .with_base(&thread_url)
+        .post_to("publish", Some(&user_token), json!({}))
+        .assert_unprocessable_entity();
+    let thread = client
+        .with_base(BASE)
+        .get_as(&user_token, thread_id)
+        .get_body_object();
+    assert_eq!(thread["state"], json!("draft"));
+    assert_eq!(thread["post_id"], json!(null));
+
+    // teardown
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn publish_submits_content() {
+    let publisher = Arc::new(RecordingPublisher::default());
+    let mut client = Client::with_services(Services {
+        publisher: Arc::clone(&publisher) as Arc<dyn Publisher>,
+        ..Services::default()
+    });
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_name = guid!();
+    let thread_id = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "thread_name": thread_name, "display_name": guid!(), "draft": true }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({
+                "name": "Overview",
+                "content": "Go for launch",
+                "in_thread_id": thread_id,
+            }),
+        )
+        .assert_created();
+    let markdown = client
+        .with_base(&thread_url)
+        .get_as(&user_token, "render")
+        .assert_ok()
+        .get_body_string();
+    assert!(markdown.contains("Go for launch"));
+
+    // test
+    // the fully rendered thread is submitted to the chosen subreddit
+    let thread = client
+        .with_base(&thread_url)
+        .post_to(
+            "publish",
+            Some(&user_token),
+            json!({ "subreddit": "spacex" }),
+        )
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(
+        publisher.submissions(),
+        vec![Submission {
+            user_id,
+            subreddit: "spacex".into(),
+            title: thread_name,
+            markdown,
+        }]
+    );
+
+    // the post is recorded on the thread
+    assert_eq!(thread["state"], json!("posted"));
+    assert_eq!(thread["post_id"], json!("post0"));
+    assert_eq!(thread["subreddit"], json!("spacex"));
+    let thread = client
+        .with_base(BASE)
+        .get(thread_id)
+        .assert_ok()
+        .get_body_object();
+    assert_eq!(thread["post_id"], json!("post0"));
+    assert_eq!(thread["subreddit"], json!("spacex"));
+
+    // a thread is only posted once
+    client
+        .with_base(&thread_url)
+        .post_to("publish", Some(&user_token), json!({}))
+        .assert_unprocessable_entity();
+    assert_eq!(publisher.submissions().len(), 1);
+
+    // teardown
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn create_submits_content() {
+    let publisher = Arc::new(RecordingPublisher::default());
+    let mut client = Client::with_services(Services {
+        publisher: Arc::clone(&publisher) as Arc<dyn Publisher>,
+        ..Services::default()
+    });
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let template = client
+        .with_base("/v1/template")
+        .post(
+            Some(&user_token),
+            json!({
+                "name": guid!(),
+                "thread_name_pattern": "{name} Launch Thread",
+                "sections": [{ "name": "Overview", "content": "Welcome!" }],
+            }),
+        )
+        .assert_created()
+        .get_body_object();
+
+    // test
+    // a thread posted immediately is submitted complete with its content
+    let thread = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "display_name": "Starlink 5",
+                "subreddit": "spacex",
+                "is_live": true,
+                "template_id": template["id"],
+            }),
+        )
+        .assert_created()
+        .get_body_object();
+    assert_eq!(thread["state"], json!("live"));
+    assert_eq!(thread["post_id"], json!("post0"));
+    let submissions = publisher.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].user_id, user_id);
+    assert_eq!(submissions[0].subreddit, "spacex");
+    assert_eq!(submissions[0].title, "Starlink 5 Launch Thread");
+    assert!(submissions[0].markdown.contains("Welcome!"));
+
+    // a thread Reddit rejects is not created
+    let mut failing_client = Client::with_services(Services {
+        publisher: Arc::new(FailingPublisher),
+        ..Services::default()
+    });
+    failing_client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({
+                "display_name": "Starlink 6",
+                "subreddit": "spacex",
+                "template_id": template["id"],
+            }),
+        )
+        .assert_bad_gateway();
+    let threads = client
+        .with_base(BASE)
+        .get_as(&user_token, format!("?created_by_user_id={}", user_id))
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(threads.len(), 1);
+
+    // teardown
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), &thread["sections_id"][0]);
+    client
+        .with_base(BASE)
+        .delete(Some(&user_token), &thread["id"]);
+    client
+        .with_base("/v1/template")
+        .delete(Some(&user_token), &template["id"]);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn drafts_are_private() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let thread_id = client
+        .with_base(BASE)
+        .post(
+            Some(&user_token),
+            json!({ "thread_name": guid!(), "display_name": guid!(), "draft": true }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let thread_url = format!("{}/{}", BASE, thread_id);
+    let query = format!("?created_by_user_id={}", user_id);
+    let section_id = client
+        .with_base("/v1/section")
+        .post(Some(&user_token), json!({ "in_thread_id": thread_id }))
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let event_id = client
+        .with_base("/v1/event")
+        .post(
+            Some(&user_token),
+            json!({ "cols": [], "in_thread_id": thread_id }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let contents_query = format!("?in_thread_id={}", thread_id);
+
+    // test
+    // the creator can see the draft and its contents
+    client
+        .with_base(BASE)
+        .get_as(&user_token, thread_id)
+        .assert_ok();
+    for &(base, id) in &[("/v1/section", section_id), ("/v1/event", event_id)] {
+        client.with_base(base).get_as(&user_token, id).assert_ok();
+        let values = client
+            .with_base(base)
+            .get_as(&user_token, &contents_query)
+            .assert_ok()
+            .get_body_array();
+        assert_eq!(values[0]["id"], json!(id));
+    }
+    let threads = client
+        .with_base(BASE)
+        .get_as(&user_token, &query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(threads[0]["id"], json!(thread_id));
+
+    // nobody else can
+    client.with_base(BASE).get(thread_id).assert_not_found();
+    client
+        .with_base(BASE)
+        .get_as(&other_user_token, thread_id)
+        .assert_not_found();
+    for path in &["full", "export", "render", "events/export", "clock"] {
+        client.with_base(&thread_url).get(path).assert_not_found();
+    }
+    client
+        .with_base(&thread_url)
+        .post_to(
+            "clone",
+            Some(&other_user_token),
+            json!({ "thread_name": guid!() }),
+        )
+        .assert_not_found();
+    let threads = client
+        .with_base(BASE)
+        .get_as(&other_user_token, &query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(threads, json!([]));
+    let threads = client
+        .with_base(BASE)
+        .get(&query)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(threads, json!([]));
+    for &(base, id) in &[("/v1/section", section_id), ("/v1/event", event_id)] {
+        client.with_base(base).get(id).assert_not_found();
+        client
+            .with_base(base)
+            .get_as(&other_user_token, id)
+            .assert_not_found();
+        let values = client
+            .with_base(base)
+            .get(&contents_query)
+            .assert_ok()
+            .get_body_array();
+        assert_eq!(values, json!([]));
+        let values = client
+            .with_base(base)
+            .get_as(&other_user_token, &contents_query)
+            .assert_ok()
+            .get_body_array();
+        assert_eq!(values, json!([]));
+    }
+
+    // until it is no longer a draft
+    client
+        .with_base(&thread_url)
+        .post_to("state", Some(&user_token), json!({ "state": "posted" }))
+        .assert_ok();
+    client.with_base(BASE).get(thread_id).assert_ok();
+    client.with_base("/v1/section").get(section_id).assert_ok();
+    client.with_base("/v1/event").get(event_id).assert_ok();
+
+    // teardown
+    client.with_base(BASE).delete(Some(&user_token), thread_id);
+    user::delete(&mut client, other_user_id);
+    user::delete(&mut client, user_id);
+}
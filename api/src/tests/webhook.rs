@@ -0,0 +1,319 @@
+use crate::{
+    controller::{deliver, WebhookError},
+    tests::helpers::{webhook::StandIn, *},
+};
+use serde_json::json;
+use std::time::Duration;
+
+const BASE: &str = "/v1/thread";
+
+/// Retry almost immediately, so as to not slow down the tests.
+const DELAYS: [Duration; 2] = [Duration::from_millis(1), Duration::from_millis(1)];
+
+#[test]
+fn deliver_retries() {
+    let stand_in = StandIn::new(&[500, 429]);
+
+    let result = deliver(
+        &reqwest::Client::new(),
+        &stand_in.url,
+        &json!({ "content": "foo" }),
+        &DELAYS,
+    );
+
+    assert_eq!(Ok(()), result);
+    assert_eq!(vec![json!({ "content": "foo" }); 3], stand_in.wait_for(3));
+}
+
+#[test]
+fn deliver_gives_up() {
+    let stand_in = StandIn::new(&[500, 502, 503]);
+
+    let result = deliver(
+        &reqwest::Client::new(),
+        &stand_in.url,
+        &json!({ "content": "foo" }),
+        &DELAYS,
+    );
+
+    assert_eq!(Err(WebhookError::Status(503)), result);
+    assert_eq!(3, stand_in.wait_for(3).len());
+}
+
+#[test]
+fn deliver_does_not_retry_client_errors() {
+    let stand_in = StandIn::new(&[404]);
+
+    let result = deliver(
+        &reqwest::Client::new(),
+        &stand_in.url,
+        &json!({ "content": "foo" }),
+        &DELAYS,
+    );
+
+    assert_eq!(Err(WebhookError::Status(404)), result);
+    assert_eq!(1, stand_in.wait_for(1).len());
+}
+
+#[test]
+fn set() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let (other_user_id, other_user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let webhooks = json!([
+        {
+            "service": "discord",
+            "url": "https://discordapp.com/api/webhooks/1/foo",
+            "events": true,
+            "sections_id": [],
+        },
+    ]);
+
+    // test
+    client
+        .with_base(BASE)
+        .put(
+            &other_user_token,
+            format!("{}/webhooks", thread_id),
+            &webhooks,
+        )
+        .assert_unauthorized();
+    client
+        .with_base(BASE)
+        .put(
+            &user_token,
+            format!("{}/webhooks", thread_id),
+            json!([{ "service": "discord", "url": "discordapp.com" }]),
+        )
+        .assert_unprocessable_entity();
+
+    // only URLs provided by the service are accepted
+    for url in &[
+        "http://discordapp.com/api/webhooks/1/foo",
+        "https://discordapp.com:8443/api/webhooks/1/foo",
+        "https://discordapp.com/api/users/1",
+        "https://hooks.slack.com/services/x",
+        "https://169.254.169.254/api/webhooks/1/foo",
+        "https://discordapp.com.example.com/api/webhooks/1/foo",
+    ] {
+        client
+            .with_base(BASE)
+            .put(
+                &user_token,
+                format!("{}/webhooks", thread_id),
+                json!([{ "service": "discord", "url": url }]),
+            )
+            .assert_unprocessable_entity();
+    }
+    client
+        .with_base(BASE)
+        .put(
+            &user_token,
+            format!("{}/webhooks", thread_id),
+            json!([{
+                "service": "slack",
+                "url": "https://hooks.slack.com/services/x",
+                "sections_id": [0],
+            }]),
+        )
+        .assert_unprocessable_entity();
+
+    let body = client
+        .with_base(BASE)
+        .put(&user_token, format!("{}/webhooks", thread_id), &webhooks)
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(webhooks, body);
+
+    let body = client
+        .with_base(BASE)
+        .get_as(&user_token, format!("{}/webhooks", thread_id))
+        .assert_ok()
+        .get_body_array();
+    assert_eq!(webhooks, body);
+
+    // The URLs are secret, so are neither returned with the thread nor changed with it.
+    client
+        .with_base(BASE)
+        .get_as(&other_user_token, format!("{}/webhooks", thread_id))
+        .assert_unauthorized();
+    let thread = client
+        .with_base(BASE)
+        .get(thread_id)
+        .assert_ok()
+        .get_body_object();
+    assert!(thread.get("webhooks").is_none());
+    client
+        .with_base(BASE)
+        .patch(
+            Some(&user_token),
+            thread_id,
+            json!({ "webhooks": webhooks }),
+        )
+        .assert_unprocessable_entity();
+
+    // teardown
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, other_user_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn event_posted() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let display_name = client.with_base(BASE).get(thread_id).get_body_object()["display_name"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    let discord = StandIn::new(&[]);
+    let slack = StandIn::new(&[]);
+    client
+        .with_base(BASE)
+        .put(
+            &user_token,
+            format!("{}/webhooks", thread_id),
+            json!([
+                { "service": "discord", "url": discord.url },
+                { "service": "slack", "url": slack.url },
+            ]),
+        )
+        .assert_ok();
+
+    // test
+    let unposted_id = client
+        .with_base("/v1/event")
+        .post(
+            Some(&user_token),
+            json!({
+                "posted": false,
+                "cols": [1_546_305_090, "T+0:30", "bar"],
+                "in_thread_id": thread_id,
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let posted_id = client
+        .with_base("/v1/event")
+        .post(
+            Some(&user_token),
+            json!({
+                "posted": true,
+                "cols": [1_546_305_060, "T+0:00", "foo"],
+                "in_thread_id": thread_id,
+            }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+
+    assert_eq!(
+        vec![json!({
+            "content": format!(
+                "**{}**\n**UTC:** 01:11\n**Countdown:** T+0:00\n**Update:** foo",
+                display_name
+            ),
+        })],
+        discord.wait_for(1)
+    );
+    assert_eq!(
+        vec![json!({
+            "text": format!("*{}*\n*UTC:* 01:11\n*Countdown:* T+0:00\n*Update:* foo", display_name),
+        })],
+        slack.wait_for(1)
+    );
+
+    client
+        .with_base("/v1/event")
+        .patch(Some(&user_token), unposted_id, json!({ "posted": true }))
+        .assert_ok();
+
+    assert_eq!(
+        json!({
+            "content": format!(
+                "**{}**\n**UTC:** 01:11\n**Countdown:** T+0:30\n**Update:** bar",
+                display_name
+            ),
+        }),
+        discord.wait_for(2)[1]
+    );
+
+    // teardown
+    for id in &[unposted_id, posted_id] {
+        client
+            .with_base("/v1/event")
+            .delete(Some(&user_token), id)
+            .assert_no_content();
+    }
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
+
+#[test]
+fn section_changed() {
+    let mut client = Client::new();
+
+    // setup
+    let (user_id, user_token) = user::create(&mut client);
+    let thread_id = thread::create(&mut client, &user_token);
+    let display_name = client.with_base(BASE).get(thread_id).get_body_object()["display_name"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    let section_id = client
+        .with_base("/v1/section")
+        .post(
+            Some(&user_token),
+            json!({ "in_thread_id": thread_id, "name": "Weather" }),
+        )
+        .assert_created()
+        .get_body_object()["id"]
+        .as_i64()
+        .unwrap();
+    let discord = StandIn::new(&[]);
+    client
+        .with_base(BASE)
+        .put(
+            &user_token,
+            format!("{}/webhooks", thread_id),
+            json!([{
+                "service": "discord",
+                "url": discord.url,
+                "events": false,
+                "sections_id": [section_id],
+            }]),
+        )
+        .assert_ok();
+
+    // test
+    client
+        .with_base("/v1/section")
+        .patch(
+            Some(&user_token),
+            section_id,
+            json!({ "content": "80% GO" }),
+        )
+        .assert_ok();
+
+    assert_eq!(
+        vec![json!({ "content": format!("**{}**\n# Weather\n80% GO", display_name) })],
+        discord.wait_for(1)
+    );
+
+    // teardown
+    client
+        .with_base("/v1/section")
+        .delete(Some(&user_token), section_id)
+        .assert_no_content();
+    thread::delete(&mut client, &user_token, thread_id);
+    user::delete(&mut client, user_id);
+}
@@ -18,6 +18,10 @@ pub enum Room {
     User,
     #[display(fmt = "thread_create")]
     ThreadCreate,
+    #[display(fmt = "preset")]
+    Preset,
+    #[display(fmt = "template")]
+    Template,
     #[display(fmt = "thread_{}", _0)]
     Thread(i32),
 }
@@ -29,6 +33,8 @@ impl std::str::FromStr for Room {
         match string {
             "user" => Ok(Room::User),
             "thread_create" => Ok(Room::ThreadCreate),
+            "preset" => Ok(Room::Preset),
+            "template" => Ok(Room::Template),
             room if room.starts_with("thread_") => match room["thread_".len()..].parse() {
                 Ok(id) => Ok(Room::Thread(id)),
                 Err(_) => Err("invalid thread id"),
@@ -39,25 +45,35 @@ impl std::str::FromStr for Room {
 }
 
 /// What action is the `data` field representing in a `Message`?
-#[derive(Debug, Display)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     #[display(fmt = "create")]
     Create,
+    #[display(fmt = "create_many")]
+    CreateMany,
     #[display(fmt = "update")]
     Update,
     #[display(fmt = "delete")]
     Delete,
+    #[display(fmt = "batch")]
+    Batch,
+    #[display(fmt = "transition")]
+    Transition,
 }
 
 /// What type is the `data` field in a `Message`?
-#[derive(Debug, Display)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
 pub enum DataType {
     #[display(fmt = "event")]
     Event,
+    #[display(fmt = "preset_event")]
+    PresetEvent,
     #[display(fmt = "section")]
     Section,
     #[display(fmt = "thread")]
     Thread,
+    #[display(fmt = "thread_template")]
+    ThreadTemplate,
     #[display(fmt = "user")]
     User,
 }
@@ -108,9 +124,14 @@ impl<T: Serialize> Message<'_, T> {
 
 /// Use this struct to add an `id` field to a preexisting struct.
 /// The fields will be flattened by serde.
+///
+/// For types that are versioned,
+/// the `version` field contains the version _after_ the update was applied.
 #[derive(Serialize, Constructor, Debug)]
 pub struct Update<'a, T: Serialize> {
-    pub id: i32,
+    pub id:      i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
     #[serde(flatten)]
-    pub data: &'a T,
+    pub data:    &'a T,
 }
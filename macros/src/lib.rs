@@ -215,6 +215,7 @@ pub fn generate_structs(item: TokenStream) -> TokenStream {
         }
 
         #[derive(
+            Clone,
             Default,
             serde::Serialize,
             serde::Deserialize,
@@ -0,0 +1,10 @@
+-- Incremented on every update,
+-- allowing clients to detect (and reject) concurrent modifications.
+ALTER TABLE thread
+ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE section
+ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE event
+ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,20 @@
+ALTER TABLE thread
+ADD COLUMN event_column_headers TEXT[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE thread
+ADD COLUMN space__utc_col_index SMALLINT;
+
+UPDATE thread
+SET event_column_headers = ARRAY(
+        SELECT col.value->>'name'
+        FROM jsonb_array_elements(event_columns) WITH ORDINALITY AS col(value, index)
+        ORDER BY col.index
+    ),
+    space__utc_col_index = (
+        SELECT MIN(col.index) - 1
+        FROM jsonb_array_elements(event_columns) WITH ORDINALITY AS col(value, index)
+        WHERE col.value->>'type' = 'utc'
+    );
+
+ALTER TABLE thread
+DROP COLUMN event_columns;
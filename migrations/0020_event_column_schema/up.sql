@@ -0,0 +1,31 @@
+-- Each column is an object containing its name and type,
+-- along with whether it is required and its default value.
+ALTER TABLE thread
+ADD COLUMN event_columns JSONB NOT NULL DEFAULT '[]'::jsonb;
+
+-- Previously, all columns were required,
+-- with the designated UTC column (if any) being the only non-text column.
+UPDATE thread
+SET event_columns = (
+    SELECT COALESCE(
+        jsonb_agg(
+            jsonb_build_object(
+                'name', col.name,
+                'type', CASE
+                    WHEN col.index - 1 = space__utc_col_index THEN 'utc'
+                    ELSE 'text'
+                END,
+                'required', TRUE
+            )
+            ORDER BY col.index
+        ),
+        '[]'::jsonb
+    )
+    FROM unnest(event_column_headers) WITH ORDINALITY AS col(name, index)
+);
+
+ALTER TABLE thread
+DROP COLUMN event_column_headers;
+
+ALTER TABLE thread
+DROP COLUMN space__utc_col_index;
@@ -0,0 +1 @@
+DROP TABLE preset_event;
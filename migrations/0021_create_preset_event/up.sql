@@ -0,0 +1,17 @@
+-- Presets allow commonly used events (such as "Liftoff!") to be reused between threads.
+-- A preset belongs to a subreddit if `subreddit` is set,
+-- and is otherwise private to the user that created it.
+CREATE TABLE preset_event (
+  id SERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  cols JSONB NOT NULL DEFAULT '[]'::jsonb,
+  subreddit VARCHAR(255),
+  created_by_user_id INTEGER NOT NULL,
+  version INTEGER NOT NULL DEFAULT 1
+);
+
+ALTER TABLE preset_event
+ADD CONSTRAINT preset_event_created_by_fk
+FOREIGN KEY (created_by_user_id)
+REFERENCES "user"
+ON DELETE CASCADE;
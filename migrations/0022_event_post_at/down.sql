@@ -0,0 +1,4 @@
+DROP INDEX event_scheduled_idx;
+
+ALTER TABLE event
+DROP COLUMN post_at;
@@ -0,0 +1,8 @@
+-- When an unposted event should be posted automatically.
+-- Either `{"utc": <timestamp>}` or `{"t_minus": <seconds relative to T-0>}`.
+ALTER TABLE event
+ADD COLUMN post_at JSONB;
+
+-- The scheduler only ever looks at events that are waiting to be posted.
+CREATE INDEX event_scheduled_idx ON event (in_thread_id)
+WHERE NOT posted AND post_at IS NOT NULL;
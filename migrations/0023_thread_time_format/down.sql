@@ -0,0 +1,2 @@
+ALTER TABLE thread
+DROP COLUMN time_format;
@@ -0,0 +1,4 @@
+-- How timestamps are displayed, such as the strftime pattern and time zone.
+-- Omitted options take their defaults, which match the previous `HH:MM` in UTC.
+ALTER TABLE thread
+ADD COLUMN time_format JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,3 @@
+-- The state of the countdown (holds and scrubs), in addition to `space__t0`.
+ALTER TABLE thread
+ADD COLUMN clock JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,3 @@
+-- The launch data last applied to the thread, along with any conflicting manual edits.
+ALTER TABLE thread
+ADD COLUMN launch_sync JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,2 @@
+ALTER TABLE section
+DROP COLUMN stats;
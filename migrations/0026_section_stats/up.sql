@@ -0,0 +1,3 @@
+-- A table of key/value pairs, rendered before the content (if present).
+ALTER TABLE section
+ADD COLUMN stats JSONB;
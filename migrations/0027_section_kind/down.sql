@@ -0,0 +1,11 @@
+ALTER TABLE section
+ADD COLUMN is_events_section BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN stats JSONB;
+
+UPDATE section SET is_events_section = TRUE WHERE kind = 'events';
+UPDATE section SET stats = data WHERE kind = 'stats';
+
+-- Any other kinds are lost, becoming plain markdown.
+ALTER TABLE section
+DROP COLUMN kind,
+DROP COLUMN data;
@@ -0,0 +1,12 @@
+-- Every section has a kind, determining how its `data` is validated and rendered.
+-- This replaces both `is_events_section` and the optional `stats`.
+ALTER TABLE section
+ADD COLUMN kind VARCHAR NOT NULL DEFAULT 'markdown',
+ADD COLUMN data JSONB NOT NULL DEFAULT 'null'::jsonb;
+
+UPDATE section SET kind = 'events' WHERE is_events_section;
+UPDATE section SET kind = 'stats', data = stats WHERE stats IS NOT NULL;
+
+ALTER TABLE section
+DROP COLUMN is_events_section,
+DROP COLUMN stats;
@@ -0,0 +1,22 @@
+-- Templates capture the common setup of a kind of thread (such as a launch thread),
+-- so that new threads can be created with their sections and event schema in one step.
+-- A template belongs to a subreddit if `subreddit` is set,
+-- and is otherwise private to the user that created it.
+CREATE TABLE thread_template (
+  id SERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  thread_name_pattern VARCHAR(300) NOT NULL DEFAULT '{name}',
+  display_name_pattern VARCHAR(300) NOT NULL DEFAULT '{name}',
+  event_columns JSONB NOT NULL DEFAULT '[]'::jsonb,
+  time_format JSONB NOT NULL DEFAULT '{}'::jsonb,
+  sections JSONB NOT NULL DEFAULT '[]'::jsonb,
+  subreddit VARCHAR(255),
+  created_by_user_id INTEGER NOT NULL,
+  version INTEGER NOT NULL DEFAULT 1
+);
+
+ALTER TABLE thread_template
+ADD CONSTRAINT thread_template_created_by_fk
+FOREIGN KEY (created_by_user_id)
+REFERENCES "user"
+ON DELETE CASCADE;
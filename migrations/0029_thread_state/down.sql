@@ -0,0 +1,3 @@
+ALTER TABLE thread
+DROP COLUMN state,
+DROP COLUMN scheduled_at;
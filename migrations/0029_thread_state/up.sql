@@ -0,0 +1,8 @@
+-- Threads move through explicit states, from a draft to being archived.
+-- `is_live` is kept in sync with the `live` state.
+-- Existing threads have all been posted.
+ALTER TABLE thread
+ADD COLUMN state VARCHAR NOT NULL DEFAULT 'draft',
+ADD COLUMN scheduled_at BIGINT;
+
+UPDATE thread SET state = CASE WHEN is_live THEN 'live' ELSE 'posted' END;
@@ -0,0 +1,4 @@
+-- Discord and Slack incoming webhooks, sent events as they are posted
+-- and designated sections as they are changed.
+ALTER TABLE thread
+ADD COLUMN webhooks JSONB NOT NULL DEFAULT '[]'::jsonb;